@@ -0,0 +1,120 @@
+//! Synthesis of the compiled R1CS into arkworks, for arkworks-based SNARKs
+//! such as Groth16 or Marlin.
+//!
+//! The public inputs then the public outputs are the instance, every other
+//! witness (including product witnesses) is private. A bare
+//! `CircuitStructure` leaves the assignment missing, which is enough for the
+//! setup; proving needs an `ArkCircuit` carrying the witness of a step.
+
+use ark_ff::PrimeField as ArkPrimeField;
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, SynthesisError, Variable,
+};
+use ff::PrimeField;
+use ivc_program::{
+    program::{Term, WitnessID, LC},
+    witness::Witness,
+};
+use std::collections::BTreeMap;
+
+use crate::{field::ff_to_ark_prime_field, program::CircuitStructure};
+
+/// A circuit with the witness of a step, as returned by the execution
+pub struct ArkCircuit<F> {
+    pub structure: CircuitStructure<F>,
+    pub witness: Witness<F>,
+}
+
+pub(crate) fn to_ark<F: PrimeField, AF: ArkPrimeField>(value: &F) -> Result<AF, SynthesisError> {
+    // Note: conversion only fails on a field mismatch
+    ff_to_ark_prime_field(value).map_err(|_| SynthesisError::Unsatisfiable)
+}
+
+fn to_ark_lc<F: PrimeField, AF: ArkPrimeField>(
+    lc: &LC<F>,
+    variables: &BTreeMap<WitnessID, Variable>,
+) -> Result<LinearCombination<AF>, SynthesisError> {
+    lc.0.iter()
+        .map(|term| match term {
+            Term::LC {
+                coefficient,
+                var_id,
+            } => Ok((to_ark(coefficient)?, variables[var_id])),
+            Term::Const(value) => Ok((to_ark(value)?, Variable::One)),
+        })
+        .collect::<Result<_, _>>()
+        .map(LinearCombination)
+}
+
+/// Allocate the witnesses missing from `variables` as private and enforce
+/// the constraints, returning the variables of all witnesses
+pub(crate) fn enforce_r1cs<F: PrimeField, AF: ArkPrimeField>(
+    structure: &CircuitStructure<F>,
+    witness: Option<&Witness<F>>,
+    cs: &ConstraintSystemRef<AF>,
+    mut variables: BTreeMap<WitnessID, Variable>,
+) -> Result<BTreeMap<WitnessID, Variable>, SynthesisError> {
+    let template = structure
+        .try_r1cs_template()
+        .map_err(|_| SynthesisError::Unsatisfiable)?;
+    let num_witness = template
+        .num_witness()
+        .map_err(|_| SynthesisError::Unsatisfiable)?;
+
+    for id in (0..num_witness).map(WitnessID) {
+        if !variables.contains_key(&id) {
+            variables.insert(id, cs.new_witness_variable(|| value(witness, &id))?);
+        }
+    }
+
+    for constraint in &template.constraints {
+        cs.enforce_constraint(
+            to_ark_lc(&constraint.a, &variables)?,
+            to_ark_lc(&constraint.b, &variables)?,
+            to_ark_lc(&constraint.c, &variables)?,
+        )?;
+    }
+
+    Ok(variables)
+}
+
+pub(crate) fn value<F: PrimeField, AF: ArkPrimeField>(
+    witness: Option<&Witness<F>>,
+    id: &WitnessID,
+) -> Result<AF, SynthesisError> {
+    witness
+        .and_then(|witness| witness.0.get(id))
+        .ok_or(SynthesisError::AssignmentMissing)
+        .and_then(to_ark)
+}
+
+fn synthesize<F: PrimeField, AF: ArkPrimeField>(
+    structure: &CircuitStructure<F>,
+    witness: Option<&Witness<F>>,
+    cs: ConstraintSystemRef<AF>,
+) -> Result<(), SynthesisError> {
+    let io = &structure.program;
+    let mut variables = BTreeMap::new();
+
+    for id in io.public_inputs.iter().chain(io.public_outputs.iter()) {
+        if !variables.contains_key(id) {
+            variables.insert(*id, cs.new_input_variable(|| value(witness, id))?);
+        }
+    }
+
+    enforce_r1cs(structure, witness, &cs, variables)?;
+
+    Ok(())
+}
+
+impl<F: PrimeField, AF: ArkPrimeField> ConstraintSynthesizer<AF> for CircuitStructure<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<AF>) -> Result<(), SynthesisError> {
+        synthesize(&self, None, cs)
+    }
+}
+
+impl<F: PrimeField, AF: ArkPrimeField> ConstraintSynthesizer<AF> for ArkCircuit<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<AF>) -> Result<(), SynthesisError> {
+        synthesize(&self.structure, Some(&self.witness), cs)
+    }
+}
@@ -0,0 +1,95 @@
+//! Concurrent execution of independent steps of one circuit.
+
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+};
+
+use acvm::{acir::acir_field::GenericFieldElement, blackbox_solver::BlackBoxFunctionSolver};
+use ark_ff::PrimeField as ArkPrimeField;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::PrimeField;
+use ivc_program::witness::Witness;
+
+use crate::{
+    execute::UnexecutedCircuit, oracle::NoOracle, program::CircuitStructure, Error, ExecutionResult,
+};
+
+/// Solves many `(public_input, private_input)` pairs of the same circuit on
+/// a bounded number of threads, e.g. to precompute hints or for benchmarks
+pub struct BatchExecutor<F> {
+    structure: Arc<CircuitStructure<F>>,
+    num_threads: usize,
+}
+
+impl<F: PrimeField> BatchExecutor<F> {
+    /// One thread per available core
+    pub fn new(structure: impl Into<Arc<CircuitStructure<F>>>) -> Self {
+        Self {
+            structure: structure.into(),
+            num_threads: std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    pub fn with_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = num_threads.max(1);
+        self
+    }
+
+    /// Execute every pair as the step numbered by its position, returning
+    /// the results in the order of `inputs`
+    pub fn execute<AF: ArkPrimeField>(
+        &self,
+        inputs: &[(Witness<F>, Witness<F>)],
+    ) -> Vec<Result<(ExecutionResult<F>, Witness<F>), Error>>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.num_threads.min(inputs.len()) {
+                let sender = sender.clone();
+                let next = &next;
+
+                scope.spawn(move || {
+                    // Note: reused, so the converted opcodes are shared by
+                    // the steps of a thread
+                    let mut circuit = UnexecutedCircuit::new(
+                        0,
+                        Witness(Default::default()),
+                        self.structure.clone(),
+                    );
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((public_input, private_input)) = inputs.get(index) else {
+                            break;
+                        };
+
+                        circuit.iteration_number = index as u64;
+                        circuit.public_input = public_input.clone();
+                        let result = circuit.execute_ref::<AF, _, _>(
+                            private_input.clone(),
+                            &mut NoOracle,
+                            &Bn254BlackBoxSolver,
+                        );
+
+                        if sender.send((index, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        drop(sender);
+
+        let mut results: Vec<_> = receiver.into_iter().collect();
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
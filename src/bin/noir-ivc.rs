@@ -0,0 +1,509 @@
+//! Command line driver of the pipeline of noir-ivc, over BN254.
+//!
+//! `noir-ivc compile <artifact.json> --out target/noir-ivc/` compiles a
+//! Nargo artifact into `noir_ivc_program.json` and `ivc_program.json`.
+//!
+//! `noir-ivc execute --program <noir_ivc_program.json> --input io_0.json
+//! --hints hints/ --steps N` executes `N` steps, step `k` taking the private
+//! input `hints/hint_k.json`. It writes the files of `ExecutionSession` to
+//! `--out`: `step_k.wit`, `step_k.res` and `io_{k+1}.json`. With `--from-step k`,
+//! the chain resumes at step `k`, e.g. from `--input target/noir-ivc/io_k.json`.
+//!
+//! `noir-ivc inspect <artifact.json>` describes a Nargo artifact, as JSON
+//! with `--json`: its opcodes, IO, R1CS size and whether it is supported.
+//!
+//! `noir-ivc prove --program <noir_ivc_program.json> --steps-dir
+//! target/noir-ivc --steps N` folds the executed steps and compresses the
+//! proof, writing `proof.bin` and the claim it proves, `proof.json`.
+//! `noir-ivc verify --program <noir_ivc_program.json> --proof-dir <dir>`
+//! checks the proof is of the program and reaches the claimed final state.
+//! Proofs use IPA over BN254 and Grumpkin, so the verifier key is derived
+//! again from the program, without a trusted setup. Both subcommands need the
+//! `nova` feature.
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use ivc_program::{program::IOProfile, witness::Witness};
+use noir_ivc::{
+    compile,
+    inputs::{parse_io, read_io},
+    inspect_file, load_program_from_file,
+    session::{read_json, write_json},
+    CircuitStructure, Error, ExecutionSession,
+};
+
+type F = halo2curves::bn256::Fr;
+type AF = ark_bn254::Fr;
+
+#[derive(Parser)]
+#[command(
+    name = "noir-ivc",
+    version,
+    about = "Incrementally verifiable Noir programs"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compile a Nargo artifact into a noir-ivc program and an IVC program
+    Compile {
+        /// The program compiled by `nargo compile`
+        artifact: PathBuf,
+        /// Output directory
+        #[arg(long, default_value = "target/noir-ivc")]
+        out: PathBuf,
+    },
+    /// Execute steps of a compiled program, writing their witnesses and results
+    Execute {
+        /// The `noir_ivc_program.json` written by `compile`
+        #[arg(long)]
+        program: PathBuf,
+        /// Public input of the first step, a list of field elements or the
+        /// `io_k.json` of a previous execution
+        #[arg(long)]
+        input: PathBuf,
+        /// Directory of the private inputs `hint_k.json` of every step `k`
+        #[arg(long)]
+        hints: PathBuf,
+        /// Number of steps
+        #[arg(long)]
+        steps: u64,
+        /// Number of the first step
+        #[arg(long, default_value_t = 0)]
+        from_step: u64,
+        /// Output directory
+        #[arg(long, default_value = "target/noir-ivc")]
+        out: PathBuf,
+    },
+    /// Describe the program of a Nargo artifact
+    Inspect {
+        /// The program compiled by `nargo compile`
+        artifact: PathBuf,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Fold executed steps into a compressed proof
+    #[cfg(feature = "nova")]
+    Prove {
+        /// The `noir_ivc_program.json` written by `compile`
+        #[arg(long)]
+        program: PathBuf,
+        /// Directory of the `step_k.wit` and `step_k.res` written by `execute`
+        #[arg(long, default_value = "target/noir-ivc")]
+        steps_dir: PathBuf,
+        /// Number of steps
+        #[arg(long)]
+        steps: u64,
+        /// Number of the first step
+        #[arg(long, default_value_t = 0)]
+        from_step: u64,
+        /// Output directory
+        #[arg(long, default_value = "target/noir-ivc")]
+        out: PathBuf,
+    },
+    /// Verify a proof written by `prove`
+    #[cfg(feature = "nova")]
+    Verify {
+        /// The `noir_ivc_program.json` the proof is expected to be of
+        #[arg(long)]
+        program: PathBuf,
+        /// Directory of `proof.bin` and `proof.json`
+        #[arg(long, default_value = "target/noir-ivc")]
+        proof_dir: PathBuf,
+        /// Expected final state, a list of field elements, instead of the
+        /// one of `proof.json`
+        #[arg(long)]
+        final_state: Option<PathBuf>,
+    },
+}
+
+/// The files of an execution in `out`
+// Note: the project is the working directory, as for `nargo`
+fn session(out: &Path) -> ExecutionSession {
+    ExecutionSession::new(".").with_output_dir(out)
+}
+
+/// A list of field elements, or else a witness as written by `execute`
+fn read_public_input(path: &Path, io_profile: &IOProfile) -> Result<Witness<F>, Error> {
+    let text = std::fs::read_to_string(path)?;
+    match parse_io::<F>(&text) {
+        Ok(io) => Ok(io.make_witness(io_profile)),
+        Err(_) => Ok(serde_json::from_str(&text).map_err(std::io::Error::from)?),
+    }
+}
+
+fn read_structure(program: &Path) -> Result<CircuitStructure<F>, Error> {
+    let structure: CircuitStructure<F> = read_json(program)?;
+    structure.check_curve()?;
+    Ok(structure)
+}
+
+fn run_compile(artifact: &Path, out: &Path) -> Result<(), Error> {
+    let noir_circuit = load_program_from_file::<AF, _>(artifact)?;
+    let (structure, program) = compile::<F, AF>(noir_circuit)?;
+
+    let files = session(out);
+    std::fs::create_dir_all(files.output_dir())?;
+    write_json(&files.structure_path(), &structure)?;
+    write_json(&files.ivc_program_path(), &program)?;
+
+    println!(
+        "{} constraints, {} witnesses, written to {}",
+        structure.num_constraints()?,
+        program.num_witness,
+        out.display()
+    );
+    Ok(())
+}
+
+fn run_execute(
+    program: &Path,
+    input: &Path,
+    hints: &Path,
+    steps: u64,
+    from_step: u64,
+    out: &Path,
+) -> Result<(), Error> {
+    let structure = read_structure(program)?;
+    let io_profile = structure.program.io.clone();
+
+    let first_public_input = read_public_input(input, &io_profile)?;
+    let private_inputs = (from_step..from_step + steps)
+        .map(|k| {
+            let io = read_io::<F, _>(hints.join(format!("hint_{}.json", k)))?;
+            Ok(io.make_witness(&io_profile))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let results = session(out).execute::<F, AF>(
+        structure,
+        first_public_input,
+        from_step,
+        private_inputs.into_iter(),
+    )?;
+    for result in results {
+        println!(
+            "step {}: solved in {} ms",
+            result.iteration_number, result.stats.solve_ms
+        );
+    }
+    Ok(())
+}
+
+fn run_inspect(artifact: &Path, json: bool) -> Result<(), Error> {
+    let metadata = inspect_file::<F, AF, _>(artifact)?;
+    if json {
+        let text = serde_json::to_string_pretty(&metadata).map_err(std::io::Error::from)?;
+        println!("{}", text);
+    } else {
+        println!("{}", metadata);
+    }
+    Ok(())
+}
+
+/// The `prove` and `verify` subcommands
+#[cfg(feature = "nova")]
+mod proving {
+    use std::{
+        fs::File,
+        io::{BufReader, BufWriter, Write},
+        path::Path,
+        sync::Arc,
+    };
+
+    use ivc_program::{input::IO, witness::Witness};
+    use noir_ivc::{
+        inputs::read_io,
+        nova_scotia::create_public_params,
+        prove::{
+            finalize, prove_steps, read_artifact, setup_compression, verify_compressed,
+            write_artifact, ArtifactKind, NoirCompressedSNARK,
+        },
+        session::{read_json, write_json},
+        verify_chain, Error, ExecutionResult,
+    };
+    use nova_snark::{
+        provider::{ipa_pc, Bn256EngineIPA, GrumpkinEngine},
+        spartan::snark::RelaxedR1CSSNARK,
+    };
+    use serde::{Deserialize, Serialize};
+
+    use super::{read_structure, session, F};
+
+    type E1 = Bn256EngineIPA;
+    type E2 = GrumpkinEngine;
+    type S1 = RelaxedR1CSSNARK<E1, ipa_pc::EvaluationEngine<E1>>;
+    type S2 = RelaxedR1CSSNARK<E2, ipa_pc::EvaluationEngine<E2>>;
+
+    fn invalid(message: &str) -> Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, message).into()
+    }
+
+    /// What a proof proves, written next to it
+    #[derive(Serialize, Deserialize)]
+    struct ProofClaim {
+        program_digest: String,
+        num_steps: usize,
+        start_state: Vec<F>,
+        final_state: Vec<F>,
+    }
+
+    pub(super) fn run_prove(
+        program: &Path,
+        steps_dir: &Path,
+        steps: u64,
+        from_step: u64,
+        out: &Path,
+    ) -> Result<(), Error> {
+        let structure = Arc::new(read_structure(program)?);
+        let digest = structure.digest()?;
+        let steps_files = session(steps_dir);
+
+        let mut results: Vec<ExecutionResult<F>> = vec![];
+        let mut witnesses: Vec<Witness<F>> = vec![];
+        for k in from_step..from_step + steps {
+            let result = steps_files.read_result::<F>(k)?;
+            if let Some(expected) = &result.program_digest {
+                if *expected != digest {
+                    return Err(Error::DigestMismatch {
+                        expected: expected.clone(),
+                        found: digest,
+                    });
+                }
+            }
+            results.push(result);
+            witnesses.push(steps_files.read_witness(k)?);
+        }
+        verify_chain(&results, &structure.program.io)?;
+
+        let values = |witness: &Witness<F>| witness.0.values().cloned().collect::<Vec<F>>();
+        let (first, last) = match (results.first(), results.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(invalid("no step to prove")),
+        };
+        let claim = ProofClaim {
+            program_digest: digest,
+            num_steps: results.len(),
+            start_state: values(&first.public_input),
+            final_state: values(&last.public_output),
+        };
+
+        let pp = create_public_params::<E1, E2>(structure.clone())?;
+        let steps = results.into_iter().zip(witnesses).map(|(result, witness)| {
+            let next = IO(values(&result.public_output));
+            Ok((result, witness, next))
+        });
+        let recursive_snark = prove_steps(structure, &pp, steps)?;
+
+        let (pk, _) = setup_compression::<E1, E2, S1, S2>(&pp)?;
+        let proof = finalize(&pp, &pk, &recursive_snark)?;
+
+        let out = session(out);
+        std::fs::create_dir_all(out.output_dir())?;
+        let mut writer = BufWriter::new(File::create(out.proof_path())?);
+        write_artifact(&mut writer, ArtifactKind::CompressedSNARK, &proof)?;
+        writer.flush()?;
+        write_json(&out.proof_claim_path(), &claim)?;
+
+        println!("proved {} steps", claim.num_steps);
+        Ok(())
+    }
+
+    pub(super) fn run_verify(
+        program: &Path,
+        proof_dir: &Path,
+        final_state: Option<&Path>,
+    ) -> Result<(), Error> {
+        let structure = Arc::new(read_structure(program)?);
+        let proof_files = session(proof_dir);
+        let claim: ProofClaim = read_json(&proof_files.proof_claim_path())?;
+
+        let found = structure.digest()?;
+        if claim.program_digest != found {
+            return Err(Error::DigestMismatch {
+                expected: claim.program_digest,
+                found,
+            });
+        }
+
+        let reader = BufReader::new(File::open(proof_files.proof_path())?);
+        let proof: NoirCompressedSNARK<E1, E2, S1, S2> =
+            read_artifact(reader, ArtifactKind::CompressedSNARK)?;
+
+        let pp = create_public_params::<E1, E2>(structure)?;
+        let (_, vk) = setup_compression::<E1, E2, S1, S2>(&pp)?;
+        let state = verify_compressed(&proof, &vk, claim.num_steps, &claim.start_state)?;
+
+        let expected = match final_state {
+            Some(path) => read_io::<F, _>(path)?.0,
+            None => claim.final_state,
+        };
+        if state != expected {
+            return Err(invalid("the proof does not reach the expected final state"));
+        }
+
+        println!("valid proof of {} steps", claim.num_steps);
+        Ok(())
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match &cli.command {
+        Command::Compile { artifact, out } => run_compile(artifact, out),
+        Command::Execute {
+            program,
+            input,
+            hints,
+            steps,
+            from_step,
+            out,
+        } => run_execute(program, input, hints, *steps, *from_step, out),
+        Command::Inspect { artifact, json } => run_inspect(artifact, *json),
+        #[cfg(feature = "nova")]
+        Command::Prove {
+            program,
+            steps_dir,
+            steps,
+            from_step,
+            out,
+        } => proving::run_prove(program, steps_dir, *steps, *from_step, out),
+        #[cfg(feature = "nova")]
+        Command::Verify {
+            program,
+            proof_dir,
+            final_state,
+        } => proving::run_verify(program, proof_dir, final_state.as_deref()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noir_ivc::{verify_chain, ExecutionResult};
+
+    use super::*;
+
+    const ARTIFACT_PATH: &str = "test_folder/invert/target/invert.json";
+    const INPUT_PATH: &str = "test_folder/invert/inputs/io_0.json";
+    const HINTS_DIR: &str = "test_folder/invert/inputs";
+
+    /// A fresh output directory for a test
+    fn out_dir(name: &str) -> PathBuf {
+        let out = std::env::temp_dir().join(format!("noir_ivc_cli_{}", name));
+        let _ = std::fs::remove_dir_all(&out);
+        out
+    }
+
+    #[test]
+    fn test_compile() {
+        let out = out_dir("compile");
+        let files = session(&out);
+        run_compile(Path::new(ARTIFACT_PATH), &out).unwrap();
+
+        let structure = read_structure(&files.structure_path()).unwrap();
+        let program = files.read_ivc_program::<F>().unwrap();
+        assert_eq!(
+            serde_json::to_value(&program).unwrap(),
+            serde_json::to_value(&structure.program).unwrap()
+        );
+        assert!(structure.num_constraints().unwrap() > 0);
+
+        assert!(run_compile(
+            Path::new("test_folder/missing.json"),
+            &out_dir("compile_missing")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let out = out_dir("execute");
+        let files = session(&out);
+        run_compile(Path::new(ARTIFACT_PATH), &out).unwrap();
+        let program = files.structure_path();
+
+        run_execute(
+            &program,
+            Path::new(INPUT_PATH),
+            Path::new(HINTS_DIR),
+            2,
+            0,
+            &out,
+        )
+        .unwrap();
+        let results: Vec<ExecutionResult<F>> =
+            (0..2).map(|k| files.read_result(k).unwrap()).collect();
+        assert_eq!(results[1].iteration_number, 1);
+        files.read_witness::<F>(1).unwrap();
+        verify_chain(&results, &read_structure(&program).unwrap().program.io).unwrap();
+
+        // resume at step 1 from the written public input
+        let resumed = out_dir("execute_resumed");
+        run_execute(
+            &program,
+            &files.io_path(1),
+            Path::new(HINTS_DIR),
+            1,
+            1,
+            &resumed,
+        )
+        .unwrap();
+        let result = session(&resumed).read_result::<F>(1).unwrap();
+        assert_eq!(result.public_output.0, results[1].public_output.0);
+        assert!(!session(&resumed).result_path(0).exists());
+
+        assert!(run_execute(
+            &program,
+            Path::new(INPUT_PATH),
+            Path::new(HINTS_DIR),
+            3,
+            0,
+            &out,
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "nova")]
+    #[test]
+    fn test_prove_verify() {
+        let out = out_dir("prove");
+        let files = session(&out);
+        run_compile(Path::new(ARTIFACT_PATH), &out).unwrap();
+        let program = files.structure_path();
+        run_execute(
+            &program,
+            Path::new(INPUT_PATH),
+            Path::new(HINTS_DIR),
+            2,
+            0,
+            &out,
+        )
+        .unwrap();
+
+        proving::run_prove(&program, &out, 2, 0, &out).unwrap();
+        assert!(files.proof_path().exists());
+        proving::run_verify(&program, &out, None).unwrap();
+
+        // the proof does not reach the public input of the first step
+        assert!(proving::run_verify(&program, &out, Some(Path::new(INPUT_PATH))).is_err());
+        // nor proves more steps than were executed
+        assert!(proving::run_prove(&program, &out, 3, 0, &out_dir("prove_missing")).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Blinding of the step artifacts written to disk.
+//!
+//! With a secret key, every value of a witness, and every private value of
+//! a result (including the commitment to its private input), is stored plus
+//! a mask derived from the key, a nonce, the step and the position of the
+//! value. Without the key, the files of a step reveal no more than its
+//! public inputs and outputs, stored as is.
+//!
+//! The nonce is drawn afresh for every step written, so the files of two
+//! runs over the same key are masked independently.
+//!
+//! Note: the proofs of nova-snark are not zero-knowledge, a proof reveals
+//! as much as the unblinded witnesses of its steps
+
+use ff::PrimeField;
+use ivc_program::witness::Witness;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::{field::ff_from_le_bytes, ExecutionResult, OracleValue};
+
+/// Drawn for every step blinded, and stored next to its files
+pub type BlindingNonce = [u8; 32];
+
+#[derive(Clone)]
+pub struct Blinding {
+    key: [u8; 32],
+}
+
+impl Blinding {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key }
+    }
+
+    /// A fresh random nonce
+    pub fn nonce() -> BlindingNonce {
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+        nonce
+    }
+
+    fn mask<F: PrimeField>(
+        &self,
+        nonce: &BlindingNonce,
+        step: u64,
+        domain: &[u8],
+        index: u64,
+    ) -> F {
+        // Note: 64 bytes reduced, so the mask is close to uniform
+        let bytes: Vec<u8> = [0u8, 1]
+            .into_iter()
+            .flat_map(|half| {
+                let mut hasher = Sha256::new();
+                hasher.update(b"noir-ivc blinding v2");
+                hasher.update(self.key);
+                hasher.update(nonce);
+                hasher.update(step.to_le_bytes());
+                hasher.update(domain);
+                hasher.update(index.to_le_bytes());
+                hasher.update([half]);
+                hasher.finalize()
+            })
+            .collect();
+        ff_from_le_bytes(&bytes).unwrap()
+    }
+
+    fn apply_witness<F: PrimeField>(
+        &self,
+        nonce: &BlindingNonce,
+        step: u64,
+        domain: &[u8],
+        witness: &mut Witness<F>,
+        sign: F,
+    ) {
+        for (id, value) in witness.0.iter_mut() {
+            *value += sign * self.mask::<F>(nonce, step, domain, id.0 as u64);
+        }
+    }
+
+    fn apply_result<F: PrimeField>(
+        &self,
+        nonce: &BlindingNonce,
+        result: &mut ExecutionResult<F>,
+        sign: F,
+    ) {
+        let step = result.iteration_number;
+        let private_input = &mut result.private_input;
+        self.apply_witness(nonce, step, b"private input", private_input, sign);
+        let private_output = &mut result.private_output;
+        self.apply_witness(nonce, step, b"private output", private_output, sign);
+
+        // Note: a commitment to few possible inputs could be matched against
+        // guessed inputs
+        if let Some(commitment) = &mut result.input_commitment {
+            *commitment += sign * self.mask::<F>(nonce, step, b"input commitment", 0);
+        }
+        if let Some(randomness) = &mut result.input_randomness {
+            *randomness += sign * self.mask::<F>(nonce, step, b"input randomness", 0);
+        }
+
+        let values = result
+            .oracle_calls
+            .iter_mut()
+            .flat_map(|call| call.inputs.iter_mut().chain(call.outputs.iter_mut()))
+            .flat_map(|value| match value {
+                OracleValue::Single(value) => std::slice::from_mut(value),
+                OracleValue::Array(values) => values.as_mut_slice(),
+            });
+        for (index, value) in values.enumerate() {
+            *value += sign * self.mask::<F>(nonce, step, b"oracle", index as u64);
+        }
+    }
+
+    pub fn blind_witness<F: PrimeField>(
+        &self,
+        nonce: &BlindingNonce,
+        step: u64,
+        witness: &Witness<F>,
+    ) -> Witness<F> {
+        let mut witness = Witness(witness.0.clone());
+        self.apply_witness(nonce, step, b"witness", &mut witness, F::ONE);
+        witness
+    }
+
+    pub fn unblind_witness<F: PrimeField>(
+        &self,
+        nonce: &BlindingNonce,
+        step: u64,
+        witness: &Witness<F>,
+    ) -> Witness<F> {
+        let mut witness = Witness(witness.0.clone());
+        self.apply_witness(nonce, step, b"witness", &mut witness, -F::ONE);
+        witness
+    }
+
+    /// Blind the private values of `result`. The transcript is dropped, as
+    /// its hashes could be matched against guessed inputs
+    pub fn blind_result<F: PrimeField>(
+        &self,
+        nonce: &BlindingNonce,
+        result: &ExecutionResult<F>,
+    ) -> ExecutionResult<F> {
+        let mut result = result.clone();
+        result.transcript = None;
+        self.apply_result(nonce, &mut result, F::ONE);
+        result
+    }
+
+    pub fn unblind_result<F: PrimeField>(
+        &self,
+        nonce: &BlindingNonce,
+        result: &ExecutionResult<F>,
+    ) -> ExecutionResult<F> {
+        let mut result = result.clone();
+        self.apply_result(nonce, &mut result, -F::ONE);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ivc_program::program::WitnessID;
+
+    use super::*;
+    use crate::OracleCall;
+
+    type F = halo2curves::bn256::Fr;
+
+    fn witness(values: &[u64]) -> Witness<F> {
+        Witness(
+            values
+                .iter()
+                .enumerate()
+                .map(|(id, value)| (WitnessID(id as u32), F::from(*value)))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn test_blinding() {
+        let blinding = Blinding::new([7; 32]);
+        let nonce = Blinding::nonce();
+
+        let original = witness(&[1, 2, 3]);
+        let blinded = blinding.blind_witness(&nonce, 0, &original);
+        assert!(blinded
+            .0
+            .values()
+            .zip(original.0.values())
+            .all(|(a, b)| a != b));
+        assert_eq!(blinding.unblind_witness(&nonce, 0, &blinded).0, original.0);
+        assert_ne!(blinding.unblind_witness(&nonce, 1, &blinded).0, original.0);
+        assert_ne!(
+            Blinding::new([8; 32]).blind_witness(&nonce, 0, &original).0,
+            blinded.0
+        );
+
+        // Note: a rerun over the same key is masked with another nonce, else
+        // the difference of two runs would not be masked
+        let rerun = blinding.blind_witness(&Blinding::nonce(), 0, &original);
+        assert_ne!(rerun.0, blinded.0);
+
+        let result = ExecutionResult {
+            iteration_number: 3,
+            public_input: witness(&[1]),
+            private_input: witness(&[2, 3]),
+            public_output: witness(&[4]),
+            private_output: witness(&[5]),
+            oracle_calls: vec![OracleCall {
+                function: "hint".to_string(),
+                inputs: vec![OracleValue::Single(F::from(6))],
+                outputs: vec![OracleValue::Array(vec![F::from(7), F::from(8)])],
+            }],
+            program_digest: None,
+            stats: Default::default(),
+            transcript: None,
+            input_commitment: Some(F::from(9)),
+            input_randomness: Some(F::from(10)),
+        };
+
+        let blinded = blinding.blind_result(&nonce, &result);
+        assert_eq!(blinded.public_input.0, result.public_input.0);
+        assert_eq!(blinded.public_output.0, result.public_output.0);
+        assert_ne!(blinded.private_input.0, result.private_input.0);
+        assert_ne!(blinded.private_output.0, result.private_output.0);
+        assert!(matches!(
+            blinded.oracle_calls[0].inputs[0],
+            OracleValue::Single(value) if value != F::from(6)
+        ));
+        assert_ne!(blinded.input_commitment, result.input_commitment);
+        assert_ne!(blinded.input_randomness, result.input_randomness);
+
+        let unblinded = blinding.unblind_result(&nonce, &blinded);
+        assert_eq!(unblinded.private_input.0, result.private_input.0);
+        assert_eq!(unblinded.private_output.0, result.private_output.0);
+        assert!(matches!(
+            &unblinded.oracle_calls[0].outputs[0],
+            OracleValue::Array(values) if *values == [F::from(7), F::from(8)]
+        ));
+        assert_eq!(unblinded.input_commitment, result.input_commitment);
+        assert_eq!(unblinded.input_randomness, result.input_randomness);
+    }
+}
@@ -0,0 +1,101 @@
+//! Unconstrained (Brillig) calls only compute hint witnesses, which are then
+//! constrained by the surrounding opcodes. They contribute no R1CS, so they
+//! are kept in their serialized ACIR form and handed back to ACVM for solving.
+
+use acvm::acir::{
+    acir_field::GenericFieldElement,
+    circuit::{
+        brillig::{BrilligBytecode, BrilligOutputs},
+        Opcode,
+    },
+};
+use ark_ff::PrimeField as ArkPrimeField;
+use ivc_program::program::WitnessID;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BrilligCall {
+    /// Index of the `BrilligCall` in the original opcode list
+    pub position: usize,
+    pub outputs: Vec<WitnessID>,
+    pub opcode: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BrilligHints {
+    pub calls: Vec<BrilligCall>,
+    pub functions: Vec<serde_json::Value>,
+}
+
+impl BrilligCall {
+    pub fn from_opcode<AF: ArkPrimeField>(
+        position: usize,
+        opcode: &Opcode<GenericFieldElement<AF>>,
+    ) -> Result<Self, Error> {
+        let outputs = match opcode {
+            Opcode::BrilligCall { outputs, .. } => outputs
+                .iter()
+                .flat_map(|output| match output {
+                    BrilligOutputs::Simple(witness) => vec![witness.0.into()],
+                    BrilligOutputs::Array(witnesses) => {
+                        witnesses.iter().map(|witness| witness.0.into()).collect()
+                    }
+                })
+                .collect(),
+            _ => {
+                return Err(Error::BrilligError(format!(
+                    "opcode {} is not a BrilligCall",
+                    position
+                )))
+            }
+        };
+
+        Ok(Self {
+            position,
+            outputs,
+            opcode: serde_json::to_value(opcode).map_err(|e| Error::BrilligError(e.to_string()))?,
+        })
+    }
+
+    pub(crate) fn opcode<AF: ArkPrimeField>(
+        &self,
+    ) -> Result<(usize, Opcode<GenericFieldElement<AF>>), Error> {
+        let opcode = serde_json::from_value(self.opcode.clone())
+            .map_err(|e| Error::BrilligError(e.to_string()))?;
+        Ok((self.position, opcode))
+    }
+}
+
+impl BrilligHints {
+    pub fn set_functions<AF: ArkPrimeField>(
+        &mut self,
+        functions: &[BrilligBytecode<GenericFieldElement<AF>>],
+    ) {
+        self.functions = functions
+            .iter()
+            .map(|function| {
+                serde_json::to_value(function).expect("brillig function serialization error")
+            })
+            .collect();
+    }
+
+    pub(crate) fn functions<AF: ArkPrimeField>(
+        &self,
+    ) -> Result<Vec<BrilligBytecode<GenericFieldElement<AF>>>, Error> {
+        self.functions
+            .iter()
+            .map(|function| {
+                serde_json::from_value(function.clone())
+                    .map_err(|e| Error::BrilligError(e.to_string()))
+            })
+            .collect()
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = WitnessID> + '_ {
+        self.calls
+            .iter()
+            .flat_map(|call| call.outputs.iter().cloned())
+    }
+}
@@ -0,0 +1,193 @@
+//! Curves whose scalar field a circuit can be defined over.
+//!
+//! A field is identified by its modulus, so any `ff` or arkworks
+//! implementation of the same field maps to the same curve.
+
+use std::{fmt, str::FromStr};
+
+use ark_ff::{BigInteger, PrimeField as ArkPrimeField};
+use ff::PrimeField;
+use ivc_program::program::get_curve_name;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    /// The field of Noir
+    Bn254,
+    /// Its scalar field is the base field of BN254, e.g. for CycleFold
+    /// secondary circuits
+    Grumpkin,
+    /// First curve of the default Nova cycle
+    Pallas,
+    Vesta,
+    Bls12_381,
+}
+
+impl Curve {
+    pub const ALL: [Curve; 5] = [
+        Curve::Bn254,
+        Curve::Grumpkin,
+        Curve::Pallas,
+        Curve::Vesta,
+        Curve::Bls12_381,
+    ];
+
+    /// Name used by serde
+    pub fn name(&self) -> &'static str {
+        match self {
+            Curve::Bn254 => "bn254",
+            Curve::Grumpkin => "grumpkin",
+            Curve::Pallas => "pallas",
+            Curve::Vesta => "vesta",
+            Curve::Bls12_381 => "bls12_381",
+        }
+    }
+
+    /// Modulus of the scalar field, in hexadecimal
+    pub fn modulus(&self) -> &'static str {
+        match self {
+            Curve::Bn254 => "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001",
+            Curve::Grumpkin => "0x30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47",
+            Curve::Pallas => "0x40000000000000000000000000000000224698fc0994a8dd8c46eb2100000001",
+            Curve::Vesta => "0x40000000000000000000000000000000224698fc094cf91b992d30ed00000001",
+            Curve::Bls12_381 => {
+                "0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001"
+            }
+        }
+    }
+
+    /// Little-endian bytes of the modulus of the scalar field
+    pub fn modulus_le_bytes(&self) -> Vec<u8> {
+        let hex = self.modulus().trim_start_matches("0x").as_bytes();
+        hex.rchunks(2)
+            .map(|digits| u8::from_str_radix(std::str::from_utf8(digits).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn of_modulus(modulus: &[u8]) -> Option<Self> {
+        let len = modulus.len() - modulus.iter().rev().take_while(|b| **b == 0).count();
+        Self::ALL
+            .into_iter()
+            .find(|curve| curve.modulus_le_bytes() == modulus[..len])
+    }
+
+    /// The curve with scalar field `F`
+    pub fn of<F: PrimeField>() -> Option<Self> {
+        let hex = F::MODULUS.trim_start_matches("0x").to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|curve| curve.modulus().trim_start_matches("0x") == hex)
+    }
+
+    /// The curve with scalar field `F`
+    pub fn of_ark<F: ArkPrimeField>() -> Option<Self> {
+        Self::of_modulus(&F::MODULUS.to_bytes_le())
+    }
+
+    /// The curve of `F` as recorded in `IVCProgram::curve`, the type name of
+    /// `F` for a field of no known curve
+    pub fn program_name<F: PrimeField>() -> String {
+        Self::of::<F>().map_or_else(get_curve_name::<F>, |curve| curve.to_string())
+    }
+
+    /// Check `name`, recorded in `IVCProgram::curve`, is the curve of `F`
+    pub fn check_program_name<F: PrimeField>(name: &str) -> Result<(), Error> {
+        // Note: older programs record the type name of `F` for any field
+        if name == get_curve_name::<F>() {
+            return Ok(());
+        }
+
+        let expected = name.parse::<Curve>()?;
+        let found = Self::of::<F>();
+        if found != Some(expected) {
+            return Err(Error::CurveMismatch {
+                expected: Some(expected),
+                found,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Check ACVM solves over `AF` the circuits over `F`
+pub(crate) fn check_acvm_field<F: PrimeField, AF: ArkPrimeField>() -> Result<(), Error> {
+    let expected = Curve::of::<F>();
+    let found = Curve::of_ark::<AF>();
+    if expected != found {
+        return Err(Error::CurveMismatch { expected, found });
+    }
+    Ok(())
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Curve {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        Self::ALL
+            .into_iter()
+            .find(|curve| curve.name() == name)
+            .ok_or_else(|| Error::UnsupportedCurve(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curves() {
+        assert_eq!(Curve::of::<halo2curves::bn256::Fr>(), Some(Curve::Bn254));
+        assert_eq!(Curve::of::<halo2curves::bn256::Fq>(), Some(Curve::Grumpkin));
+        assert_eq!(Curve::of::<halo2curves::pasta::Fq>(), Some(Curve::Pallas));
+        assert_eq!(Curve::of::<halo2curves::pasta::Fp>(), Some(Curve::Vesta));
+
+        assert_eq!(Curve::of_ark::<ark_bn254::Fr>(), Some(Curve::Bn254));
+        assert_eq!(Curve::of_ark::<ark_bn254::Fq>(), Some(Curve::Grumpkin));
+        assert_eq!(Curve::of_ark::<ark_pallas::Fr>(), Some(Curve::Pallas));
+        assert_eq!(Curve::of_ark::<ark_vesta::Fr>(), Some(Curve::Vesta));
+
+        for curve in Curve::ALL {
+            assert_eq!(curve.to_string().parse::<Curve>().unwrap(), curve);
+            assert_eq!(
+                serde_json::to_string(&curve).unwrap(),
+                format!("\"{}\"", curve)
+            );
+        }
+        assert!(matches!(
+            "secp256k1".parse::<Curve>(),
+            Err(Error::UnsupportedCurve(_))
+        ));
+    }
+
+    #[test]
+    fn test_program_name() {
+        type F = halo2curves::bn256::Fr;
+
+        assert_eq!(Curve::program_name::<F>(), "bn254");
+        Curve::check_program_name::<F>("bn254").unwrap();
+        Curve::check_program_name::<F>(&get_curve_name::<F>()).unwrap();
+        assert!(matches!(
+            Curve::check_program_name::<F>("pallas"),
+            Err(Error::CurveMismatch {
+                expected: Some(Curve::Pallas),
+                found: Some(Curve::Bn254),
+            })
+        ));
+        assert!(matches!(
+            Curve::check_program_name::<F>("secp256k1"),
+            Err(Error::UnsupportedCurve(_))
+        ));
+
+        check_acvm_field::<F, ark_bn254::Fr>().unwrap();
+        assert!(check_acvm_field::<F, ark_pallas::Fr>().is_err());
+    }
+}
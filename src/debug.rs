@@ -0,0 +1,120 @@
+//! Noir source locations of the opcodes, from the debug symbols of a compiled
+//! program.
+//!
+//! The artifact stores them compressed (`debug_symbols`, base64 of deflated
+//! JSON) with the sources in `file_map`. Only the innermost location of the
+//! call stack of every ACIR opcode is kept.
+
+use std::{collections::BTreeMap, fmt, io::Read};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based
+    pub column: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path, self.line)
+    }
+}
+
+/// Line and column of the byte `offset` of `source`
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+fn decode_debug_symbols(symbols: &serde_json::Value) -> Result<serde_json::Value, Error> {
+    // Note: older artifacts store the JSON as is
+    let Some(encoded) = symbols.as_str() else {
+        return Ok(symbols.clone());
+    };
+
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| Error::DebugInfoError(e.to_string()))?;
+    let mut json = vec![];
+    flate2::read::DeflateDecoder::new(&compressed[..]).read_to_end(&mut json)?;
+
+    serde_json::from_slice(&json).map_err(|e| Error::DebugInfoError(e.to_string()))
+}
+
+/// Source location of every ACIR opcode of the main function of a compiled
+/// Noir program, by opcode index
+pub fn source_locations(
+    artifact: &serde_json::Value,
+) -> Result<BTreeMap<usize, SourceLocation>, Error> {
+    let debug_symbols = decode_debug_symbols(&artifact["debug_symbols"])?;
+    let locations = debug_symbols["debug_infos"][0]["locations"]
+        .as_object()
+        .ok_or_else(|| Error::DebugInfoError("missing opcode locations".to_string()))?;
+
+    let mut source_map = BTreeMap::new();
+
+    for (opcode_location, call_stack) in locations {
+        // Brillig locations "acir.brillig" point to their call
+        let Ok(index) = opcode_location
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .parse::<usize>()
+        else {
+            continue;
+        };
+        let Some(location) = call_stack.as_array().and_then(|stack| stack.last()) else {
+            continue;
+        };
+
+        let file = &artifact["file_map"][location["file"].to_string()];
+        let (Some(path), Some(source)) = (file["path"].as_str(), file["source"].as_str()) else {
+            continue;
+        };
+        let start = location["span"]["start"].as_u64().unwrap_or_default() as usize;
+        let (line, column) = line_column(source, start);
+
+        source_map.entry(index).or_insert(SourceLocation {
+            path: path.to_string(),
+            line,
+            column,
+        });
+    }
+
+    Ok(source_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_locations() {
+        let source = "fn main(x: Field) {\n    assert(x == 1);\n}\n";
+        let artifact = serde_json::json!({
+            "debug_symbols": {
+                "debug_infos": [{
+                    "locations": {
+                        "0": [{"span": {"start": 24, "end": 38}, "file": 1}],
+                        "1.4": [{"span": {"start": 0, "end": 2}, "file": 1}]
+                    }
+                }]
+            },
+            "file_map": {"1": {"source": source, "path": "src/main.nr"}}
+        });
+
+        let source_map = source_locations(&artifact).unwrap();
+        assert_eq!(source_map[&0].to_string(), "src/main.nr:2");
+        assert_eq!(source_map[&0].column, 5);
+        assert_eq!(source_map[&1].line, 1);
+    }
+}
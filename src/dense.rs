@@ -0,0 +1,104 @@
+//! Dense witnesses.
+//!
+//! Padding numbers the R1CS witnesses contiguously from 0, so a produced
+//! witness is held as a `Vec` indexed by witness while it is built, and only
+//! converted to the sparse `Witness` at the API boundary.
+
+use ivc_program::{program::WitnessID, witness::Witness};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Value of witness `i` at index `i`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DenseWitness<F>(pub Vec<F>);
+
+impl<F> DenseWitness<F> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, id: WitnessID) -> Option<&F> {
+        self.0.get(id.0 as usize)
+    }
+}
+
+impl<F: Clone> DenseWitness<F> {
+    /// Fails on the first witness missing from `0..witness.len()`
+    pub fn from_sparse(witness: &Witness<F>) -> Result<Self, Error> {
+        witness
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, (id, value))| match id.0 as usize == i {
+                true => Ok(value.clone()),
+                false => Err(Error::MissingWitness(i as u32)),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn to_sparse(&self) -> Witness<F> {
+        Witness(
+            self.0
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (WitnessID(i as u32), value.clone()))
+                .collect(),
+        )
+    }
+}
+
+impl<F> From<DenseWitness<F>> for Witness<F> {
+    fn from(witness: DenseWitness<F>) -> Self {
+        Witness(
+            witness
+                .0
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| (WitnessID(i as u32), value))
+                .collect(),
+        )
+    }
+}
+
+impl<F: Clone> TryFrom<&Witness<F>> for DenseWitness<F> {
+    type Error = Error;
+
+    fn try_from(witness: &Witness<F>) -> Result<Self, Error> {
+        Self::from_sparse(witness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let sparse = Witness(
+            [(WitnessID(0), 5u64), (WitnessID(1), 7)]
+                .into_iter()
+                .collect(),
+        );
+        let dense = DenseWitness::from_sparse(&sparse).unwrap();
+
+        assert_eq!(dense.0, vec![5, 7]);
+        assert_eq!(dense.get(WitnessID(1)), Some(&7));
+        assert_eq!(Witness::from(dense).0, sparse.0);
+
+        let gap = Witness(
+            [(WitnessID(0), 5u64), (WitnessID(2), 7)]
+                .into_iter()
+                .collect(),
+        );
+        assert!(matches!(
+            DenseWitness::try_from(&gap),
+            Err(Error::MissingWitness(1))
+        ));
+    }
+}
@@ -1,124 +1,733 @@
-use std::collections::BTreeMap;
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use acvm::{
-    acir::{acir_field::GenericFieldElement, circuit::Opcode, native_types::WitnessMap},
-    blackbox_solver::StubbedBlackBoxSolver,
-    pwg::{ACVMStatus, ACVM},
+    acir::{
+        acir_field::GenericFieldElement,
+        brillig::ForeignCallResult,
+        circuit::{brillig::BrilligBytecode, AssertionPayload, Opcode, OpcodeLocation},
+        native_types::WitnessMap,
+    },
+    blackbox_solver::BlackBoxFunctionSolver,
+    pwg::{
+        ACVMStatus, BrilligSolver, BrilligSolverStatus, ErrorLocation, ForeignCallWaitInfo,
+        OpcodeNotSolvable, OpcodeResolutionError, ResolvedAssertionPayload, StepResult, ACVM,
+    },
 };
 use ark_ff::PrimeField as ArkPrimeField;
 use ff::PrimeField;
 use ivc_program::{program::WitnessID, witness::Witness};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    field::{ff_to_ark_prime_field, generic_ark_ff_to_prime_field},
+    field::{ff_slice_to_ark_prime_field, generic_ark_ff_slice_to_prime_field, ConversionSite},
+    oracle::{OracleCall, OracleResolver},
     program::CircuitStructure,
-    Error, ExecutionResult,
+    r1cs::R1CSTemplate,
+    transcript::TranscriptEntry,
+    Error, ExecutionResult, StepStats,
 };
 
+fn to_witness_map<F: PrimeField, AF: ArkPrimeField>(
+    witness: &BTreeMap<WitnessID, F>,
+) -> Result<WitnessMap<GenericFieldElement<AF>>, Error> {
+    let values: Vec<F> = witness.values().cloned().collect();
+    let values: Vec<AF> = ff_slice_to_ark_prime_field(&values)?;
+
+    let witness: BTreeMap<acvm::acir::native_types::Witness, GenericFieldElement<AF>> = witness
+        .keys()
+        .zip(values)
+        .map(|(witness_id, value)| {
+            let id = acvm::acir::native_types::Witness(witness_id.0);
+            (id, GenericFieldElement::from_repr(value))
+        })
+        .collect();
+
+    Ok(WitnessMap::from(witness))
+}
+
+fn from_witness_map<F: PrimeField, AF: ArkPrimeField>(
+    witness: WitnessMap<GenericFieldElement<AF>>,
+) -> Result<BTreeMap<WitnessID, F>, Error> {
+    let (ids, values): (Vec<_>, Vec<_>) = witness.into_iter().unzip();
+    let values: Vec<F> = generic_ark_ff_slice_to_prime_field(&values)
+        .map_err(|e| e.at_site(ConversionSite::WitnessValue))?;
+
+    Ok(ids
+        .into_iter()
+        .zip(values)
+        .map(|(witness, value)| (witness.0.into(), value))
+        .collect())
+}
+
+/// Turn a failed solve into an error, surfacing Noir assertion messages.
+/// `offset` is the position of the first solved opcode in the circuit.
+fn solve_error<AF: ArkPrimeField>(
+    status: ACVMStatus<GenericFieldElement<AF>>,
+    offset: usize,
+) -> Error {
+    let (location, payload) = match &status {
+        ACVMStatus::Failure(OpcodeResolutionError::UnsatisfiedConstrain {
+            opcode_location,
+            payload: Some(payload),
+        }) => {
+            let location = match opcode_location {
+                ErrorLocation::Resolved(location) => Some(location.clone()),
+                ErrorLocation::Unresolved => None,
+            };
+            (location, payload)
+        }
+        ACVMStatus::Failure(OpcodeResolutionError::BrilligFunctionFailed {
+            call_stack,
+            payload: Some(payload),
+            ..
+        }) => (call_stack.first().cloned(), payload),
+        _ => return Error::ACVMSolveError(format!("{:?}", status)),
+    };
+
+    let message = match payload {
+        ResolvedAssertionPayload::String(message) => message.clone(),
+        ResolvedAssertionPayload::Raw(raw) => format!("{:?}", raw),
+    };
+
+    let opcode_index = location.map(|location| match location {
+        OpcodeLocation::Acir(index) => index + offset,
+        OpcodeLocation::Brillig { acir_index, .. } => acir_index + offset,
+    });
+
+    Error::AssertionFailed {
+        message,
+        opcode_index,
+        location: None,
+    }
+}
+
+/// The opcode ACVM is blocked at, with its unassigned witnesses (in the ACIR
+/// numbering)
+fn stalled_error<AF: ArkPrimeField, B: BlackBoxFunctionSolver<GenericFieldElement<AF>>>(
+    acvm: &ACVM<'_, GenericFieldElement<AF>, B>,
+    segment: &Segment<AF>,
+) -> Error {
+    let position = acvm.instruction_pointer();
+    let witness_map = acvm.witness_map();
+
+    let mut missing_witnesses: BTreeSet<u32> = match segment.opcodes.get(position) {
+        Some(Opcode::AssertZero(expr)) => expr
+            .mul_terms
+            .iter()
+            .flat_map(|(_, left, right)| [*left, *right])
+            .chain(expr.linear_combinations.iter().map(|(_, id)| *id))
+            .filter(|id| !witness_map.contains_key(id))
+            .map(|id| id.0)
+            .collect(),
+        _ => Default::default(),
+    };
+
+    if let ACVMStatus::Failure(OpcodeResolutionError::OpcodeNotSolvable(
+        OpcodeNotSolvable::MissingAssignment(id),
+    )) = acvm.get_status()
+    {
+        missing_witnesses.insert(*id);
+    }
+
+    Error::SolveStalled {
+        opcode_index: segment.offset + position,
+        missing_witnesses: missing_witnesses.into_iter().collect(),
+        location: None,
+    }
+}
+
+/// Opcodes solved between two checks of the cancellation token and the
+/// solve timeout
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+fn is_cancelled(cancel: Option<&AtomicBool>) -> bool {
+    cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+type ForeignCallResolver<'r, AF> = dyn FnMut(
+        &ForeignCallWaitInfo<GenericFieldElement<AF>>,
+    ) -> Result<ForeignCallResult<GenericFieldElement<AF>>, Error>
+    + 'r;
+
+/// When solving should stop
+#[derive(Clone, Copy)]
+struct Deadline<'c> {
+    cancel: Option<&'c AtomicBool>,
+    start: Instant,
+    max_duration: Option<Duration>,
+}
+
+impl Deadline<'_> {
+    fn is_set(&self) -> bool {
+        self.cancel.is_some() || self.max_duration.is_some()
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        if is_cancelled(self.cancel) {
+            return Err(Error::Cancelled);
+        }
+        match self.max_duration {
+            Some(max_duration) if self.start.elapsed() >= max_duration => {
+                Err(Error::SolveTimeout(max_duration))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Same as `ACVM::solve`, checking `deadline` between batches of opcodes,
+/// and of Brillig opcodes within a call, so a call that never returns is
+/// stopped too. Foreign calls of a Brillig call are resolved by `resolve`
+fn solve_cancellable<AF: ArkPrimeField, B: BlackBoxFunctionSolver<GenericFieldElement<AF>>>(
+    acvm: &mut ACVM<'_, GenericFieldElement<AF>, B>,
+    deadline: Deadline,
+    resolve: &mut ForeignCallResolver<AF>,
+) -> Result<ACVMStatus<GenericFieldElement<AF>>, Error> {
+    if !deadline.is_set() {
+        return Ok(acvm.solve());
+    }
+
+    // Note: solving an opcode of a solved circuit panics
+    if !matches!(acvm.get_status(), ACVMStatus::InProgress) {
+        return Ok(acvm.get_status().clone());
+    }
+
+    loop {
+        deadline.check()?;
+
+        for _ in 0..CANCEL_CHECK_INTERVAL {
+            let status = match acvm.step_into_brillig() {
+                StepResult::Status(status) => status,
+                StepResult::IntoBrillig(solver) => solve_brillig(acvm, solver, deadline, resolve)?,
+            };
+            if !matches!(status, ACVMStatus::InProgress) {
+                return Ok(status);
+            }
+        }
+    }
+}
+
+/// Run a Brillig call stepped into by `acvm`, checking `deadline` between
+/// batches of Brillig opcodes
+fn solve_brillig<'a, AF: ArkPrimeField, B: BlackBoxFunctionSolver<GenericFieldElement<AF>>>(
+    acvm: &mut ACVM<'a, GenericFieldElement<AF>, B>,
+    mut solver: BrilligSolver<'a, GenericFieldElement<AF>, B>,
+    deadline: Deadline,
+    resolve: &mut ForeignCallResolver<AF>,
+) -> Result<ACVMStatus<GenericFieldElement<AF>>, Error> {
+    loop {
+        deadline.check()?;
+
+        for _ in 0..CANCEL_CHECK_INTERVAL {
+            match solver.step() {
+                Ok(BrilligSolverStatus::InProgress) => {}
+                Ok(BrilligSolverStatus::Finished) => {
+                    return Ok(acvm.finish_brillig_with_solver(solver))
+                }
+                Ok(BrilligSolverStatus::ForeignCallWait(info)) => {
+                    solver.resolve_pending_foreign_call(resolve(&info)?)
+                }
+                Err(e) => return Ok(ACVMStatus::Failure(e)),
+            }
+        }
+    }
+}
+
+/// Opcodes solved by one ACVM, up to an opcode lowered by a registered
+/// lowering
+struct Segment<AF: ArkPrimeField> {
+    opcodes: Vec<Opcode<GenericFieldElement<AF>>>,
+    assert_messages: Vec<(OpcodeLocation, AssertionPayload<GenericFieldElement<AF>>)>,
+    /// Position of the first opcode in the circuit
+    offset: usize,
+    /// Index of the lowered opcode in `custom`
+    lowered: Option<usize>,
+}
+
+/// Everything the solver needs besides the witness, converted to the field
+/// of the solver on the first step and shared by the next ones.
+// Note: ACVM cannot be reset to a new witness map, so each step still builds
+// one per segment, which only borrows from here
+struct ExecutionContext<AF: ArkPrimeField> {
+    segments: Vec<Segment<AF>>,
+    unconstrained_functions: Vec<BrilligBytecode<GenericFieldElement<AF>>>,
+}
+
+impl<AF: ArkPrimeField> ExecutionContext<AF> {
+    fn new<F: PrimeField>(structure: &CircuitStructure<F>) -> Result<Self, Error> {
+        structure.check_acvm_curve::<AF>()?;
+
+        let mut segments = vec![];
+        let mut offset = 0;
+
+        for (opcodes, lowered) in structure.acir_segments::<AF>()? {
+            let assert_messages = structure.assert_messages::<AF>(offset, opcodes.len())?;
+            let len = opcodes.len() + lowered.is_some() as usize;
+
+            segments.push(Segment {
+                opcodes,
+                assert_messages,
+                offset,
+                lowered,
+            });
+            offset += len;
+        }
+
+        Ok(Self {
+            segments,
+            unconstrained_functions: structure.brillig.functions::<AF>()?,
+        })
+    }
+}
+
+/// State of an execution between two steps, to resume it in another process
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExecutionCheckpoint<F> {
+    pub iteration_number: u64,
+    pub public_input: Witness<F>,
+    /// Digest of the program the execution must resume with
+    pub program_digest: String,
+}
+
+/// Public input of the next step from the result of a step
+pub type NextInput<F> = Arc<dyn Fn(&ExecutionResult<F>) -> Witness<F> + Send + Sync>;
+
+/// Reported once the witness of a step is built
+#[derive(Clone, Debug)]
+pub struct StepProgress {
+    pub index: u64,
+    /// Time spent in ACVM
+    pub solve_ms: u128,
+    /// Number of R1CS constraints, `None` if the structure is not compiled
+    pub constraints: Option<usize>,
+    pub witnesses: usize,
+}
+
+/// Called with the progress of every step, e.g. to drive a progress bar
+pub type OnStep = Arc<dyn Fn(&StepProgress) + Send + Sync>;
+
+/// Commitment to the private input of a step, in witness order, with its
+/// randomness
+type InputCommitment<F> = Arc<dyn Fn(&[F], F) -> F + Send + Sync>;
+
 #[derive(Clone)]
 pub struct UnexecutedCircuit<F> {
     pub iteration_number: u64,
     pub public_input: Witness<F>,
-    pub structure: CircuitStructure<F>,
+
+    // Note: shared, so advancing to the next step does not copy the circuit
+    pub structure: Arc<CircuitStructure<F>>,
+
+    /// Computes the public input of the next step, instead of the public
+    /// output as is
+    pub next_input: Option<NextInput<F>>,
+
+    pub on_step: Option<OnStep>,
+
+    /// Set to abort the execution with `Error::Cancelled`, checked between
+    /// steps and while solving
+    pub cancel: Option<Arc<AtomicBool>>,
+
+    /// Time allowed to solve one step before failing with
+    /// `Error::SolveTimeout`
+    pub max_solve_duration: Option<Duration>,
+
+    /// Evaluate every R1CS constraint on the witness of each step, failing
+    /// with `Error::UnsatisfiedConstraint`
+    pub check_constraints: bool,
+
+    /// Record a `TranscriptEntry` in every result
+    pub transcript: bool,
+
+    // Note: `StateDigest::commit`, type erased as the digest needs the
+    // `nova` feature
+    commit_inputs: Option<InputCommitment<F>>,
+
+    // Note: an `ExecutionContext<AF>`, type erased as `AF` is only known
+    // when executing. It is not rebuilt if `structure` is replaced
+    context: Option<Arc<dyn Any + Send + Sync>>,
+
+    // Note: the R1CS of a structure that is not compiled, generated once for
+    // `check_constraints`. It is not rebuilt if `structure` is replaced
+    r1cs: Option<Arc<R1CSTemplate<F>>>,
 }
 
 impl<F> UnexecutedCircuit<F> {
     pub fn new(
         iteration_number: u64,
         init_public_input: Witness<F>,
-        structure: CircuitStructure<F>,
+        structure: impl Into<Arc<CircuitStructure<F>>>,
     ) -> Self {
         Self {
             iteration_number,
             public_input: init_public_input,
+            structure: structure.into(),
+            next_input: None,
+            on_step: None,
+            cancel: None,
+            max_solve_duration: None,
+            check_constraints: false,
+            transcript: false,
+            commit_inputs: None,
+            context: None,
+            r1cs: None,
+        }
+    }
+
+    /// Save the state before the next step. The `next_input` hook is not
+    /// saved, set it again after `resume`.
+    // Note: compile the structure first, or the digest regenerates the R1CS
+    pub fn checkpoint(&self) -> Result<ExecutionCheckpoint<F>, Error>
+    where
+        F: PrimeField,
+    {
+        Ok(ExecutionCheckpoint {
+            iteration_number: self.iteration_number,
+            public_input: self.public_input.clone(),
+            program_digest: self.structure.digest()?,
+        })
+    }
+
+    /// Resume from `checkpoint`, failing if `structure` is not the circuit
+    /// it was saved with
+    pub fn resume(
+        checkpoint: ExecutionCheckpoint<F>,
+        structure: impl Into<Arc<CircuitStructure<F>>>,
+    ) -> Result<Self, Error>
+    where
+        F: PrimeField,
+    {
+        let structure = structure.into();
+
+        let found = structure.digest()?;
+        if found != checkpoint.program_digest {
+            return Err(Error::DigestMismatch {
+                expected: checkpoint.program_digest,
+                found,
+            });
+        }
+
+        Ok(Self::new(
+            checkpoint.iteration_number,
+            checkpoint.public_input,
             structure,
+        ))
+    }
+
+    /// Compute the public input of every next step with `hook`, e.g. to
+    /// remap the state or inject external data between steps
+    pub fn with_next_input(
+        mut self,
+        hook: impl Fn(&ExecutionResult<F>) -> Witness<F> + Send + Sync + 'static,
+    ) -> Self {
+        self.next_input = Some(Arc::new(hook));
+        self
+    }
+
+    /// Abort the execution once `cancel` is set, e.g. by a server dropping a job
+    pub fn with_cancellation(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Fail a step with `Error::SolveTimeout` once solving it takes longer
+    /// than `max_duration`, e.g. on adversarial inputs.
+    // Note: checked between batches of opcodes and of Brillig opcodes within
+    // a call, so a Brillig call that never returns is interrupted too, but a
+    // single slow foreign call is not
+    pub fn with_max_solve_duration(mut self, max_duration: Duration) -> Self {
+        self.max_solve_duration = Some(max_duration);
+        self
+    }
+
+    /// Check the R1CS witness of every step against the constraints, to catch
+    /// a lowering bug before folding
+    pub fn with_constraint_check(mut self) -> Self {
+        self.check_constraints = true;
+        self
+    }
+
+    /// Record the hashes of every step in its result, to compare the run with
+    /// another one (see `Transcript`)
+    pub fn with_transcript(mut self) -> Self {
+        self.transcript = true;
+        self
+    }
+
+    /// Record the commitment of `digest` to the private input of every step
+    /// in its result, with fresh randomness, to match
+    /// `NoirStepCircuit::with_input_commitment`
+    #[cfg(feature = "nova")]
+    pub fn with_input_commitment(mut self, digest: crate::nova::StateDigest<F>) -> Self
+    where
+        F: PrimeField,
+    {
+        self.commit_inputs = Some(Arc::new(move |input, randomness| {
+            digest.commit(input, randomness)
+        }));
+        self
+    }
+
+    /// Report the progress of every step to `callback`.
+    // Note: with `execute_steps_from`, it is called from the witness worker
+    pub fn with_progress(
+        mut self,
+        callback: impl Fn(&StepProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_step = Some(Arc::new(callback));
+        self
+    }
+}
+
+/// A step solved by ACVM, before its R1CS witness is built
+pub(crate) struct SolvedStep<F> {
+    pub(crate) result: ExecutionResult<F>,
+    /// In the ACIR numbering
+    solved_witness: Witness<F>,
+    /// Public input of the next step
+    pub(crate) next_input: Witness<F>,
+    on_step: Option<OnStep>,
+    check_constraints: bool,
+    /// Checked against if the structure is not compiled
+    r1cs: Option<Arc<R1CSTemplate<F>>>,
+}
+
+impl<F: PrimeField> SolvedStep<F> {
+    /// Build the R1CS witness of the step, independent of the next steps
+    pub(crate) fn finish(
+        self,
+        structure: &CircuitStructure<F>,
+    ) -> Result<(ExecutionResult<F>, Witness<F>), Error> {
+        let witness = structure.make_dense_witness(&self.solved_witness)?;
+        if self.check_constraints {
+            match &self.r1cs {
+                Some(template) => template.check_dense_witness(&witness.0)?,
+                None => structure
+                    .try_r1cs_template()?
+                    .check_dense_witness(&witness.0)?,
+            }
         }
+
+        let mut result = self.result;
+        result.stats.constraints = structure.r1cs.as_ref().map(|r1cs| r1cs.constraints.len());
+        result.stats.witnesses = witness.len();
+
+        if let Some(on_step) = &self.on_step {
+            on_step(&StepProgress {
+                index: result.iteration_number,
+                solve_ms: result.stats.solve_ms,
+                constraints: result.stats.constraints,
+                witnesses: result.stats.witnesses,
+            });
+        }
+
+        Ok((result, witness.into()))
     }
 }
 
 impl<F: PrimeField> UnexecutedCircuit<F> {
-    pub fn execute<AF: ArkPrimeField>(
-        self,
+    pub fn execute<AF, O, B>(
+        mut self,
         private_input: Witness<F>,
-    ) -> Result<(ExecutionResult<F>, Witness<F>, Self), Error> {
-        assert!(self
-            .structure
-            .is_valid_input(&self.public_input, &private_input));
+        oracle: &mut O,
+        blackbox_solver: &B,
+    ) -> Result<(ExecutionResult<F>, Witness<F>, Self), Error>
+    where
+        AF: ArkPrimeField,
+        O: OracleResolver<F>,
+        B: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let solved = self.solve::<AF, _, _>(private_input, oracle, blackbox_solver)?;
+        let (result, witness) = solved.finish(&self.structure)?;
+
+        Ok((result, witness, self))
+    }
 
-        // merge public and private input into one
-        let mut assigned_witness = self.public_input.clone();
-        assigned_witness.0.extend(private_input.0);
+    /// Same as `execute`, advancing `self` to the next step only on success,
+    /// so a failed step can be retried, e.g. with corrected hints
+    pub fn execute_ref<AF, O, B>(
+        &mut self,
+        private_input: Witness<F>,
+        oracle: &mut O,
+        blackbox_solver: &B,
+    ) -> Result<(ExecutionResult<F>, Witness<F>), Error>
+    where
+        AF: ArkPrimeField,
+        O: OracleResolver<F>,
+        B: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        // Note: cheap, the structure and the context are shared
+        let (result, witness, next) =
+            self.clone()
+                .execute::<AF, _, _>(private_input, oracle, blackbox_solver)?;
+        *self = next;
 
-        let initial_witness: Result<_, Error> = assigned_witness
-            .iter()
-            .map(|(witness_id, value)| {
-                let value: AF = ff_to_ark_prime_field(value)?;
-                let id = acvm::acir::native_types::Witness(witness_id.0);
+        Ok((result, witness))
+    }
 
-                Ok((id, GenericFieldElement::from_repr(value)))
-            })
-            .collect();
+    /// Solve the step with ACVM, advancing `self` to the next step only on
+    /// success. The R1CS witness is left to `SolvedStep::finish`
+    pub(crate) fn solve<AF, O, B>(
+        &mut self,
+        private_input: Witness<F>,
+        oracle: &mut O,
+        blackbox_solver: &B,
+    ) -> Result<SolvedStep<F>, Error>
+    where
+        AF: ArkPrimeField,
+        O: OracleResolver<F>,
+        B: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let start = Instant::now();
 
-        let initial_witness: BTreeMap<acvm::acir::native_types::Witness, GenericFieldElement<AF>> =
-            initial_witness?;
+        if is_cancelled(self.cancel.as_deref()) {
+            return Err(Error::Cancelled);
+        }
 
-        let initial_witness = WitnessMap::from(initial_witness);
+        let context = match self
+            .context
+            .clone()
+            .and_then(|context| context.downcast::<ExecutionContext<AF>>().ok())
+        {
+            Some(context) => context,
+            None => Arc::new(ExecutionContext::<AF>::new(&self.structure)?),
+        };
 
-        // Todo: cache
-        let opcodes: Vec<Opcode<GenericFieldElement<AF>>> = self
-            .structure
-            .gates
-            .iter()
-            .cloned()
-            .map(|gate| gate.into())
-            .collect::<Vec<_>>();
+        if self.check_constraints && self.structure.r1cs.is_none() && self.r1cs.is_none() {
+            let template = self.structure.try_r1cs_template()?.into_owned();
+            self.r1cs = Some(Arc::new(template));
+        }
 
-        let mut acvm = ACVM::new(&StubbedBlackBoxSolver, &opcodes, initial_witness, &[], &[]);
+        self.structure
+            .validate_input(&self.public_input, &private_input)?;
 
-        let status = acvm.solve();
-        match status {
-            ACVMStatus::Solved => Ok(()),
-            _ => Err(Error::ACVMSolveError(format!("{:?}", status))),
-        }?;
+        // merge public and private input into one, in the ACIR numbering
+        let mut assigned_witness = self.structure.to_acir_witness(&self.public_input);
+        assigned_witness.extend(self.structure.to_acir_witness(&private_input));
 
-        let solved_witness = acvm.finalize();
+        // constant witnesses are known before solving
+        for (id, value) in &self.structure.constants {
+            assigned_witness.entry(*id).or_insert(*value);
+        }
 
-        let solved_witness: BTreeMap<WitnessID, F> = solved_witness
-            .into_iter()
-            .map(|(witness, value)| {
-                let value = generic_ark_ff_to_prime_field(&value).expect("output fill error");
-                (witness.0.into(), value)
-            })
-            .collect();
+        let initial_witness = to_witness_map::<F, AF>(&assigned_witness)?;
 
-        let solved_witness = Witness(solved_witness);
+        let mut witness_map = initial_witness;
+        let mut oracle_calls = vec![];
+        let mut resolve = |info: &ForeignCallWaitInfo<GenericFieldElement<AF>>| {
+            let (call, result) = OracleCall::resolve(info, oracle)?;
+            oracle_calls.push(call);
+            Ok(result)
+        };
+        let deadline = Deadline {
+            cancel: self.cancel.as_deref(),
+            start,
+            max_duration: self.max_solve_duration,
+        };
+
+        for segment in &context.segments {
+            let mut acvm = ACVM::new(
+                blackbox_solver,
+                &segment.opcodes,
+                witness_map,
+                &context.unconstrained_functions,
+                &segment.assert_messages,
+            );
+
+            loop {
+                match solve_cancellable(&mut acvm, deadline, &mut resolve)? {
+                    ACVMStatus::Solved => break,
+                    ACVMStatus::RequiresForeignCall(info) => {
+                        acvm.resolve_pending_foreign_call(resolve(&info)?);
+                    }
+                    ACVMStatus::Failure(OpcodeResolutionError::OpcodeNotSolvable(_)) => {
+                        return Err(self.structure.locate(stalled_error(&acvm, segment)))
+                    }
+                    status => {
+                        return Err(self.structure.locate(solve_error(status, segment.offset)))
+                    }
+                }
+            }
+
+            witness_map = acvm.finalize();
+
+            if let Some(index) = segment.lowered {
+                let mut witness = from_witness_map(witness_map)?;
+                self.structure
+                    .lowerings
+                    .solve(&self.structure.custom[index], &mut witness)?;
+                witness_map = to_witness_map::<F, AF>(&witness)?;
+            }
+        }
 
-        let public_input = solved_witness.extract_subset(&self.structure.program.public_inputs)?;
-        let private_input =
-            solved_witness.extract_subset(&self.structure.program.private_inputs)?;
-        let public_output =
-            solved_witness.extract_subset(&self.structure.program.public_outputs)?;
-        let private_output =
-            solved_witness.extract_subset(&self.structure.program.private_outputs)?;
+        let mut solved_witness: BTreeMap<WitnessID, F> = from_witness_map(witness_map)?;
 
-        let result = ExecutionResult {
+        for block in &self.structure.memory {
+            block.assign_aux(&mut solved_witness)?;
+        }
+
+        let solved_witness = Witness(solved_witness);
+        let io = &self.structure.program;
+        let subset = |ids| self.structure.solved_subset(&solved_witness, ids);
+
+        let mut result = ExecutionResult {
             iteration_number: self.iteration_number,
-            public_input: public_input.clone(),
-            private_input,
-            public_output: public_output.clone(),
-            private_output,
+            public_input: subset(&io.public_inputs)?,
+            private_input: subset(&io.private_inputs)?,
+            public_output: subset(&io.public_outputs)?,
+            private_output: subset(&io.private_outputs)?,
+            oracle_calls,
+            program_digest: self.structure.digest.clone(),
+            stats: StepStats {
+                solve_ms: start.elapsed().as_millis(),
+                opcodes: context
+                    .segments
+                    .iter()
+                    .map(|segment| segment.opcodes.len() + segment.lowered.is_some() as usize)
+                    .sum(),
+                ..Default::default()
+            },
+            transcript: None,
+            input_commitment: None,
+            input_randomness: None,
         };
+        if self.transcript {
+            result.transcript = Some(TranscriptEntry::new(&result, &solved_witness));
+        }
+        if let Some(commit) = &self.commit_inputs {
+            let private_input: Vec<F> = result.private_input.0.values().cloned().collect();
+            let randomness = F::random(OsRng);
+            result.input_commitment = Some(commit(&private_input, randomness));
+            result.input_randomness = Some(randomness);
+        }
 
-        let step = self.structure.make_step(&solved_witness)?;
-
-        let new_public_input = public_output.make_next_input_witness(&self.structure.program.io);
+        let next_input = match &self.next_input {
+            Some(hook) => hook(&result),
+            None => result.public_output.make_next_input_witness(&io.io),
+        };
 
-        let next = Self {
-            iteration_number: self.iteration_number + 1,
-            public_input: new_public_input,
-            structure: self.structure,
+        let solved = SolvedStep {
+            result,
+            solved_witness,
+            next_input: next_input.clone(),
+            on_step: self.on_step.clone(),
+            check_constraints: self.check_constraints,
+            r1cs: self.r1cs.clone(),
         };
 
-        Ok((result, step.witness, next))
+        self.iteration_number += 1;
+        self.public_input = next_input;
+        self.context = Some(context);
+
+        Ok(solved)
     }
 }
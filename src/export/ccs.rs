@@ -0,0 +1,139 @@
+//! Customizable constraint system (CCS) form of a compiled program, as
+//! consumed by HyperNova-style folding.
+//!
+//! An R1CS is the CCS with `t = 3` matrices `(A, B, C)`, `q = 2` terms with
+//! multisets `{0, 1}` and `{2}`, constants `1` and `-1`, and degree `d = 2`:
+//! `(A·z) ∘ (B·z) - C·z = 0`. Columns are laid out as in `SparseR1CS`.
+
+use ff::PrimeField;
+use serde::{Deserialize, Serialize};
+
+use super::sparse::{SparseMatrix, SparseR1CS};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CCS<F> {
+    /// Number of constraints (rows)
+    pub m: usize,
+    /// Number of columns of `z`, including the constant one
+    pub n: usize,
+    /// Number of public IO columns
+    pub l: usize,
+    /// Number of matrices
+    pub t: usize,
+    /// Number of terms
+    pub q: usize,
+    /// Degree, the largest multiset size
+    pub d: usize,
+
+    pub matrices: Vec<SparseMatrix<F>>,
+    /// Selectors: indices of the matrices multiplied in each term
+    pub multisets: Vec<Vec<usize>>,
+    pub constants: Vec<F>,
+
+    /// Columns of the public IO, inputs then outputs
+    pub public_io: Vec<usize>,
+}
+
+impl<F: PrimeField> From<SparseR1CS<F>> for CCS<F> {
+    fn from(r1cs: SparseR1CS<F>) -> Self {
+        let public_io: Vec<usize> = r1cs
+            .public_inputs
+            .iter()
+            .chain(&r1cs.public_outputs)
+            .cloned()
+            .collect();
+
+        Self {
+            m: r1cs.a.num_rows,
+            n: r1cs.a.num_cols,
+            l: public_io.len(),
+            t: 3,
+            q: 2,
+            d: 2,
+            matrices: vec![r1cs.a, r1cs.b, r1cs.c],
+            multisets: vec![vec![0, 1], vec![2]],
+            constants: vec![F::ONE, -F::ONE],
+            public_io,
+        }
+    }
+}
+
+impl<F: PrimeField> SparseMatrix<F> {
+    /// `M·z`
+    pub fn mul_vector(&self, z: &[F]) -> Vec<F> {
+        let mut res = vec![F::ZERO; self.num_rows];
+        for (row, col, value) in &self.entries {
+            res[*row] += *value * z[*col];
+        }
+        res
+    }
+}
+
+impl<F: PrimeField> CCS<F> {
+    /// Whether `sum_i c_i · ∘_{j ∈ S_i} (M_j·z) = 0`
+    pub fn is_satisfied(&self, z: &[F]) -> bool {
+        if z.len() != self.n {
+            return false;
+        }
+
+        let products: Vec<Vec<F>> = self
+            .matrices
+            .iter()
+            .map(|matrix| matrix.mul_vector(z))
+            .collect();
+
+        (0..self.m).all(|row| {
+            let sum: F = self
+                .multisets
+                .iter()
+                .zip(&self.constants)
+                .map(|(multiset, constant)| {
+                    multiset
+                        .iter()
+                        .fold(*constant, |acc, j| acc * products[*j][row])
+                })
+                .sum();
+            bool::from(sum.is_zero())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_r1cs_to_ccs() {
+        // w0 * w1 = w2, with z = (1, w0, w1, w2)
+        let matrix = |col| SparseMatrix {
+            num_rows: 1,
+            num_cols: 4,
+            entries: vec![(0, col, F::one())],
+        };
+        let r1cs = SparseR1CS {
+            a: matrix(1),
+            b: matrix(2),
+            c: matrix(3),
+            public_inputs: vec![1],
+            private_inputs: vec![2],
+            public_outputs: vec![3],
+            private_outputs: vec![],
+        };
+
+        let ccs = CCS::from(r1cs);
+        assert_eq!(
+            (ccs.m, ccs.n, ccs.l, ccs.t, ccs.q, ccs.d),
+            (1, 4, 2, 3, 2, 2)
+        );
+
+        let z = |values: [u64; 3]| {
+            let mut z = vec![F::one()];
+            z.extend(values.map(F::from));
+            z
+        };
+        assert!(ccs.is_satisfied(&z([2, 3, 6])));
+        assert!(!ccs.is_satisfied(&z([2, 3, 5])));
+    }
+}
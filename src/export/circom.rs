@@ -0,0 +1,170 @@
+//! The circom `.r1cs` binary format, as read by snarkjs and Nova-Scotia.
+//!
+//! Wire 0 is the constant one, followed by the public outputs, the public
+//! inputs, the private inputs and all other witnesses, in witness order.
+//! The label of the wire of witness `i` is `i + 1`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use ff::PrimeField;
+use ivc_program::program::{Term, WitnessID, LC};
+
+use crate::{
+    field::{ff_to_le_bytes, modulus_to_le_bytes},
+    program::CircuitStructure,
+    Error,
+};
+
+const R1CS_MAGIC: &[u8; 4] = b"r1cs";
+const R1CS_VERSION: u32 = 1;
+
+const SECTION_HEADER: u32 = 1;
+const SECTION_CONSTRAINTS: u32 = 2;
+const SECTION_WIRE_TO_LABEL: u32 = 3;
+
+/// Bytes per field element
+pub(crate) fn field_size<F: PrimeField>() -> usize {
+    (F::NUM_BITS as usize).div_ceil(64) * 8
+}
+
+/// Witnesses in wire order, from wire 1 on
+pub fn wire_order<F: PrimeField>(structure: &CircuitStructure<F>) -> Result<Vec<WitnessID>, Error> {
+    let num_witness = structure.try_r1cs_template()?.num_witness()?;
+    let io = &structure.program;
+
+    let mut wires: Vec<WitnessID> = io
+        .public_outputs
+        .iter()
+        .chain(io.public_inputs.iter())
+        .chain(io.private_inputs.iter())
+        .cloned()
+        .collect();
+
+    let placed: BTreeSet<WitnessID> = wires.iter().cloned().collect();
+    wires.extend(
+        (0..num_witness)
+            .map(WitnessID)
+            .filter(|id| !placed.contains(id)),
+    );
+
+    Ok(wires)
+}
+
+pub(crate) fn write_section(
+    writer: &mut impl Write,
+    section_type: u32,
+    content: &[u8],
+) -> Result<(), Error> {
+    writer.write_all(&section_type.to_le_bytes())?;
+    writer.write_all(&(content.len() as u64).to_le_bytes())?;
+    writer.write_all(content)?;
+    Ok(())
+}
+
+fn write_lc<F: PrimeField>(
+    content: &mut Vec<u8>,
+    lc: &LC<F>,
+    wires: &BTreeMap<WitnessID, u32>,
+) -> Result<(), Error> {
+    // terms sorted by wire, merging duplicates
+    let mut terms: BTreeMap<u32, F> = BTreeMap::new();
+
+    for term in &lc.0 {
+        let (wire, coefficient) = match term {
+            Term::LC {
+                coefficient,
+                var_id,
+            } => {
+                let wire = wires.get(var_id).ok_or(Error::MissingWitness(var_id.0))?;
+                (*wire, *coefficient)
+            }
+            Term::Const(value) => (0, *value),
+        };
+        *terms.entry(wire).or_insert(F::ZERO) += coefficient;
+    }
+
+    terms.retain(|_, coefficient| !bool::from(coefficient.is_zero()));
+
+    content.extend((terms.len() as u32).to_le_bytes());
+    for (wire, coefficient) in terms {
+        content.extend(wire.to_le_bytes());
+        content.extend(ff_to_le_bytes(&coefficient, field_size::<F>()));
+    }
+
+    Ok(())
+}
+
+/// Write the compiled R1CS of `structure` in the circom `.r1cs` format
+pub fn write_circom_r1cs<F: PrimeField>(
+    structure: &CircuitStructure<F>,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let n8 = field_size::<F>();
+    let template = structure.try_r1cs_template()?;
+    let io = &structure.program;
+
+    let order = wire_order(structure)?;
+    let wires: BTreeMap<WitnessID, u32> = order
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (*id, i as u32 + 1))
+        .collect();
+
+    let header = {
+        let mut content = vec![];
+        content.extend((n8 as u32).to_le_bytes());
+        content.extend(modulus_to_le_bytes::<F>(n8));
+        content.extend((order.len() as u32 + 1).to_le_bytes());
+        content.extend((io.public_outputs.len() as u32).to_le_bytes());
+        content.extend((io.public_inputs.len() as u32).to_le_bytes());
+        content.extend((io.private_inputs.len() as u32).to_le_bytes());
+        content.extend((template.num_witness()? as u64 + 1).to_le_bytes());
+        content.extend((template.constraints.len() as u32).to_le_bytes());
+        content
+    };
+
+    let constraints = {
+        let mut content = vec![];
+        for constraint in &template.constraints {
+            write_lc(&mut content, &constraint.a, &wires)?;
+            write_lc(&mut content, &constraint.b, &wires)?;
+            write_lc(&mut content, &constraint.c, &wires)?;
+        }
+        content
+    };
+
+    let wire_to_label = {
+        let mut content = vec![];
+        content.extend(0u64.to_le_bytes());
+        for id in &order {
+            content.extend((id.0 as u64 + 1).to_le_bytes());
+        }
+        content
+    };
+
+    writer.write_all(R1CS_MAGIC)?;
+    writer.write_all(&R1CS_VERSION.to_le_bytes())?;
+    writer.write_all(&3u32.to_le_bytes())?;
+
+    write_section(writer, SECTION_HEADER, &header)?;
+    write_section(writer, SECTION_CONSTRAINTS, &constraints)?;
+    write_section(writer, SECTION_WIRE_TO_LABEL, &wire_to_label)?;
+
+    Ok(())
+}
+
+/// Write the compiled R1CS of `structure` to a circom `.r1cs` file
+pub fn to_circom_r1cs<F: PrimeField, P: AsRef<Path>>(
+    structure: &CircuitStructure<F>,
+    path: P,
+) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_circom_r1cs(structure, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,19 @@
+//! Exports of compiled circuits for other provers and toolchains.
+
+mod ccs;
+mod circom;
+#[cfg(feature = "solidity")]
+mod solidity;
+mod sparse;
+mod wtns;
+
+pub use ccs::CCS;
+pub use circom::{to_circom_r1cs, wire_order, write_circom_r1cs};
+#[cfg(feature = "solidity")]
+pub use solidity::{
+    decide_chain, decider_params, decode_calldata, encode_calldata, encode_words, from_word,
+    solidity_verifier, to_word, verifier_key, verify_decided_chain, ChainDecider, ChainNova,
+    ChainProof, DeciderProverParam, DeciderVerifierParam, NovaCycleFoldVerifierKey, PublicIO,
+};
+pub use sparse::{CsrMatrix, IVCProgramExt, SparseMatrix, SparseR1CS};
+pub use wtns::{to_wire_order, to_wtns, write_wtns};
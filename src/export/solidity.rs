@@ -0,0 +1,323 @@
+//! Solidity verifier of a folded chain.
+//!
+//! A chain folded by sonobe's Nova over BN254 and Grumpkin (see
+//! `fold_steps`) is compressed by its Ethereum decider: a Groth16 proof of
+//! the decider circuit and the KZG openings of the final instances. The
+//! contract is the one of sonobe's solidity verifiers for this decider, its
+//! verifier key being made from the decider verifier parameters and the
+//! state length, the number of public inputs of the compiled circuit.
+//!
+//! Every parameter of the contract is a static array of `uint256`, so its
+//! calldata is the function selector followed by 32-byte big-endian words:
+//! the number of steps, the initial state, the final state, then the proof.
+
+use acvm::acir::acir_field::GenericFieldElement;
+use ark_bn254::{constraints::GVar, Bn254, Fr, G1Projective};
+use ark_groth16::Groth16;
+use ark_grumpkin::{constraints::GVar as GVar2, Projective as G2Projective};
+use ark_std::rand::{CryptoRng, RngCore};
+use ff::PrimeField;
+use folding_schemes::{
+    commitment::{kzg::KZG, pedersen::Pedersen},
+    folding::nova::{decider_eth, CommittedInstance, Nova},
+    Decider,
+};
+pub use solidity_verifiers::NovaCycleFoldVerifierKey;
+use solidity_verifiers::{
+    utils::get_function_selector_for_nova_cyclefold_verifier,
+    verifiers::nova_cyclefold::get_decider_template_for_cyclefold_decider,
+};
+
+use crate::{
+    field::{
+        ff_from_canonical_le_bytes, ff_to_ark_prime_field, ff_to_le_bytes,
+        generic_ark_ff_to_prime_field,
+    },
+    program::CircuitStructure,
+    Error,
+};
+
+/// Nova of sonobe, with the commitment schemes its Ethereum decider expects
+pub type ChainNova<F> = Nova<
+    G1Projective,
+    GVar,
+    G2Projective,
+    GVar2,
+    CircuitStructure<F>,
+    KZG<'static, Bn254>,
+    Pedersen<G2Projective>,
+    false,
+>;
+
+/// Ethereum decider of `ChainNova`
+pub type ChainDecider<F> = decider_eth::Decider<
+    G1Projective,
+    GVar,
+    G2Projective,
+    GVar2,
+    CircuitStructure<F>,
+    KZG<'static, Bn254>,
+    Pedersen<G2Projective>,
+    Groth16<Bn254>,
+    ChainNova<F>,
+>;
+
+pub type DeciderProverParam<F> = <ChainDecider<F> as Decider<
+    G1Projective,
+    G2Projective,
+    CircuitStructure<F>,
+    ChainNova<F>,
+>>::ProverParam;
+
+pub type DeciderVerifierParam<F> = <ChainDecider<F> as Decider<
+    G1Projective,
+    G2Projective,
+    CircuitStructure<F>,
+    ChainNova<F>,
+>>::VerifierParam;
+
+type NovaParams<F> = (
+    <ChainNova<F> as folding_schemes::FoldingScheme<
+        G1Projective,
+        G2Projective,
+        CircuitStructure<F>,
+    >>::ProverParam,
+    <ChainNova<F> as folding_schemes::FoldingScheme<
+        G1Projective,
+        G2Projective,
+        CircuitStructure<F>,
+    >>::VerifierParam,
+);
+
+/// Final proof of a chain, with the instances it opens
+#[derive(Clone, Debug)]
+pub struct ChainProof {
+    pub running_instance: CommittedInstance<G1Projective>,
+    pub incoming_instance: CommittedInstance<G1Projective>,
+    pub proof: decider_eth::Proof<G1Projective, KZG<'static, Bn254>, Groth16<Bn254>>,
+}
+
+/// Public IO of a chain, as checked by the contract
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicIO<F> {
+    pub num_steps: u64,
+    pub z_0: Vec<F>,
+    pub z_i: Vec<F>,
+}
+
+fn to_ff<F: PrimeField>(values: &[Fr]) -> Result<Vec<F>, Error> {
+    values
+        .iter()
+        .map(|x| generic_ark_ff_to_prime_field(&GenericFieldElement::from_repr(*x)))
+        .collect()
+}
+
+fn to_ark<F: PrimeField>(values: &[F]) -> Result<Vec<Fr>, Error> {
+    values.iter().map(ff_to_ark_prime_field).collect()
+}
+
+/// Parameters of the decider, from the ones `folded` was folded with.
+/// Note: the Groth16 setup of the decider circuit is not universal, so the
+/// parameters are only valid for the compiled circuit of `folded`
+pub fn decider_params<F: PrimeField>(
+    rng: impl RngCore + CryptoRng,
+    nova_params: NovaParams<F>,
+    folded: &ChainNova<F>,
+) -> Result<(DeciderProverParam<F>, DeciderVerifierParam<F>), Error> {
+    Ok(ChainDecider::<F>::preprocess(
+        rng,
+        nova_params,
+        folded.clone(),
+    )?)
+}
+
+/// Final proof of the chain of `folded`, with its public IO
+pub fn decide_chain<F: PrimeField>(
+    rng: impl RngCore + CryptoRng,
+    params: DeciderProverParam<F>,
+    folded: ChainNova<F>,
+) -> Result<(ChainProof, PublicIO<F>), Error> {
+    let limbs = ark_ff::PrimeField::into_bigint(folded.i).0;
+    if limbs[1..].iter().any(|limb| *limb != 0) {
+        return Err(Error::InvalidInput);
+    }
+    let public_io = PublicIO {
+        num_steps: limbs[0],
+        z_0: to_ff(&folded.z_0)?,
+        z_i: to_ff(&folded.z_i)?,
+    };
+
+    let running_instance = folded.U_i.clone();
+    let incoming_instance = folded.u_i.clone();
+    let proof = ChainDecider::<F>::prove(rng, params, folded)?;
+
+    Ok((
+        ChainProof {
+            running_instance,
+            incoming_instance,
+            proof,
+        },
+        public_io,
+    ))
+}
+
+/// Check `proof` natively, as the contract does
+pub fn verify_decided_chain<F: PrimeField>(
+    params: DeciderVerifierParam<F>,
+    proof: &ChainProof,
+    public_io: &PublicIO<F>,
+) -> Result<bool, Error> {
+    Ok(ChainDecider::<F>::verify(
+        params,
+        Fr::from(public_io.num_steps),
+        to_ark(&public_io.z_0)?,
+        to_ark(&public_io.z_i)?,
+        &proof.running_instance,
+        &proof.incoming_instance,
+        &proof.proof,
+    )?)
+}
+
+/// Key of the contract verifying the final proofs of a circuit with
+/// `state_len` public inputs
+pub fn verifier_key<F: PrimeField>(
+    params: DeciderVerifierParam<F>,
+    state_len: usize,
+) -> NovaCycleFoldVerifierKey {
+    NovaCycleFoldVerifierKey::from((params, state_len))
+}
+
+/// Source of a contract verifying the final proof of a chain
+pub fn solidity_verifier(verifier_key: &NovaCycleFoldVerifierKey) -> String {
+    get_decider_template_for_cyclefold_decider(verifier_key.clone())
+}
+
+/// Big-endian word of a field element
+pub fn to_word<F: PrimeField>(value: &F) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word.copy_from_slice(&ff_to_le_bytes(value, 32));
+    word.reverse();
+    word
+}
+
+/// Fails if the word is not a canonical field element
+pub fn from_word<F: PrimeField>(word: &[u8; 32]) -> Result<F, Error> {
+    let mut bytes = *word;
+    bytes.reverse();
+    ff_from_canonical_le_bytes(&bytes)
+        .map_err(|_| Error::InvalidCalldata("non-canonical field element"))
+}
+
+fn selector(state_len: usize) -> [u8; 4] {
+    get_function_selector_for_nova_cyclefold_verifier(1 + 2 * state_len)
+}
+
+/// Calldata of the contract of `solidity_verifier` for `proof`
+pub fn encode_calldata<F: PrimeField>(
+    proof: &ChainProof,
+    public_io: &PublicIO<F>,
+) -> Result<Vec<u8>, Error> {
+    Ok(decider_eth::prepare_calldata(
+        selector(public_io.z_0.len()),
+        Fr::from(public_io.num_steps),
+        to_ark(&public_io.z_0)?,
+        to_ark(&public_io.z_i)?,
+        &proof.running_instance,
+        &proof.incoming_instance,
+        proof.proof.clone(),
+    )?)
+}
+
+/// Same layout as `encode_calldata`, with the proof given as the words of
+/// its parameters after the public IO, as returned by `decode_calldata`
+pub fn encode_words<F: PrimeField>(proof: &[[u8; 32]], public_io: &PublicIO<F>) -> Vec<u8> {
+    let mut calldata = selector(public_io.z_0.len()).to_vec();
+
+    let mut num_steps = [0u8; 32];
+    num_steps[24..].copy_from_slice(&public_io.num_steps.to_be_bytes());
+    calldata.extend(num_steps);
+
+    for value in public_io.z_0.iter().chain(&public_io.z_i) {
+        calldata.extend(to_word(value));
+    }
+    for word in proof {
+        calldata.extend(word);
+    }
+    calldata
+}
+
+/// Inverse of `encode_calldata`, for a state of `state_len` elements,
+/// returning the words of the proof
+pub fn decode_calldata<F: PrimeField>(
+    calldata: &[u8],
+    state_len: usize,
+) -> Result<(Vec<[u8; 32]>, PublicIO<F>), Error> {
+    let (head, words) = calldata
+        .split_first_chunk::<4>()
+        .ok_or(Error::InvalidCalldata("no selector"))?;
+    if *head != selector(state_len) {
+        return Err(Error::InvalidCalldata("wrong selector"));
+    }
+    if words.len() % 32 != 0 || words.len() / 32 < 1 + 2 * state_len {
+        return Err(Error::InvalidCalldata("wrong length"));
+    }
+
+    let mut words = words
+        .chunks_exact(32)
+        .map(|word| <[u8; 32]>::try_from(word).unwrap());
+
+    let num_steps = words.next().unwrap();
+    if num_steps[..24].iter().any(|byte| *byte != 0) {
+        return Err(Error::InvalidCalldata("number of steps over 64 bits"));
+    }
+    let num_steps = u64::from_be_bytes(num_steps[24..].try_into().unwrap());
+
+    let mut state = || {
+        words
+            .by_ref()
+            .take(state_len)
+            .map(|word| from_word(&word))
+            .collect::<Result<Vec<F>, _>>()
+    };
+    let z_0 = state()?;
+    let z_i = state()?;
+
+    Ok((
+        words.collect(),
+        PublicIO {
+            num_steps,
+            z_0,
+            z_i,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_calldata_words() {
+        let public_io = PublicIO {
+            num_steps: 2,
+            z_0: vec![F::from(1), F::from(2)],
+            z_i: vec![F::from(5), -F::from(1)],
+        };
+        let proof = vec![[7u8; 32]; 3];
+
+        let calldata = encode_words(&proof, &public_io);
+        assert_eq!(calldata.len(), 4 + 32 * (1 + 4 + 3));
+        assert_eq!(calldata[4 + 31], 2);
+        assert_eq!(calldata[4 + 32 + 31], 1);
+
+        let (decoded_proof, decoded_io) = decode_calldata::<F>(&calldata, 2).unwrap();
+        assert_eq!(decoded_proof, proof);
+        assert_eq!(decoded_io, public_io);
+
+        assert!(decode_calldata::<F>(&calldata, 3).is_err());
+        assert!(decode_calldata::<F>(&calldata[..calldata.len() - 1], 2).is_err());
+        assert!(from_word::<F>(&[0xff; 32]).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Sparse A, B, C matrices of a compiled program.
+//!
+//! Column 0 is the constant one, column `i + 1` is witness `i`, so a
+//! satisfying assignment is `z = (1, w_0, w_1, ...)` with
+//! `(A·z) ∘ (B·z) = C·z`.
+
+use std::collections::BTreeMap;
+
+use ff::PrimeField;
+use ivc_program::program::{IVCProgram, R1CSConstraint, Term, WitnessID, LC};
+use serde::{Deserialize, Serialize};
+
+use super::ccs::CCS;
+
+/// Matrix in coordinate (COO) form, entries sorted by row then column
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMatrix<F> {
+    pub num_rows: usize,
+    pub num_cols: usize,
+    pub entries: Vec<(usize, usize, F)>,
+}
+
+/// Matrix in compressed sparse row (CSR) form
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsrMatrix<F> {
+    pub num_rows: usize,
+    pub num_cols: usize,
+    /// Entries of row `i` are at `row_ptr[i]..row_ptr[i + 1]`
+    pub row_ptr: Vec<usize>,
+    pub col_idx: Vec<usize>,
+    pub values: Vec<F>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SparseR1CS<F> {
+    pub a: SparseMatrix<F>,
+    pub b: SparseMatrix<F>,
+    pub c: SparseMatrix<F>,
+
+    /// Columns of the IO, in witness order
+    pub public_inputs: Vec<usize>,
+    pub private_inputs: Vec<usize>,
+    pub public_outputs: Vec<usize>,
+    pub private_outputs: Vec<usize>,
+}
+
+pub trait IVCProgramExt<F: PrimeField> {
+    fn to_sparse_matrices(&self) -> SparseR1CS<F>;
+
+    fn to_ccs(&self) -> CCS<F> {
+        self.to_sparse_matrices().into()
+    }
+}
+
+/// Column of a witness, after the constant one
+pub fn column(id: WitnessID) -> usize {
+    id.0 as usize + 1
+}
+
+impl<F: Copy> SparseMatrix<F> {
+    pub fn to_csr(&self) -> CsrMatrix<F> {
+        let mut row_ptr = vec![0; self.num_rows + 1];
+        for (row, _, _) in &self.entries {
+            row_ptr[row + 1] += 1;
+        }
+        for i in 0..self.num_rows {
+            row_ptr[i + 1] += row_ptr[i];
+        }
+
+        CsrMatrix {
+            num_rows: self.num_rows,
+            num_cols: self.num_cols,
+            row_ptr,
+            col_idx: self.entries.iter().map(|(_, col, _)| *col).collect(),
+            values: self.entries.iter().map(|(_, _, value)| *value).collect(),
+        }
+    }
+}
+
+/// Entries of one row, merging the terms of the same column
+fn row_entries<F: PrimeField>(row: usize, lc: &LC<F>) -> impl Iterator<Item = (usize, usize, F)> {
+    let mut columns: BTreeMap<usize, F> = BTreeMap::new();
+
+    for term in &lc.0 {
+        let (col, value) = match term {
+            Term::LC {
+                coefficient,
+                var_id,
+            } => (column(*var_id), *coefficient),
+            Term::Const(value) => (0, *value),
+        };
+        *columns.entry(col).or_insert(F::ZERO) += value;
+    }
+
+    columns
+        .into_iter()
+        .filter(|(_, value)| !bool::from(value.is_zero()))
+        .map(move |(col, value)| (row, col, value))
+}
+
+impl<F: PrimeField> IVCProgramExt<F> for IVCProgram<F> {
+    fn to_sparse_matrices(&self) -> SparseR1CS<F> {
+        let num_rows = self.r1cs_constraints.len();
+        let num_cols = self.num_witness as usize + 1;
+
+        let matrix = |lc: fn(&R1CSConstraint<F>) -> &LC<F>| SparseMatrix {
+            num_rows,
+            num_cols,
+            entries: self
+                .r1cs_constraints
+                .iter()
+                .enumerate()
+                .flat_map(|(row, constraint)| row_entries(row, lc(constraint)))
+                .collect(),
+        };
+
+        let columns = |ids: &std::collections::BTreeSet<WitnessID>| {
+            ids.iter().map(|id| column(*id)).collect()
+        };
+
+        SparseR1CS {
+            a: matrix(|constraint| &constraint.a),
+            b: matrix(|constraint| &constraint.b),
+            c: matrix(|constraint| &constraint.c),
+            public_inputs: columns(&self.public_inputs),
+            private_inputs: columns(&self.private_inputs),
+            public_outputs: columns(&self.public_outputs),
+            private_outputs: columns(&self.private_outputs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ivc_program::program::{IOProfile, VERSION_0_1};
+
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_to_sparse_matrices() {
+        // w0 * w1 = w2 + 3
+        let var = |id| Term::LC {
+            coefficient: F::one(),
+            var_id: WitnessID(id),
+        };
+        let constraint = R1CSConstraint {
+            a: LC(vec![var(0)]),
+            b: LC(vec![var(1)]),
+            c: LC(vec![var(2), Term::Const(F::from(3))]),
+        };
+
+        let program = IVCProgram {
+            io: IOProfile {
+                public_inputs: Default::default(),
+                private_inputs: Default::default(),
+                public_outputs: Default::default(),
+                private_outputs: Default::default(),
+            },
+            num_witness: 3,
+            r1cs_constraints: vec![constraint.clone(), constraint],
+            curve: crate::Curve::program_name::<F>(),
+            version: VERSION_0_1.to_string(),
+        };
+
+        let r1cs = program.to_sparse_matrices();
+        assert_eq!(r1cs.a.num_cols, 4);
+        assert_eq!(r1cs.a.entries, vec![(0, 1, F::one()), (1, 1, F::one())]);
+        assert_eq!(r1cs.c.entries[..2], [(0, 0, F::from(3)), (0, 3, F::one())]);
+
+        let csr = r1cs.c.to_csr();
+        assert_eq!(csr.row_ptr, vec![0, 2, 4]);
+        assert_eq!(csr.col_idx, vec![0, 3, 0, 3]);
+    }
+}
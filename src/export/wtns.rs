@@ -0,0 +1,87 @@
+//! The snarkjs `.wtns` binary witness format, matching the wires of the
+//! `.r1cs` export.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use ff::PrimeField;
+use ivc_program::{program::WitnessID, witness::Witness};
+
+use super::circom::{field_size, wire_order, write_section};
+use crate::{
+    field::{ff_to_le_bytes, modulus_to_le_bytes},
+    program::CircuitStructure,
+    Error,
+};
+
+const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const WTNS_VERSION: u32 = 2;
+
+const SECTION_HEADER: u32 = 1;
+const SECTION_WITNESS: u32 = 2;
+
+/// Reorder a step witness so that witness `i` holds the value of wire `i + 1`
+/// of the `.r1cs` export of `structure`
+pub fn to_wire_order<F: PrimeField>(
+    structure: &CircuitStructure<F>,
+    witness: &Witness<F>,
+) -> Result<Witness<F>, Error> {
+    wire_order(structure)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let value = witness.get(&id).ok_or(Error::MissingWitness(id.0))?;
+            Ok((WitnessID(i as u32), *value))
+        })
+        .collect::<Result<_, _>>()
+        .map(Witness)
+}
+
+/// Write a witness in wire order (see `to_wire_order`) in the `.wtns`
+/// format, with the constant one as wire 0
+pub fn write_wtns<F: PrimeField>(
+    witness: &Witness<F>,
+    writer: &mut impl Write,
+) -> Result<(), Error> {
+    let n8 = field_size::<F>();
+
+    let header = {
+        let mut content = vec![];
+        content.extend((n8 as u32).to_le_bytes());
+        content.extend(modulus_to_le_bytes::<F>(n8));
+        content.extend((witness.len() as u32 + 1).to_le_bytes());
+        content
+    };
+
+    let values = {
+        let mut content = ff_to_le_bytes(&F::ONE, n8);
+        for (i, (id, value)) in witness.iter().enumerate() {
+            // witnesses must be numbered contiguously
+            if id.0 != i as u32 {
+                return Err(Error::MissingWitness(i as u32));
+            }
+            content.extend(ff_to_le_bytes(value, n8));
+        }
+        content
+    };
+
+    writer.write_all(WTNS_MAGIC)?;
+    writer.write_all(&WTNS_VERSION.to_le_bytes())?;
+    writer.write_all(&2u32.to_le_bytes())?;
+
+    write_section(writer, SECTION_HEADER, &header)?;
+    write_section(writer, SECTION_WITNESS, &values)?;
+
+    Ok(())
+}
+
+/// Write a witness in wire order to a `.wtns` file
+pub fn to_wtns<F: PrimeField, P: AsRef<Path>>(witness: &Witness<F>, path: P) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_wtns(witness, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -1,58 +1,376 @@
-use std::any::type_name;
-
-use crate::{
-    constants::{CURVE_BN254, CURVE_BN254_ARK},
-    Error,
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    sync::{OnceLock, RwLock},
 };
-use acvm::{acir::acir_field::GenericFieldElement, AcirField};
-use ark_ff::PrimeField as ArkPrimeField;
+
+use crate::{curve::Curve, Error};
+use acvm::acir::acir_field::GenericFieldElement;
+use ark_ff::{BigInteger, PrimeField as ArkPrimeField};
 use ff::PrimeField as PF;
-use num::Num;
+
+/// Direction of a failed conversion
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConversionDirection {
+    FfToArk,
+    ArkToFf,
+    /// From text or bytes, e.g. of an input file
+    Parse,
+}
+
+/// What the value that failed to convert was
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConversionSite {
+    GateCoefficient,
+    ConstantTerm,
+    WitnessValue,
+    OracleValue,
+    Input,
+}
+
+fn in_site(site: &Option<ConversionSite>) -> String {
+    site.map_or_else(String::new, |site| format!(" in {:?}", site))
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("cannot convert {value} from {from} to {to} ({direction:?}){}", in_site(.site))]
+pub struct FieldConversionError {
+    pub value: String,
+    pub direction: ConversionDirection,
+    pub from: &'static str,
+    pub to: &'static str,
+    /// Set by the caller when known
+    pub site: Option<ConversionSite>,
+}
+
+impl FieldConversionError {
+    pub(crate) fn new<From, To>(direction: ConversionDirection, value: String) -> Self {
+        Self {
+            value,
+            direction,
+            from: type_name::<From>(),
+            to: type_name::<To>(),
+            site: None,
+        }
+    }
+
+    /// A value of `F` that failed to parse from `value`
+    pub(crate) fn parse<F>(value: String) -> Self {
+        Self {
+            from: "text",
+            ..Self::new::<(), F>(ConversionDirection::Parse, value)
+        }
+    }
+}
+
+/// Whether `A` and `B` are the same prime field, i.e. of the same `Curve`
+/// if any, with a little-endian `B::Repr` as the byte conversions expect
+// Note: cached by type, as the conversions check it for every value
+pub fn same_field<A: ArkPrimeField, B: PF>() -> bool {
+    static SAME_FIELDS: OnceLock<RwLock<HashMap<(TypeId, TypeId), bool>>> = OnceLock::new();
+
+    let key = (TypeId::of::<A>(), TypeId::of::<B>());
+    let cache = SAME_FIELDS.get_or_init(Default::default);
+    if let Some(same) = cache.read().unwrap().get(&key) {
+        return *same;
+    }
+
+    let same = compare_fields::<A, B>();
+    cache.write().unwrap().insert(key, same);
+    same
+}
+
+fn compare_fields<A: ArkPrimeField, B: PF>() -> bool {
+    if Curve::of_ark::<A>() != Curve::of::<B>() {
+        return false;
+    }
+
+    // Note: -1 is the modulus minus one, so its canonical little-endian bytes
+    // only match for equal moduli and byte orders. The modulus of `ff` is a
+    // string, which rules out a compile-time check
+    let a = (-A::ONE).into_bigint().to_bytes_le();
+    let b = (-B::ONE).to_repr();
+
+    let trim = |bytes: &[u8]| bytes.len() - bytes.iter().rev().take_while(|b| **b == 0).count();
+    a[..trim(&a)] == b.as_ref()[..trim(b.as_ref())]
+}
+
+/// Fails with the conversion of `value` in `direction` unless `A` and `B`
+/// are the same field
+fn check_fields<A: ArkPrimeField, B: PF>(
+    direction: ConversionDirection,
+    value: impl FnOnce() -> String,
+) -> Result<(), Error> {
+    if same_field::<A, B>() {
+        return Ok(());
+    }
+    let error = match direction {
+        ConversionDirection::FfToArk => FieldConversionError::new::<B, A>(direction, value()),
+        _ => FieldConversionError::new::<A, B>(direction, value()),
+    };
+    Err(error.into())
+}
 
 pub fn assert_types<A: ArkPrimeField, B: PF>() {
-    let a = type_name::<A>();
-    let b = type_name::<B>();
+    assert!(
+        same_field::<A, B>(),
+        "{} ({:?}) and {} ({:?}) are different fields",
+        type_name::<A>(),
+        Curve::of_ark::<A>(),
+        type_name::<B>(),
+        Curve::of::<B>()
+    );
+}
+
+/// Conversions between `ark_bn254::Fr` and `halo2curves::bn256::Fr`, taken
+/// by the generic conversions when both sides are these types
+#[cfg(feature = "bn254-fast")]
+mod bn254 {
+    use std::any::Any;
+
+    use acvm::acir::acir_field::GenericFieldElement;
+    use ark_ff::{BigInt, PrimeField as ArkPrimeField};
+    use ff::PrimeField as PF;
+    use halo2curves::serde::SerdeObject;
+
+    type ArkFr = ark_bn254::Fr;
+    type Fr = halo2curves::bn256::Fr;
+
+    // Note: both keep 4 limbs in Montgomery form for R = 2^256, so the limbs
+    // are copied as is, without reduction
+    pub fn ark_to_halo2(input: &ArkFr) -> Fr {
+        let mut bytes = [0u8; 32];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(input.0 .0) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        Fr::from_raw_bytes_unchecked(&bytes)
+    }
+
+    pub fn halo2_to_ark(input: &Fr) -> ArkFr {
+        let mut bytes = [0u8; 32];
+        input
+            .write_raw(&mut &mut bytes[..])
+            .expect("32 bytes of limbs");
+        let limbs = std::array::from_fn(|i| {
+            u64::from_le_bytes(bytes[8 * i..8 * i + 8].try_into().unwrap())
+        });
+        ArkFr::new_unchecked(BigInt(limbs))
+    }
+
+    pub fn ark_to_ff<IF: ArkPrimeField, OF: PF>(input: &GenericFieldElement<IF>) -> Option<OF> {
+        let input = input.into_repr();
+        let input = (&input as &dyn Any).downcast_ref::<ArkFr>()?;
+        (&ark_to_halo2(input) as &dyn Any).downcast_ref().copied()
+    }
 
-    assert_eq!(a, CURVE_BN254_ARK);
-    assert_eq!(b, CURVE_BN254);
+    pub fn ff_to_ark<IF: PF, OF: ArkPrimeField>(input: &IF) -> Option<OF> {
+        let input = (input as &dyn Any).downcast_ref::<Fr>()?;
+        (&halo2_to_ark(input) as &dyn Any).downcast_ref().copied()
+    }
 }
 
-pub fn generic_ark_ff_to_prime_field<IF: ArkPrimeField, OF: PF>(
+// Note: the fields are the same (see `check_fields`) and both
+// representations are little-endian, so conversions copy bytes
+fn ark_to_ff_bytes<IF: ArkPrimeField, OF: PF>(
     input: &GenericFieldElement<IF>,
 ) -> Result<OF, Error> {
-    assert_types::<IF, OF>();
+    #[cfg(feature = "bn254-fast")]
+    if let Some(output) = bn254::ark_to_ff(input) {
+        return Ok(output);
+    }
+
+    let bytes = input.into_repr().into_bigint().to_bytes_le();
+    let mut repr = OF::Repr::default();
+    let len = repr.as_ref().len().min(bytes.len());
+    repr.as_mut()[..len].copy_from_slice(&bytes[..len]);
+    Option::from(OF::from_repr(repr)).ok_or_else(|| {
+        FieldConversionError::new::<IF, OF>(
+            ConversionDirection::ArkToFf,
+            format!("{}", input.into_repr()),
+        )
+        .into()
+    })
+}
 
-    if input.is_zero() {
-        return Ok(OF::from(0));
+fn ff_to_ark_bytes<IF: PF, OF: ArkPrimeField>(input: &IF) -> OF {
+    #[cfg(feature = "bn254-fast")]
+    if let Some(output) = bn254::ff_to_ark(input) {
+        return output;
     }
 
-    let text = format!("{}", input.into_repr());
+    OF::from_le_bytes_mod_order(input.to_repr().as_ref())
+}
 
-    OF::from_str_vartime(&text).ok_or(Error::FieldConversionError(text))
+pub fn generic_ark_ff_to_prime_field<IF: ArkPrimeField, OF: PF>(
+    input: &GenericFieldElement<IF>,
+) -> Result<OF, Error> {
+    check_fields::<IF, OF>(ConversionDirection::ArkToFf, || {
+        format!("{}", input.into_repr())
+    })?;
+    ark_to_ff_bytes(input)
 }
 
 pub fn ff_to_ark_prime_field<IF: PF, OF: ArkPrimeField>(input: &IF) -> Result<OF, Error> {
-    assert_types::<OF, IF>();
+    check_fields::<OF, IF>(ConversionDirection::FfToArk, || format!("{:?}", input))?;
+    Ok(ff_to_ark_bytes(input))
+}
+
+/// Same as `generic_ark_ff_to_prime_field` on every element, in parallel with
+/// the `parallel` feature
+pub fn generic_ark_ff_slice_to_prime_field<IF: ArkPrimeField, OF: PF>(
+    inputs: &[GenericFieldElement<IF>],
+) -> Result<Vec<OF>, Error> {
+    check_fields::<IF, OF>(ConversionDirection::ArkToFf, || {
+        format!("{} values", inputs.len())
+    })?;
 
-    if input.is_zero().into() {
-        return Ok(OF::zero());
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        inputs.par_iter().map(ark_to_ff_bytes).collect()
     }
 
-    let bn = {
-        let text = format!("{:?}", input);
-        let text = text.split_at(2).1;
-        num::BigInt::from_str_radix(text, 16).unwrap()
-    };
+    #[cfg(not(feature = "parallel"))]
+    {
+        inputs.iter().map(ark_to_ff_bytes).collect()
+    }
+}
+
+/// Same as `ff_to_ark_prime_field` on every element, in parallel with the
+/// `parallel` feature
+pub fn ff_slice_to_ark_prime_field<IF: PF, OF: ArkPrimeField>(
+    inputs: &[IF],
+) -> Result<Vec<OF>, Error> {
+    check_fields::<OF, IF>(ConversionDirection::FfToArk, || {
+        format!("{} values", inputs.len())
+    })?;
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        Ok(inputs.par_iter().map(ff_to_ark_bytes).collect())
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        Ok(inputs.iter().map(ff_to_ark_bytes).collect())
+    }
+}
+
+/// Canonical little-endian bytes of `input`, whatever the byte order of
+/// `F::Repr`
+// Note: one is 1 in its lowest byte, which tells the byte order
+fn ff_to_repr_le<F: PF>(input: &F) -> Vec<u8> {
+    let mut bytes = input.to_repr().as_ref().to_vec();
+    if F::ONE.to_repr().as_ref()[0] != 1 {
+        bytes.reverse();
+    }
+    bytes
+}
+
+/// Inverse of `ff_to_repr_le`, `None` for a value that is not canonical
+fn ff_from_repr_le<F: PF>(bytes: &[u8]) -> Option<F> {
+    let mut repr = F::Repr::default();
+    let len = repr.as_ref().len();
+    let trimmed = bytes.len() - bytes.iter().rev().take_while(|b| **b == 0).count();
+    if trimmed > len {
+        return None;
+    }
+
+    repr.as_mut()[..trimmed].copy_from_slice(&bytes[..trimmed]);
+    if F::ONE.to_repr().as_ref()[0] != 1 {
+        repr.as_mut().reverse();
+    }
+    Option::from(F::from_repr(repr))
+}
+
+/// The modulus of `F`, one more than -1
+fn modulus<F: PF>() -> num::BigUint {
+    num::BigUint::from_bytes_le(&ff_to_repr_le(&-F::ONE)) + 1u32
+}
+
+fn to_le_bytes(bn: &num::BigUint, len: usize) -> Vec<u8> {
+    let mut bytes = bn.to_bytes_le();
+    bytes.resize(len, 0);
+    bytes
+}
 
-    let text = format!("{}", bn);
+/// Little-endian bytes of a field element, zero-padded to `len`
+pub fn ff_to_le_bytes<F: PF>(input: &F, len: usize) -> Vec<u8> {
+    let mut bytes = ff_to_repr_le(input);
+    bytes.resize(len, 0);
+    bytes
+}
 
-    OF::from_str(&text).map_err(|_| Error::FieldConversionError(text))
+fn bytes_error<F>(bytes: &[u8]) -> Error {
+    FieldConversionError {
+        from: "bytes",
+        ..FieldConversionError::parse::<F>(num::BigUint::from_bytes_le(bytes).to_string())
+    }
+    .into()
+}
+
+/// The field element of little-endian `bytes`, reduced modulo `F::MODULUS`
+pub fn ff_from_le_bytes<F: PF>(bytes: &[u8]) -> Result<F, Error> {
+    let value = (num::BigUint::from_bytes_le(bytes) % modulus::<F>()).to_bytes_le();
+    ff_from_repr_le(&value).ok_or_else(|| bytes_error::<F>(&value))
+}
+
+/// Same as `ff_from_le_bytes`, rejecting a value that is not below
+/// `F::MODULUS` instead of reducing it
+pub fn ff_from_canonical_le_bytes<F: PF>(bytes: &[u8]) -> Result<F, Error> {
+    ff_from_repr_le(bytes).ok_or_else(|| bytes_error::<F>(bytes))
+}
+
+/// Little-endian bytes of the field modulus, zero-padded to `len`
+pub fn modulus_to_le_bytes<F: PF>(len: usize) -> Vec<u8> {
+    to_le_bytes(&modulus::<F>(), len)
 }
 
 #[cfg(test)]
 mod tests {
+    use acvm::AcirField;
+    use num::Num;
+
     use super::*;
 
+    // Note: the former conversions through text, as a reference
+    fn ark_to_ff_text<IF: ArkPrimeField, OF: PF>(input: &GenericFieldElement<IF>) -> OF {
+        if input.is_zero() {
+            return OF::from(0);
+        }
+        OF::from_str_vartime(&format!("{}", input.into_repr())).unwrap()
+    }
+
+    fn ff_to_ark_text<IF: PF, OF: ArkPrimeField>(input: &IF) -> OF {
+        if input.is_zero().into() {
+            return OF::zero();
+        }
+        let text = format!("{:?}", input);
+        let bn = num::BigInt::from_str_radix(text.split_at(2).1, 16).unwrap();
+        OF::from_str(&format!("{}", bn)).ok().unwrap()
+    }
+
+    #[test]
+    fn test_bytes_match_text() {
+        type AF = ark_bn254::Fr;
+        type F = halo2curves::bn256::Fr;
+
+        let mut value = F::from(7);
+        for _ in 0..64 {
+            // Note: quickly grows to fill all the limbs
+            value = value * value * F::from(3) + F::from(1);
+
+            let ark: AF = ff_to_ark_prime_field(&value).unwrap();
+            assert_eq!(ark, ff_to_ark_text::<F, AF>(&value));
+
+            let generic = GenericFieldElement::from_repr(ark);
+            let back: F = generic_ark_ff_to_prime_field(&generic).unwrap();
+            assert_eq!(back, value);
+            assert_eq!(back, ark_to_ff_text::<AF, F>(&generic));
+        }
+    }
+
     #[test]
     fn test_ark_to_ff_bn254() {
         type AF = ark_bn254::Fr;
@@ -87,4 +405,157 @@ mod tests {
         check(F::from(16), AF::from(16));
         check(F::zero() - F::one(), AF::from(-1));
     }
+
+    #[cfg(feature = "bn254-fast")]
+    #[test]
+    fn test_bn254_fast_path() {
+        type AF = ark_bn254::Fr;
+        type F = halo2curves::bn256::Fr;
+
+        // Note: xorshift, to not depend on a random generator
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut values = vec![AF::from(0), AF::from(1), AF::from(-1)];
+        values.extend((0..10_000).map(|_| {
+            let bytes: Vec<u8> = (0..4).flat_map(|_| next().to_le_bytes()).collect();
+            AF::from_le_bytes_mod_order(&bytes)
+        }));
+
+        for value in values {
+            let fast = bn254::ark_to_halo2(&value);
+            let bytes = value.into_bigint().to_bytes_le();
+            let generic = F::from_repr(bytes.try_into().unwrap()).unwrap();
+            assert_eq!(fast, generic);
+
+            assert_eq!(bn254::halo2_to_ark(&fast), value);
+            assert_eq!(AF::from_le_bytes_mod_order(&generic.to_repr()), value);
+        }
+    }
+
+    #[test]
+    fn test_same_field() {
+        // the second time from the cache
+        for _ in 0..2 {
+            assert!(same_field::<ark_bn254::Fr, halo2curves::bn256::Fr>());
+            assert!(same_field::<ark_bn254::Fq, halo2curves::bn256::Fq>());
+            assert!(!same_field::<ark_bn254::Fq, halo2curves::bn256::Fr>());
+        }
+    }
+
+    #[test]
+    fn test_bn254_base_field() {
+        type AF = ark_bn254::Fq;
+        type F = halo2curves::bn256::Fq;
+
+        let values = [F::from(0), F::from(258), F::zero() - F::one()];
+        let converted: Vec<AF> = ff_slice_to_ark_prime_field(&values).unwrap();
+        assert_eq!(converted[1], AF::from(258));
+        assert_eq!(converted[2], AF::from(-1));
+
+        let generic: Vec<_> = converted
+            .into_iter()
+            .map(GenericFieldElement::from_repr)
+            .collect();
+        let back: Vec<F> = generic_ark_ff_slice_to_prime_field(&generic).unwrap();
+        assert_eq!(back, values);
+
+        assert_eq!(crate::Curve::of::<F>(), Some(crate::Curve::Grumpkin));
+    }
+
+    #[test]
+    fn test_pasta() {
+        use halo2curves::pasta::{Fp, Fq};
+
+        // Note: the scalar field of each curve is the base field of the other
+        assert!(same_field::<ark_pallas::Fr, Fq>());
+        assert!(same_field::<ark_vesta::Fr, Fp>());
+        assert!(!same_field::<ark_pallas::Fr, Fp>());
+
+        for value in [Fq::from(0), Fq::from(258), Fq::zero() - Fq::one()] {
+            let ark: ark_pallas::Fr = ff_to_ark_prime_field(&value).unwrap();
+            let generic = GenericFieldElement::from_repr(ark);
+            assert_eq!(
+                generic_ark_ff_to_prime_field::<_, Fq>(&generic).unwrap(),
+                value
+            );
+        }
+
+        let value = Fp::from(258);
+        let ark: ark_vesta::Fr = ff_to_ark_prime_field(&value).unwrap();
+        assert_eq!(ark, ark_vesta::Fr::from(258));
+    }
+
+    #[test]
+    fn test_slice_conversions() {
+        type AF = ark_bn254::Fr;
+        type F = halo2curves::bn256::Fr;
+
+        let values = [F::from(0), F::from(1), F::from(258), F::zero() - F::one()];
+
+        let converted: Vec<AF> = ff_slice_to_ark_prime_field(&values).unwrap();
+        for (value, converted) in values.iter().zip(&converted) {
+            assert_eq!(*converted, ff_to_ark_prime_field::<F, AF>(value).unwrap());
+        }
+
+        let generic: Vec<_> = converted
+            .into_iter()
+            .map(GenericFieldElement::from_repr)
+            .collect();
+        let back: Vec<F> = generic_ark_ff_slice_to_prime_field(&generic).unwrap();
+        assert_eq!(back, values);
+    }
+
+    #[test]
+    fn test_conversion_directions() {
+        type F = halo2curves::bn256::Fr;
+        type OtherAF = ark_pallas::Fr;
+
+        let direction = |result: Result<(), Error>| match result {
+            Err(Error::FieldConversionError(e)) => e.direction,
+            result => panic!("unexpected {:?}", result),
+        };
+
+        let ff_to_ark = ff_to_ark_prime_field::<F, OtherAF>(&F::from(1)).map(|_| ());
+        assert_eq!(direction(ff_to_ark), ConversionDirection::FfToArk);
+        let ff_to_ark = ff_slice_to_ark_prime_field::<F, OtherAF>(&[F::from(1)]).map(|_| ());
+        assert_eq!(direction(ff_to_ark), ConversionDirection::FfToArk);
+
+        let generic = GenericFieldElement::from_repr(OtherAF::from(1));
+        let ark_to_ff = generic_ark_ff_to_prime_field::<_, F>(&generic).map(|_| ());
+        assert_eq!(direction(ark_to_ff), ConversionDirection::ArkToFf);
+        let ark_to_ff = generic_ark_ff_slice_to_prime_field::<_, F>(&[generic]).map(|_| ());
+        assert_eq!(direction(ark_to_ff), ConversionDirection::ArkToFf);
+
+        // Note: `ff_from_le_bytes` reduces the modulus to zero
+        let modulus = modulus_to_le_bytes::<F>(32);
+        assert_eq!(ff_from_le_bytes::<F>(&modulus).unwrap(), F::ZERO);
+        let parse = ff_from_canonical_le_bytes::<F>(&modulus).map(|_| ());
+        assert_eq!(direction(parse), ConversionDirection::Parse);
+    }
+
+    #[test]
+    fn test_le_bytes() {
+        type F = halo2curves::bn256::Fr;
+
+        let bytes = ff_to_le_bytes(&F::from(258), 32);
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(bytes[..3], [2, 1, 0]);
+        assert_eq!(ff_from_le_bytes::<F>(&bytes).unwrap(), F::from(258));
+
+        let minus_one = F::zero() - F::one();
+        let bytes = ff_to_le_bytes(&minus_one, 32);
+        assert_eq!(ff_from_le_bytes::<F>(&bytes).unwrap(), minus_one);
+
+        assert_eq!(ff_from_canonical_le_bytes::<F>(&bytes).unwrap(), minus_one);
+
+        let mut modulus = modulus_to_le_bytes::<F>(32);
+        modulus[0] -= 1;
+        assert_eq!(modulus, bytes);
+    }
 }
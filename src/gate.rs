@@ -1,10 +1,16 @@
+use std::collections::BTreeMap;
+
 use acvm::acir::{acir_field::GenericFieldElement, circuit::Opcode, native_types::Expression};
 use ark_ff::PrimeField as ArkPrimeField;
 use ff::PrimeField;
-use ivc_program::program::WitnessID;
+use ivc_program::{program::WitnessID, witness::Witness};
 use serde::{Deserialize, Serialize};
 
-use crate::field::{ff_to_ark_prime_field, generic_ark_ff_to_prime_field};
+use crate::{
+    field::{ff_to_ark_prime_field, generic_ark_ff_to_prime_field, ConversionSite},
+    load::UnsupportedProgramError,
+    Error,
+};
 
 // adapted from arkworks_backend::bridge::AcirArithGate
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -14,79 +20,335 @@ pub struct AcirArithGate<F> {
     pub constant_term: F,
 }
 
-impl<AF: ArkPrimeField, F: PrimeField> From<AcirArithGate<F>> for Opcode<GenericFieldElement<AF>> {
-    fn from(source: AcirArithGate<F>) -> Self {
-        let mut_terms = source
+impl<F> AcirArithGate<F> {
+    pub fn witnesses(&self) -> impl Iterator<Item = WitnessID> + '_ {
+        self.mul_terms
+            .iter()
+            .flat_map(|(_, l, r)| [*l, *r])
+            .chain(self.add_terms.iter().map(|(_, id)| *id))
+    }
+}
+
+impl<F: PrimeField> AcirArithGate<F> {
+    /// Evaluate `sum(mul_terms) + sum(add_terms) + constant_term` on a witness
+    pub fn evaluate(&self, witness: &Witness<F>) -> Result<F, Error> {
+        let get = |id: &WitnessID| witness.get(id).ok_or(Error::MissingWitness(id.0));
+
+        let mut res = self.constant_term;
+
+        for (coeff, left, right) in &self.mul_terms {
+            res += *coeff * get(left)? * get(right)?;
+        }
+
+        for (coeff, id) in &self.add_terms {
+            res += *coeff * get(id)?;
+        }
+
+        Ok(res)
+    }
+
+    /// If the gate is `c * w + k = 0`, the witness `w` it fixes to `-k / c`
+    pub fn constant_assignment(&self) -> Option<(WitnessID, F)> {
+        match (self.mul_terms.as_slice(), self.add_terms.as_slice()) {
+            ([], [(coeff, id)]) => Option::<F>::from(coeff.invert())
+                .map(|inverse| (*id, -self.constant_term * inverse)),
+            _ => None,
+        }
+    }
+
+    /// Whether the witness satisfies the gate, i.e. it evaluates to zero
+    pub fn is_satisfied(&self, witness: &Witness<F>) -> Result<bool, Error> {
+        Ok(self.evaluate(witness)?.is_zero().into())
+    }
+
+    /// Replace the witnesses in `constants` by their values
+    pub fn substitute(&self, constants: &BTreeMap<WitnessID, F>) -> Self {
+        let mut gate = Self {
+            mul_terms: vec![],
+            add_terms: vec![],
+            constant_term: self.constant_term,
+        };
+
+        for (coeff, left, right) in &self.mul_terms {
+            match (constants.get(left), constants.get(right)) {
+                (Some(l), Some(r)) => gate.constant_term += *coeff * l * r,
+                (Some(l), None) => gate.add_terms.push((*coeff * l, *right)),
+                (None, Some(r)) => gate.add_terms.push((*coeff * r, *left)),
+                (None, None) => gate.mul_terms.push((*coeff, *left, *right)),
+            }
+        }
+
+        for (coeff, id) in &self.add_terms {
+            match constants.get(id) {
+                Some(value) => gate.constant_term += *coeff * value,
+                None => gate.add_terms.push((*coeff, *id)),
+            }
+        }
+
+        gate
+    }
+
+    pub fn map_witnesses(&self, f: impl Fn(WitnessID) -> WitnessID) -> Self {
+        Self {
+            mul_terms: self
+                .mul_terms
+                .iter()
+                .map(|(coeff, left, right)| (*coeff, f(*left), f(*right)))
+                .collect(),
+            add_terms: self
+                .add_terms
+                .iter()
+                .map(|(coeff, id)| (*coeff, f(*id)))
+                .collect(),
+            constant_term: self.constant_term,
+        }
+    }
+
+    /// A gate without terms whose constant is zero, i.e. `0 = 0`
+    pub fn is_trivial(&self) -> bool {
+        self.mul_terms.is_empty()
+            && self.add_terms.is_empty()
+            && bool::from(self.constant_term.is_zero())
+    }
+}
+
+impl<AF: ArkPrimeField, F: PrimeField> TryFrom<AcirArithGate<F>>
+    for Expression<GenericFieldElement<AF>>
+{
+    type Error = Error;
+
+    fn try_from(source: AcirArithGate<F>) -> Result<Self, Self::Error> {
+        let coefficient = |c: &F, site| {
+            ff_to_ark_prime_field(c)
+                .map(GenericFieldElement::from_repr)
+                .map_err(|e| e.at_site(site))
+        };
+
+        let mul_terms = source
             .mul_terms
-            .into_iter()
+            .iter()
             .map(|(c, l, r)| {
-                let c = ff_to_ark_prime_field(&c).expect("mul terms conversion error");
-                let l = l.0.into();
-                let r = r.0.into();
-                (GenericFieldElement::from_repr(c), l, r)
+                let c = coefficient(c, ConversionSite::GateCoefficient)?;
+                Ok((c, l.0.into(), r.0.into()))
             })
-            .collect();
+            .collect::<Result<_, Error>>()?;
 
         let add_terms = source
             .add_terms
-            .into_iter()
-            .map(|(c, w)| {
-                let c = ff_to_ark_prime_field(&c).expect("add terms conversion error");
-                let w = w.0.into();
-                (GenericFieldElement::from_repr(c), w)
-            })
-            .collect();
-
-        let constant_term = GenericFieldElement::from_repr(
-            ff_to_ark_prime_field(&source.constant_term).expect("constant term conversion error"),
-        );
+            .iter()
+            .map(|(c, w)| Ok((coefficient(c, ConversionSite::GateCoefficient)?, w.0.into())))
+            .collect::<Result<_, Error>>()?;
 
-        Opcode::AssertZero(Expression {
-            mul_terms: mut_terms,
+        Ok(Expression {
+            mul_terms,
             linear_combinations: add_terms,
-            q_c: constant_term,
+            q_c: coefficient(&source.constant_term, ConversionSite::ConstantTerm)?,
         })
     }
 }
 
-impl<AF, F> From<Opcode<GenericFieldElement<AF>>> for AcirArithGate<F>
+impl<AF: ArkPrimeField, F: PrimeField> TryFrom<AcirArithGate<F>>
+    for Opcode<GenericFieldElement<AF>>
+{
+    type Error = Error;
+
+    fn try_from(source: AcirArithGate<F>) -> Result<Self, Self::Error> {
+        Ok(Opcode::AssertZero(source.try_into()?))
+    }
+}
+
+impl<AF, F> TryFrom<Expression<GenericFieldElement<AF>>> for AcirArithGate<F>
 where
     AF: ArkPrimeField,
     F: PrimeField,
 {
-    fn from(opcode: Opcode<GenericFieldElement<AF>>) -> Self {
+    type Error = Error;
+
+    fn try_from(expr: Expression<GenericFieldElement<AF>>) -> Result<Self, Self::Error> {
+        let coefficient = |c: &GenericFieldElement<AF>, site| {
+            generic_ark_ff_to_prime_field(c).map_err(|e| e.at_site(site))
+        };
+
+        let mul_terms = expr
+            .mul_terms
+            .iter()
+            .map(|(c, l, r)| {
+                let c = coefficient(c, ConversionSite::GateCoefficient)?;
+                Ok((c, l.0.into(), r.0.into()))
+            })
+            .collect::<Result<_, Error>>()?;
+        let add_terms = expr
+            .linear_combinations
+            .iter()
+            .map(|(c, w)| Ok((coefficient(c, ConversionSite::GateCoefficient)?, w.0.into())))
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            mul_terms,
+            add_terms,
+            constant_term: coefficient(&expr.q_c, ConversionSite::ConstantTerm)?,
+        })
+    }
+}
+
+/// Convert the opcode at `index` of a circuit into a gate
+impl<AF, F> TryFrom<(usize, Opcode<GenericFieldElement<AF>>)> for AcirArithGate<F>
+where
+    AF: ArkPrimeField,
+    F: PrimeField,
+{
+    type Error = UnsupportedProgramError;
+
+    fn try_from(
+        (index, opcode): (usize, Opcode<GenericFieldElement<AF>>),
+    ) -> Result<Self, Self::Error> {
         if let Opcode::AssertZero(op) = opcode {
-            let mul_terms = op
-                .mul_terms
-                .into_iter()
-                .map(|(c, l, r)| {
-                    (
-                        generic_ark_ff_to_prime_field(&c).expect("mul terms conversion error"),
-                        l.0.into(),
-                        r.0.into(),
-                    )
-                })
-                .collect();
-            let add_terms = op
-                .linear_combinations
-                .into_iter()
-                .map(|(c, w)| {
-                    (
-                        generic_ark_ff_to_prime_field(&c).expect("add terms conversion error"),
-                        w.0.into(),
-                    )
-                })
-                .collect();
-            let constant_term =
-                generic_ark_ff_to_prime_field(&op.q_c).expect("constant term conversion error");
-
-            Self {
-                mul_terms,
-                add_terms,
-                constant_term,
-            }
+            op.try_into()
+                .map_err(|e| UnsupportedProgramError::UnconvertibleOpcode(index, Box::new(e)))
         } else {
-            panic!("Unsupported opcode");
+            Err(UnsupportedProgramError::NonAssertZeroOpcode(
+                index,
+                format!("{:?}", opcode),
+            ))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_evaluate() {
+        // 2 * w0 * w1 - w2 + 3
+        let gate = AcirArithGate {
+            mul_terms: vec![(F::from(2), WitnessID(0), WitnessID(1))],
+            add_terms: vec![(-F::one(), WitnessID(2))],
+            constant_term: F::from(3),
+        };
+
+        let witness = |values: [u64; 3]| {
+            Witness(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &v)| (WitnessID(i as u32), F::from(v)))
+                    .collect(),
+            )
+        };
+
+        assert_eq!(gate.evaluate(&witness([2, 3, 1])).unwrap(), F::from(14));
+        assert!(gate.is_satisfied(&witness([2, 3, 15])).unwrap());
+        assert!(!gate.is_satisfied(&witness([2, 3, 14])).unwrap());
+
+        let partial = Witness([(WitnessID(0), F::one())].into_iter().collect());
+        assert!(matches!(
+            gate.evaluate(&partial),
+            Err(Error::MissingWitness(1))
+        ));
+    }
+
+    #[test]
+    fn test_constant_assignment() {
+        // 2 * w0 - 6 = 0
+        let gate = AcirArithGate {
+            mul_terms: vec![],
+            add_terms: vec![(F::from(2), WitnessID(0))],
+            constant_term: -F::from(6),
+        };
+        assert_eq!(gate.constant_assignment(), Some((WitnessID(0), F::from(3))));
+
+        let gate = AcirArithGate {
+            mul_terms: vec![],
+            add_terms: vec![(F::one(), WitnessID(0)), (F::one(), WitnessID(1))],
+            constant_term: F::zero(),
+        };
+        assert_eq!(gate.constant_assignment(), None);
+    }
+
+    #[test]
+    fn test_substitute() {
+        // 2 * w0 * w1 + w0 * w2 - w3 + 1
+        let gate = AcirArithGate {
+            mul_terms: vec![
+                (F::from(2), WitnessID(0), WitnessID(1)),
+                (F::one(), WitnessID(0), WitnessID(2)),
+            ],
+            add_terms: vec![(-F::one(), WitnessID(3))],
+            constant_term: F::one(),
+        };
+
+        // w0 = 3, w1 = 4: 3 * w2 - w3 + 25
+        let constants = [(WitnessID(0), F::from(3)), (WitnessID(1), F::from(4))]
+            .into_iter()
+            .collect();
+        let folded = gate.substitute(&constants);
+        assert!(folded.mul_terms.is_empty());
+        assert_eq!(
+            folded.add_terms,
+            vec![(F::from(3), WitnessID(2)), (-F::one(), WitnessID(3))]
+        );
+        assert_eq!(folded.constant_term, F::from(25));
+        assert!(!folded.is_trivial());
+
+        // w2 = 1, w3 = 28 leaves 0 = 0
+        let constants = [(WitnessID(2), F::one()), (WitnessID(3), F::from(28))]
+            .into_iter()
+            .collect();
+        assert!(folded.substitute(&constants).is_trivial());
+    }
+
+    #[test]
+    fn test_conversions() {
+        use crate::field::ConversionDirection;
+
+        type AF = ark_bn254::Fr;
+        type OtherAF = ark_pallas::Fr;
+
+        // 2 * w0 * w1 - w2 + 3
+        let gate = AcirArithGate {
+            mul_terms: vec![(F::from(2), WitnessID(0), WitnessID(1))],
+            add_terms: vec![(-F::one(), WitnessID(2))],
+            constant_term: F::from(3),
+        };
+
+        let expr: Expression<GenericFieldElement<AF>> = gate.clone().try_into().unwrap();
+        let back: AcirArithGate<F> = expr.try_into().unwrap();
+        assert_eq!(back.mul_terms, gate.mul_terms);
+        assert_eq!(back.add_terms, gate.add_terms);
+        assert_eq!(back.constant_term, gate.constant_term);
+
+        let failure = |result: Result<(), Error>| match result {
+            Err(Error::FieldConversionError(e)) => (e.direction, e.site),
+            result => panic!("unexpected {:?}", result),
+        };
+
+        let to_other = Expression::<GenericFieldElement<OtherAF>>::try_from(gate).map(|_| ());
+        assert_eq!(
+            failure(to_other),
+            (
+                ConversionDirection::FfToArk,
+                Some(ConversionSite::GateCoefficient)
+            )
+        );
+
+        let constant = Expression::<GenericFieldElement<OtherAF>>::from_field(
+            GenericFieldElement::from_repr(OtherAF::from(3)),
+        );
+        let from_other = AcirArithGate::<F>::try_from(constant.clone()).map(|_| ());
+        assert_eq!(
+            failure(from_other),
+            (
+                ConversionDirection::ArkToFf,
+                Some(ConversionSite::ConstantTerm)
+            )
+        );
+
+        assert!(matches!(
+            AcirArithGate::<F>::try_from((4, Opcode::AssertZero(constant))),
+            Err(UnsupportedProgramError::UnconvertibleOpcode(4, _))
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+//! A halo2 circuit over the Plonk lowering of a circuit structure.
+//!
+//! Every row of the `PlonkLayout` is a row of a single region with the
+//! standard Plonk gate, the cells of a wire are copy constrained to its
+//! first cell, and the instance column holds the public inputs then the
+//! public outputs.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use ff::PrimeField;
+use halo2_proofs::{
+    circuit::{Cell, Layouter, SimpleFloorPlanner, Value},
+    plonk::{self, Advice, Circuit, Column, ConstraintSystem, Fixed, Instance},
+    poly::Rotation,
+};
+use ivc_program::witness::Witness;
+
+use crate::{
+    plonk::{PlonkLayout, Wire},
+    program::CircuitStructure,
+    Error,
+};
+
+#[derive(Clone, Debug)]
+pub struct PlonkConfig {
+    advice: [Column<Advice>; 3],
+    q_a: Column<Fixed>,
+    q_b: Column<Fixed>,
+    q_c: Column<Fixed>,
+    q_m: Column<Fixed>,
+    q_const: Column<Fixed>,
+    instance: Column<Instance>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NoirHalo2Circuit<F> {
+    pub layout: Arc<PlonkLayout<F>>,
+    values: Option<Vec<[F; 3]>>,
+}
+
+impl<F: PrimeField> NoirHalo2Circuit<F> {
+    /// The circuit without witness, for the keys
+    pub fn new(structure: &CircuitStructure<F>) -> Self {
+        Self {
+            layout: Arc::new(PlonkLayout::from_structure(structure)),
+            values: None,
+        }
+    }
+
+    /// The circuit of a step, with the witness returned by the execution
+    pub fn with_witness(&self, witness: &Witness<F>) -> Result<Self, Error> {
+        Ok(Self {
+            layout: self.layout.clone(),
+            values: Some(self.layout.assign(witness)?),
+        })
+    }
+
+    /// Values of the instance column for a witness
+    pub fn instance(&self, witness: &Witness<F>) -> Result<Vec<F>, Error> {
+        self.layout
+            .instance
+            .iter()
+            .map(|id| {
+                witness
+                    .0
+                    .get(id)
+                    .cloned()
+                    .ok_or(Error::MissingWitness(id.0))
+            })
+            .collect()
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for NoirHalo2Circuit<F> {
+    type Config = PlonkConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            layout: self.layout.clone(),
+            values: None,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> PlonkConfig {
+        let advice = [(); 3].map(|_| meta.advice_column());
+        let instance = meta.instance_column();
+
+        for column in advice {
+            meta.enable_equality(column);
+        }
+        meta.enable_equality(instance);
+
+        let config = PlonkConfig {
+            advice,
+            q_a: meta.fixed_column(),
+            q_b: meta.fixed_column(),
+            q_c: meta.fixed_column(),
+            q_m: meta.fixed_column(),
+            q_const: meta.fixed_column(),
+            instance,
+        };
+
+        meta.create_gate("standard plonk", |meta| {
+            let [a, b, c] = advice.map(|column| meta.query_advice(column, Rotation::cur()));
+            let q_a = meta.query_fixed(config.q_a, Rotation::cur());
+            let q_b = meta.query_fixed(config.q_b, Rotation::cur());
+            let q_c = meta.query_fixed(config.q_c, Rotation::cur());
+            let q_m = meta.query_fixed(config.q_m, Rotation::cur());
+            let q_const = meta.query_fixed(config.q_const, Rotation::cur());
+
+            vec![q_a * a.clone() + q_b * b.clone() + q_c * c + q_m * a * b + q_const]
+        });
+
+        config
+    }
+
+    fn synthesize(
+        &self,
+        config: PlonkConfig,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), plonk::Error> {
+        let cells = layouter.assign_region(
+            || "gates",
+            |mut region| {
+                let mut cells: BTreeMap<Wire, Cell> = BTreeMap::new();
+
+                for (i, row) in self.layout.rows.iter().enumerate() {
+                    for (column, q) in [
+                        (config.q_a, row.q_a),
+                        (config.q_b, row.q_b),
+                        (config.q_c, row.q_c),
+                        (config.q_m, row.q_m),
+                        (config.q_const, row.q_const),
+                    ] {
+                        region.assign_fixed(|| "selector", column, i, || Value::known(q))?;
+                    }
+
+                    for (k, wire) in row.wires.iter().enumerate() {
+                        let value = match &self.values {
+                            Some(values) => Value::known(values[i][k]),
+                            None => Value::unknown(),
+                        };
+                        let cell = region
+                            .assign_advice(|| "wire", config.advice[k], i, || value)?
+                            .cell();
+
+                        if let Some(wire) = wire {
+                            match cells.get(wire) {
+                                Some(first) => region.constrain_equal(*first, cell)?,
+                                None => {
+                                    cells.insert(*wire, cell);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                Ok(cells)
+            },
+        )?;
+
+        for (i, id) in self.layout.instance.iter().enumerate() {
+            layouter.constrain_instance(cells[&Wire::Witness(*id)], config.instance, i)?;
+        }
+
+        Ok(())
+    }
+}
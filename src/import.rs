@@ -0,0 +1,282 @@
+//! Import of circom constraint systems (`.r1cs` with its `.sym`) and
+//! witnesses (`.wtns`).
+//!
+//! Wire `w > 0` becomes witness `w - 1`, wire 0 is the constant one. Every
+//! constraint `(A·z) * (B·z) = C·z` is expanded into an arithmetic gate, so
+//! with the quadratic encoding a constraint whose A and B have a single term
+//! compiles back to a single constraint.
+//!
+//! Note: ACVM cannot solve such gates in general, so steps of an imported
+//! structure are built with `make_step` from a witness computed by circom
+//! (see `read_wtns`).
+
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use ff::PrimeField;
+use ivc_program::{
+    program::{IOProfile, WitnessID},
+    witness::Witness,
+};
+
+use crate::{
+    field::{ff_from_le_bytes, modulus_to_le_bytes, ConversionSite},
+    gate::AcirArithGate,
+    layout::IOLayout,
+    program::CircuitStructure,
+    r1cs::R1CSEncoding,
+    Error,
+};
+
+/// Terms of a linear combination as `(wire, coefficient)`
+pub type CircomLC<F> = Vec<(u32, F)>;
+
+pub struct CircomR1CS<F> {
+    pub num_wires: u32,
+    pub num_pub_out: u32,
+    pub num_pub_in: u32,
+    pub num_prv_in: u32,
+    pub constraints: Vec<(CircomLC<F>, CircomLC<F>, CircomLC<F>)>,
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::ImportError("unexpected end of file".to_string()));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Sections by type, after checking the magic
+    fn sections(mut self, magic: &[u8; 4]) -> Result<BTreeMap<u32, &'a [u8]>, Error> {
+        if self.take(4)? != magic {
+            return Err(Error::ImportError("wrong magic".to_string()));
+        }
+        let _version = self.u32()?;
+
+        let mut sections = BTreeMap::new();
+        for _ in 0..self.u32()? {
+            let section_type = self.u32()?;
+            let size = self.u64()? as usize;
+            sections.insert(section_type, self.take(size)?);
+        }
+
+        Ok(sections)
+    }
+}
+
+fn section<'a>(sections: &BTreeMap<u32, &'a [u8]>, section_type: u32) -> Result<Cursor<'a>, Error> {
+    let bytes = sections
+        .get(&section_type)
+        .ok_or_else(|| Error::ImportError(format!("missing section {}", section_type)))?;
+    Ok(Cursor { bytes })
+}
+
+/// Read the field size and check the prime matches `F`
+fn read_field<F: PrimeField>(cursor: &mut Cursor) -> Result<usize, Error> {
+    let n8 = cursor.u32()? as usize;
+    if cursor.take(n8)? != modulus_to_le_bytes::<F>(n8) {
+        return Err(Error::ImportError(
+            "prime does not match the field".to_string(),
+        ));
+    }
+    Ok(n8)
+}
+
+pub fn read_circom_r1cs<F: PrimeField>(reader: &mut impl Read) -> Result<CircomR1CS<F>, Error> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let sections = Cursor { bytes: &bytes }.sections(b"r1cs")?;
+
+    let mut header = section(&sections, 1)?;
+    let n8 = read_field::<F>(&mut header)?;
+    let num_wires = header.u32()?;
+    let num_pub_out = header.u32()?;
+    let num_pub_in = header.u32()?;
+    let num_prv_in = header.u32()?;
+    let _num_labels = header.u64()?;
+    let num_constraints = header.u32()?;
+
+    let mut content = section(&sections, 2)?;
+    let mut lc = || -> Result<CircomLC<F>, Error> {
+        (0..content.u32()?)
+            .map(|_| {
+                let wire = content.u32()?;
+                let coefficient = ff_from_le_bytes(content.take(n8)?)
+                    .map_err(|e| e.at_site(ConversionSite::GateCoefficient))?;
+                Ok((wire, coefficient))
+            })
+            .collect()
+    };
+
+    let constraints = (0..num_constraints)
+        .map(|_| Ok((lc()?, lc()?, lc()?)))
+        .collect::<Result<_, Error>>()?;
+
+    Ok(CircomR1CS {
+        num_wires,
+        num_pub_out,
+        num_pub_in,
+        num_prv_in,
+        constraints,
+    })
+}
+
+/// Names of the wires from a `.sym` file, with lines
+/// `label,wire,component,name` (wire `-1` for removed signals)
+pub fn read_sym(reader: impl BufRead) -> Result<BTreeMap<u32, String>, Error> {
+    let mut names = BTreeMap::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.splitn(4, ',').collect();
+        if let [_, wire, _, name] = fields[..] {
+            if let Ok(wire) = wire.parse::<u32>() {
+                names.insert(wire, name.to_string());
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+fn witness_of(wire: u32) -> WitnessID {
+    WitnessID(wire - 1)
+}
+
+/// `(sum a) * (sum b) - (sum c) = 0` as a gate
+fn constraint_gate<F: PrimeField>(
+    a: &CircomLC<F>,
+    b: &CircomLC<F>,
+    c: &CircomLC<F>,
+) -> AcirArithGate<F> {
+    let mut gate = AcirArithGate {
+        mul_terms: vec![],
+        add_terms: vec![],
+        constant_term: F::ZERO,
+    };
+
+    for (wire_a, coeff_a) in a {
+        for (wire_b, coeff_b) in b {
+            let coeff = *coeff_a * coeff_b;
+            match (*wire_a, *wire_b) {
+                (0, 0) => gate.constant_term += coeff,
+                (0, wire) | (wire, 0) => gate.add_terms.push((coeff, witness_of(wire))),
+                (left, right) => gate
+                    .mul_terms
+                    .push((coeff, witness_of(left), witness_of(right))),
+            }
+        }
+    }
+
+    for (wire, coeff) in c {
+        match wire {
+            0 => gate.constant_term -= coeff,
+            wire => gate.add_terms.push((-*coeff, witness_of(*wire))),
+        }
+    }
+
+    gate
+}
+
+impl<F: PrimeField> CircomR1CS<F> {
+    /// Build a structure, naming the IO after `names` (see `read_sym`)
+    pub fn into_structure(self, names: &BTreeMap<u32, String>) -> CircuitStructure<F> {
+        let wires = |start: u32, len: u32| (start..start + len).map(witness_of).collect::<Vec<_>>();
+
+        let public_outputs = wires(1, self.num_pub_out);
+        let public_inputs = wires(1 + self.num_pub_out, self.num_pub_in);
+        let private_inputs = wires(1 + self.num_pub_out + self.num_pub_in, self.num_prv_in);
+
+        let io = IOProfile {
+            public_inputs: public_inputs.iter().cloned().collect(),
+            private_inputs: private_inputs.iter().cloned().collect(),
+            public_outputs: public_outputs.iter().cloned().collect(),
+            private_outputs: Default::default(),
+        };
+
+        let parameters = public_outputs
+            .iter()
+            .chain(&public_inputs)
+            .chain(&private_inputs)
+            .map(|id| {
+                let name = names.get(&(id.0 + 1)).cloned().unwrap_or_default();
+                (name, vec![*id])
+            })
+            .collect();
+
+        let gates = self
+            .constraints
+            .iter()
+            .map(|(a, b, c)| constraint_gate(a, b, c))
+            .collect();
+
+        let mut structure =
+            CircuitStructure::from_gates(gates, io).with_encoding(R1CSEncoding::Quadratic);
+
+        structure.layout = Some(IOLayout {
+            public_inputs,
+            private_inputs,
+            public_outputs,
+            private_outputs: vec![],
+            parameters,
+        });
+
+        structure
+    }
+}
+
+/// Import a circom `.r1cs` file with its `.sym` file
+pub fn from_circom_r1cs<F: PrimeField, P: AsRef<Path>, Q: AsRef<Path>>(
+    r1cs_path: P,
+    sym_path: Q,
+) -> Result<CircuitStructure<F>, Error> {
+    let r1cs = read_circom_r1cs::<F>(&mut std::fs::File::open(r1cs_path)?)?;
+    let names = read_sym(BufReader::new(std::fs::File::open(sym_path)?))?;
+    Ok(r1cs.into_structure(&names))
+}
+
+/// Read a `.wtns` witness, dropping the constant one of wire 0
+pub fn read_wtns<F: PrimeField>(reader: &mut impl Read) -> Result<Witness<F>, Error> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let sections = Cursor { bytes: &bytes }.sections(b"wtns")?;
+
+    let mut header = section(&sections, 1)?;
+    let n8 = read_field::<F>(&mut header)?;
+    let num_witness = header.u32()?;
+
+    let mut values = section(&sections, 2)?;
+    let _one = values.take(n8)?;
+
+    Ok(Witness(
+        (1..num_witness)
+            .map(|wire| {
+                let value = ff_from_le_bytes(values.take(n8)?)
+                    .map_err(|e| e.at_site(ConversionSite::WitnessValue))?;
+                Ok((witness_of(wire), value))
+            })
+            .collect::<Result<_, Error>>()?,
+    ))
+}
+
+pub fn from_wtns<F: PrimeField, P: AsRef<Path>>(path: P) -> Result<Witness<F>, Error> {
+    read_wtns(&mut std::fs::File::open(path)?)
+}
@@ -0,0 +1,185 @@
+//! Reading of inputs from JSON.
+//!
+//! An input file holds a list of field elements, each of them either:
+//! - a number, e.g. `5` or `-1`, up to 64 bits in JSON
+//! - a decimal string, e.g. `"5"` or `"-1"`
+//! - a hexadecimal string, e.g. `"0x05"` or `"-0x01"`
+//! - a boolean, `false` for 0 and `true` for 1
+//! - an array of 32 bytes, little-endian
+//!
+//! A negative value `-x` is the field element `p - x`. Values of the field
+//! modulus or more are reduced, except byte arrays which must be canonical.
+
+use std::{fmt, fs::File, io::BufReader, marker::PhantomData, path::Path};
+
+use ff::PrimeField;
+use ivc_program::input::IO;
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{
+    field::{ff_from_canonical_le_bytes, ConversionSite, FieldConversionError},
+    Error,
+};
+
+/// A field element with any of the encodings of the module
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldValue<F>(pub F);
+
+fn parse_hex<F: PrimeField>(text: &str) -> Option<F> {
+    let value = num::BigUint::parse_bytes(text.as_bytes(), 16)?;
+    F::from_str_vartime(&value.to_string())
+}
+
+impl<F: PrimeField> std::str::FromStr for FieldValue<F> {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let trimmed = text.trim();
+        let (negative, magnitude) = match trimmed.strip_prefix('-') {
+            Some(magnitude) => (true, magnitude),
+            None => (false, trimmed),
+        };
+
+        let value = match magnitude
+            .strip_prefix("0x")
+            .or_else(|| magnitude.strip_prefix("0X"))
+        {
+            Some(hex) => parse_hex::<F>(hex),
+            None => F::from_str_vartime(magnitude),
+        };
+
+        value
+            .map(|value| FieldValue(if negative { -value } else { value }))
+            .ok_or_else(|| {
+                Error::from(FieldConversionError::parse::<F>(text.to_string()))
+                    .at_site(ConversionSite::Input)
+            })
+    }
+}
+
+struct FieldValueVisitor<F>(PhantomData<F>);
+
+impl<'de, F: PrimeField> Visitor<'de> for FieldValueVisitor<F> {
+    type Value = FieldValue<F>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number, a decimal or hexadecimal string, a boolean or 32 bytes")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(FieldValue(F::from(value as u64)))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(FieldValue(F::from(value)))
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<Self::Value, E> {
+        Ok(FieldValue(F::from_u128(value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        let magnitude = F::from(value.unsigned_abs());
+        Ok(FieldValue(match value < 0 {
+            true => -magnitude,
+            false => magnitude,
+        }))
+    }
+
+    fn visit_i128<E: de::Error>(self, value: i128) -> Result<Self::Value, E> {
+        let magnitude = F::from_u128(value.unsigned_abs());
+        Ok(FieldValue(match value < 0 {
+            true => -magnitude,
+            false => magnitude,
+        }))
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Self::Value, E> {
+        text.parse().map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(32);
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        if bytes.len() != 32 {
+            return Err(de::Error::invalid_length(bytes.len(), &self));
+        }
+
+        let value = ff_from_canonical_le_bytes::<F>(&bytes).map_err(de::Error::custom)?;
+        Ok(FieldValue(value))
+    }
+}
+
+impl<'de, F: PrimeField> Deserialize<'de> for FieldValue<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FieldValueVisitor(PhantomData))
+    }
+}
+
+/// Parse a JSON list of field elements
+pub fn parse_io<F: PrimeField>(json_text: &str) -> Result<IO<F>, Error> {
+    let values: Vec<FieldValue<F>> =
+        serde_json::from_str(json_text).map_err(std::io::Error::from)?;
+    Ok(IO(values.into_iter().map(|value| value.0).collect()))
+}
+
+/// Read a JSON list of field elements from a file
+pub fn read_io<F: PrimeField, P: AsRef<Path>>(path: P) -> Result<IO<F>, Error> {
+    let reader = BufReader::new(File::open(path)?);
+    let values: Vec<FieldValue<F>> =
+        serde_json::from_reader(reader).map_err(std::io::Error::from)?;
+    Ok(IO(values.into_iter().map(|value| value.0).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::ConversionDirection;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_encodings() {
+        let mut bytes = vec![0u8; 32];
+        bytes[0] = 2;
+        bytes[1] = 1;
+
+        let io: IO<F> = parse_io(&format!(
+            r#"[258, "258", "0x102", "0X0102", {:?}, "-1", -1, "340282366920938463463374607431768211455"]"#,
+            bytes
+        ))
+        .unwrap();
+
+        assert_eq!(io.0[..5], [F::from(258); 5]);
+        assert_eq!(io.0[5], -F::from(1));
+        assert_eq!(io.0[6], -F::from(1));
+        assert_eq!(io.0[7], F::from_u128(u128::MAX));
+
+        let io: IO<F> = parse_io(r#"[true, false, -258, "-0x102", "-258", " 7 "]"#).unwrap();
+        assert_eq!(io.0[..2], [F::from(1), F::from(0)]);
+        assert_eq!(io.0[2..5], [-F::from(258); 3]);
+        assert_eq!(io.0[5], F::from(7));
+
+        let e = "five".parse::<FieldValue<F>>().unwrap_err();
+        assert!(matches!(
+            &e,
+            Error::FieldConversionError(FieldConversionError {
+                direction: ConversionDirection::Parse,
+                site: Some(ConversionSite::Input),
+                ..
+            })
+        ));
+        assert!(e.to_string().contains("five"));
+
+        assert!(parse_io::<F>(r#"["0xzz"]"#).is_err());
+        assert!(parse_io::<F>(r#"["--1"]"#).is_err());
+        assert!(parse_io::<F>(r#"["five"]"#).is_err());
+        assert!(parse_io::<F>("[[1, 2]]").is_err());
+        assert!(parse_io::<F>(&format!("[{:?}]", [255u8; 32])).is_err());
+    }
+}
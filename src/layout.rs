@@ -0,0 +1,230 @@
+//! Ordering of the IO following the Noir ABI.
+//!
+//! The IO sets of the program are ordered by witness index. An `IOLayout`
+//! orders them as in the Noir signature instead: parameters in declaration
+//! order, each flattened (arrays element by element, struct fields in
+//! declaration order), then the return value.
+
+use std::collections::BTreeSet;
+
+use ivc_program::{input::IO, program::WitnessID, witness::Witness};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IOLayout {
+    pub public_inputs: Vec<WitnessID>,
+    pub private_inputs: Vec<WitnessID>,
+    pub public_outputs: Vec<WitnessID>,
+    pub private_outputs: Vec<WitnessID>,
+
+    /// Parameter names with their flattened witnesses
+    pub parameters: Vec<(String, Vec<WitnessID>)>,
+
+    /// Field counts of the public return value: of every element of a
+    /// returned tuple, or of the value itself
+    #[serde(default)]
+    pub public_return: Vec<u32>,
+}
+
+/// Number of field elements of an ABI type, in its serialized form
+pub(crate) fn abi_field_count(typ: &serde_json::Value) -> Result<u32, Error> {
+    let malformed = || Error::AbiMismatch(format!("unknown ABI type {}", typ));
+    let length = || {
+        typ["length"]
+            .as_u64()
+            .map(|x| x as u32)
+            .ok_or_else(malformed)
+    };
+    let fields = || typ["fields"].as_array().ok_or_else(malformed);
+
+    match typ["kind"].as_str().ok_or_else(malformed)? {
+        "field" | "integer" | "boolean" => Ok(1),
+        "string" => length(),
+        "array" => Ok(length()? * abi_field_count(&typ["type"])?),
+        "struct" => fields()?
+            .iter()
+            .map(|field| abi_field_count(&field["type"]))
+            .sum(),
+        "tuple" => fields()?.iter().map(abi_field_count).sum(),
+        _ => Err(malformed()),
+    }
+}
+
+impl IOLayout {
+    /// Build the layout in the ACIR numbering from the `abi` of a compiled
+    /// Noir program. Noir assigns the parameters the leading witnesses in
+    /// declaration order. The return witnesses are not ordered by the ABI,
+    /// so they are kept in witness order
+    pub fn from_abi(
+        abi: &serde_json::Value,
+        return_values: &BTreeSet<WitnessID>,
+        private_outputs: &BTreeSet<WitnessID>,
+    ) -> Result<Self, Error> {
+        let parameters = abi["parameters"]
+            .as_array()
+            .ok_or_else(|| Error::AbiMismatch("missing ABI parameters".to_string()))?;
+
+        let mut layout = Self::default();
+        let mut next_witness = 0;
+
+        for parameter in parameters {
+            let count = abi_field_count(&parameter["type"])?;
+            let witnesses: Vec<WitnessID> = (next_witness..next_witness + count)
+                .map(WitnessID)
+                .collect();
+            next_witness += count;
+
+            // a public parameter returned as is belongs to the outputs
+            let inputs = witnesses.iter().filter(|id| !return_values.contains(id));
+            if parameter["visibility"] == "public" {
+                layout.public_inputs.extend(inputs);
+            } else {
+                layout.private_inputs.extend(inputs);
+            }
+
+            let name = parameter["name"].as_str().unwrap_or_default().to_string();
+            layout.parameters.push((name, witnesses));
+        }
+
+        for id in return_values {
+            if private_outputs.contains(id) {
+                layout.private_outputs.push(*id);
+            } else {
+                layout.public_outputs.push(*id);
+            }
+        }
+
+        let return_type = &abi["return_type"];
+        if return_type["visibility"] == "public" {
+            let typ = &return_type["abi_type"];
+            layout.public_return = match typ["kind"].as_str() {
+                Some("tuple") => typ["fields"]
+                    .as_array()
+                    .ok_or_else(|| Error::AbiMismatch(format!("unknown ABI type {}", typ)))?
+                    .iter()
+                    .map(abi_field_count)
+                    .collect::<Result<_, _>>()?,
+                _ => vec![abi_field_count(typ)?],
+            };
+        }
+
+        Ok(layout)
+    }
+
+    /// Field counts of the public parameters in declaration order, without
+    /// the witnesses returned as is
+    pub fn public_input_shape(&self) -> Vec<usize> {
+        let public: BTreeSet<&WitnessID> = self.public_inputs.iter().collect();
+        self.parameters
+            .iter()
+            .map(|(_, ids)| ids.iter().filter(|id| public.contains(id)).count())
+            .filter(|count| *count > 0)
+            .collect()
+    }
+
+    /// Field counts of the public return value, unless they do not cover
+    /// the public outputs, e.g. once `specialize` removed some
+    pub fn public_output_shape(&self) -> Option<Vec<usize>> {
+        let shape: Vec<usize> = self
+            .public_return
+            .iter()
+            .map(|count| *count as usize)
+            .collect();
+        (shape.iter().sum::<usize>() == self.public_outputs.len()).then_some(shape)
+    }
+
+    /// Apply `f` to every witness, dropping the ones it maps to `None`
+    pub fn map_witnesses(&self, f: impl Fn(WitnessID) -> Option<WitnessID>) -> Self {
+        let map = |ids: &[WitnessID]| ids.iter().filter_map(|id| f(*id)).collect();
+
+        Self {
+            public_inputs: map(&self.public_inputs),
+            private_inputs: map(&self.private_inputs),
+            public_outputs: map(&self.public_outputs),
+            private_outputs: map(&self.private_outputs),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|(name, ids)| (name.clone(), map(ids)))
+                .collect(),
+            public_return: self.public_return.clone(),
+        }
+    }
+
+    /// Assign the values of an IO vector to the witnesses `ids`, in order
+    pub fn make_witness<F: Clone>(ids: &[WitnessID], io: &IO<F>) -> Result<Witness<F>, Error> {
+        if ids.len() != io.0.len() {
+            return Err(Error::InvalidInput);
+        }
+
+        Ok(Witness(
+            ids.iter().cloned().zip(io.0.iter().cloned()).collect(),
+        ))
+    }
+
+    /// Read the witnesses `ids` of a witness into an IO vector, in order
+    pub fn make_io<F: Clone>(ids: &[WitnessID], witness: &Witness<F>) -> Result<IO<F>, Error> {
+        Ok(IO(ids
+            .iter()
+            .map(|id| {
+                witness
+                    .0
+                    .get(id)
+                    .cloned()
+                    .ok_or(Error::MissingWitness(id.0))
+            })
+            .collect::<Result<_, _>>()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_from_abi() {
+        // fn main(x: [Field; 2], s: pub (Field, bool)) -> pub Field
+        let abi = serde_json::json!({
+            "parameters": [
+                {
+                    "name": "x",
+                    "type": {"kind": "array", "length": 2, "type": {"kind": "field"}},
+                    "visibility": "private"
+                },
+                {
+                    "name": "s",
+                    "type": {"kind": "tuple", "fields": [{"kind": "field"}, {"kind": "boolean"}]},
+                    "visibility": "public"
+                }
+            ],
+            "return_type": {"abi_type": {"kind": "field"}, "visibility": "public"},
+            "error_types": {}
+        });
+
+        let ids = |ids: &[u32]| ids.iter().cloned().map(WitnessID).collect::<Vec<_>>();
+
+        let return_values = [WitnessID(7)].into_iter().collect();
+        let layout = IOLayout::from_abi(&abi, &return_values, &Default::default()).unwrap();
+
+        assert_eq!(layout.private_inputs, ids(&[0, 1]));
+        assert_eq!(layout.public_inputs, ids(&[2, 3]));
+        assert_eq!(layout.public_outputs, ids(&[7]));
+        assert_eq!(layout.parameters[1], ("s".to_string(), ids(&[2, 3])));
+        assert_eq!(layout.public_input_shape(), [2]);
+        assert_eq!(layout.public_output_shape(), Some(vec![1]));
+
+        let io = IO(vec![F::from(5), F::from(6)]);
+        let witness = IOLayout::make_witness(&layout.public_inputs, &io).unwrap();
+        assert_eq!(witness.0[&WitnessID(3)], F::from(6));
+        assert_eq!(
+            IOLayout::make_io(&layout.public_inputs, &witness)
+                .unwrap()
+                .0,
+            io.0
+        );
+    }
+}
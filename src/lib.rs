@@ -2,16 +2,53 @@ use ivc_program::witness::Witness;
 use serde::{Deserialize, Serialize};
 
 pub mod constants {
-    pub const CURVE_BN254: &str = "halo2curves::bn256::fr::Fr";
-    pub const CURVE_BN254_ARK: &str = "ark_ff::fields::models::fp::Fp<ark_ff::fields::models::fp::montgomery_backend::MontBackend<ark_bn254::fields::fr::FrConfig, 4>, 4>";
     pub const NOIR_VERSION_0_33: &str = "0.33.0+325dac54efb6f99201de9fdeb0a507d45189607d";
 }
 
+pub mod export;
+pub mod import;
+pub mod inputs;
+#[cfg(feature = "nova")]
+pub mod nova_scotia;
+#[cfg(feature = "nova")]
+pub mod prove;
+pub mod session;
+
+#[cfg(feature = "ark")]
+mod ark;
+mod batch;
+mod blinding;
+mod brillig;
+mod curve;
+mod debug;
+mod dense;
 mod execute;
 mod field;
 mod gate;
+#[cfg(feature = "halo2")]
+mod halo2;
+mod layout;
 mod load;
+mod lowering;
+mod memory;
+mod merkle;
+#[cfg(feature = "nova")]
+mod nova;
+mod oracle;
+mod partition;
+mod plan;
+mod plonk;
 mod program;
+mod program_set;
+mod r1cs;
+mod renumber;
+#[cfg(feature = "sonobe")]
+mod sonobe;
+#[cfg(feature = "async")]
+mod stream;
+#[cfg(feature = "supernova")]
+mod supernova;
+mod transcript;
 
 #[cfg(test)]
 mod tests;
@@ -21,17 +58,162 @@ pub enum Error {
     #[error("Unsupported program: {0}")]
     UnsupportedProgram(#[from] load::UnsupportedProgramError),
 
-    #[error("Field conversion error {0}")]
-    FieldConversionError(String),
+    #[error("Unsupported Noir version {0}")]
+    UnsupportedVersion(String),
+
+    #[error("Unsupported curve {0}")]
+    UnsupportedCurve(String),
+
+    #[error("Curve mismatch: expected {expected:?}, found {found:?}")]
+    CurveMismatch {
+        expected: Option<Curve>,
+        found: Option<Curve>,
+    },
+
+    #[error("Field conversion error: {0}")]
+    FieldConversionError(#[from] field::FieldConversionError),
 
     #[error("Invalid input")]
     InvalidInput,
 
+    #[error("Invalid artifact: {0}")]
+    InvalidArtifact(&'static str),
+
+    #[error("Unsupported artifact format version {0}")]
+    UnsupportedArtifactVersion(u8),
+
+    #[error("Invalid calldata: {0}")]
+    InvalidCalldata(&'static str),
+
+    #[error("Invalid program set: {0}")]
+    InvalidProgramSet(&'static str),
+
+    #[error("Program counter selects no circuit at step {0}")]
+    InvalidProgramCounter(u64),
+
+    #[error("Invalid execution plan: {0}")]
+    InvalidExecutionPlan(&'static str),
+
+    #[error("Input does not match the IO profile: {0}")]
+    InputMismatch(#[from] InputMismatch),
+
+    #[error("Witness {0} is not assigned")]
+    MissingWitness(u32),
+
     #[error("IVCProgram error: {0}")]
     IVCProgramError(#[from] ivc_program::Error),
 
     #[error("ACVM Solving error: {0}")]
     ACVMSolveError(String),
+
+    #[error("Assertion failed: {message}{} (opcode {opcode_index:?})", at(.location))]
+    AssertionFailed {
+        message: String,
+        opcode_index: Option<usize>,
+        /// Set if the structure has a `source_map`
+        location: Option<SourceLocation>,
+    },
+
+    #[error(
+        "Solving stalled{} at opcode {opcode_index}, unassigned witnesses {missing_witnesses:?}",
+        at(.location)
+    )]
+    SolveStalled {
+        opcode_index: usize,
+        /// In the ACIR numbering
+        missing_witnesses: Vec<u32>,
+        location: Option<SourceLocation>,
+    },
+
+    #[error("Memory checking error: {0}")]
+    MemoryCheckingError(String),
+
+    #[error("Brillig error: {0}")]
+    BrilligError(String),
+
+    #[error("No resolver for oracle call: {0}")]
+    UnresolvedOracle(String),
+
+    #[error("Gate lowering {0} is not registered")]
+    MissingLowering(String),
+
+    #[error("ABI does not match the circuit: {0}")]
+    AbiMismatch(String),
+
+    #[error("IO error: {0}")]
+    IOError(#[from] std::io::Error),
+
+    #[error("Debug info error: {0}")]
+    DebugInfoError(String),
+
+    #[error("Import error: {0}")]
+    ImportError(String),
+
+    #[error("Too many witnesses: index {0} does not fit in 32 bits")]
+    TooManyWitnesses(u64),
+
+    #[error("Too many {what}: {count} exceeds the limit of {limit}")]
+    LimitExceeded {
+        what: &'static str,
+        count: usize,
+        limit: usize,
+    },
+
+    #[error("Gate {gate_index} (opcode {opcode_index}) can never be satisfied: {message:?}")]
+    UnsatisfiableGate {
+        gate_index: usize,
+        opcode_index: usize,
+        message: Option<String>,
+    },
+
+    #[error("Step witness does not satisfy R1CS constraint {0}")]
+    UnsatisfiedConstraint(usize),
+
+    #[error("Execution cancelled")]
+    Cancelled,
+
+    #[error("Solving exceeded {0:?}")]
+    SolveTimeout(std::time::Duration),
+
+    #[error("Replayed step does not match the result: {0}")]
+    ReplayMismatch(&'static str),
+
+    #[error("Broken chain at step {iteration_number}: {reason}")]
+    BrokenChain {
+        iteration_number: u64,
+        reason: &'static str,
+    },
+
+    #[error("Program digest mismatch: expected {expected}, found {found}")]
+    DigestMismatch { expected: String, found: String },
+
+    #[cfg(feature = "nova")]
+    #[error("Nova error: {0}")]
+    NovaError(#[from] nova_snark::errors::NovaError),
+
+    #[cfg(feature = "sonobe")]
+    #[error("Sonobe error: {0}")]
+    SonobeError(#[from] folding_schemes::Error),
+
+    #[cfg(feature = "supernova")]
+    #[error("SuperNova error: {0}")]
+    SuperNovaError(#[from] arecibo::supernova::error::SuperNovaError),
+}
+
+impl Error {
+    /// Record where a field conversion failed, if not known yet
+    pub(crate) fn at_site(mut self, site: ConversionSite) -> Self {
+        if let Error::FieldConversionError(e) = &mut self {
+            e.site.get_or_insert(site);
+        }
+        self
+    }
+}
+
+fn at(location: &Option<SourceLocation>) -> String {
+    location
+        .as_ref()
+        .map_or_else(String::new, |location| format!(" at {}", location))
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -41,30 +223,90 @@ pub struct ExecutionResult<F> {
     pub private_input: Witness<F>,
     pub public_output: Witness<F>,
     pub private_output: Witness<F>,
+
+    #[serde(default)]
+    pub oracle_calls: Vec<OracleCall<F>>,
+
+    /// Digest of the compiled program that produced this result
+    #[serde(default)]
+    pub program_digest: Option<String>,
+
+    #[serde(default)]
+    pub stats: StepStats,
+
+    /// Set if executed with `UnexecutedCircuit::with_transcript`
+    #[serde(default)]
+    pub transcript: Option<TranscriptEntry>,
+
+    /// Commitment to the private input, set if executed with
+    /// `UnexecutedCircuit::with_input_commitment`
+    #[serde(default)]
+    pub input_commitment: Option<F>,
+
+    /// Randomness of `input_commitment`, to open it or prove the step
+    #[serde(default)]
+    pub input_randomness: Option<F>,
+}
+
+/// Counters of a step, to profile a chain
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StepStats {
+    /// Time spent in ACVM
+    pub solve_ms: u128,
+    /// ACIR opcodes solved, including the lowered ones
+    pub opcodes: usize,
+    /// R1CS constraints, `None` if the structure is not compiled
+    pub constraints: Option<usize>,
+    /// Size of the R1CS witness
+    pub witnesses: usize,
 }
 
 pub mod functions {
-    use std::path::Path;
+    use std::{
+        collections::{BTreeMap, BTreeSet, VecDeque},
+        fs::File,
+        io::{BufWriter, Write},
+        path::Path,
+        sync::{mpsc, Arc},
+    };
 
-    use acvm::acir::{acir_field::GenericFieldElement, circuit::Circuit as ACVMCircuit};
+    use acvm::{
+        acir::{
+            acir_field::GenericFieldElement,
+            circuit::{Circuit as ACVMCircuit, Program},
+        },
+        blackbox_solver::BlackBoxFunctionSolver,
+    };
     use ark_ff::PrimeField as ArkPrimeField;
     use arkworks_backend::ProgramArtifactGeneric;
+    use bn254_blackbox_solver::Bn254BlackBoxSolver;
     use ff::PrimeField;
-    use ivc_program::{input::IO, program::IVCProgram, witness::Witness};
+    use ivc_program::{
+        input::IO,
+        program::{IOProfile, IVCProgram, WitnessID},
+        witness::Witness,
+    };
+    use serde::Serialize;
 
     use crate::{
         constants::NOIR_VERSION_0_33,
-        execute::UnexecutedCircuit,
-        load::{check_supported, print_metadata},
-        program::CircuitStructure,
+        curve::check_acvm_field,
+        debug::{source_locations, SourceLocation},
+        execute::{SolvedStep, UnexecutedCircuit},
+        layout::IOLayout,
+        load::{check_supported, print_metadata, ProgramMetadata, UnsupportedProgramError},
+        lowering::LoweringRegistry,
+        oracle::{NoOracle, OracleResolver, RecordedOracle},
+        program::{CircuitStructure, CompileLimits},
         Error, ExecutionResult,
     };
 
-    fn load_circuit<F: ArkPrimeField>(
+    fn load_program<F: ArkPrimeField>(
         program: &[u8],
         print_info: bool,
-    ) -> Result<ACVMCircuit<GenericFieldElement<F>>, Error> {
-        let noir_program: ProgramArtifactGeneric<F> = serde_json::from_slice(program).unwrap();
+    ) -> Result<Program<GenericFieldElement<F>>, Error> {
+        let noir_program: ProgramArtifactGeneric<F> =
+            serde_json::from_slice(program).map_err(std::io::Error::from)?;
 
         let program = noir_program.bytecode;
 
@@ -72,30 +314,100 @@ pub mod functions {
             print_metadata(&program);
         }
 
-        assert_eq!(noir_program.noir_version, NOIR_VERSION_0_33.to_string());
+        if noir_program.noir_version != NOIR_VERSION_0_33 {
+            return Err(Error::UnsupportedVersion(noir_program.noir_version));
+        }
 
         check_supported(&program)?;
 
-        Ok(program.functions[0].clone())
+        Ok(program)
+    }
+
+    fn load_circuit<F: ArkPrimeField>(
+        program: &[u8],
+        print_info: bool,
+    ) -> Result<ACVMCircuit<GenericFieldElement<F>>, Error> {
+        let mut program = load_program(program, print_info)?;
+        Ok(program.functions.swap_remove(0))
     }
 
     /// Load a noir circuit from a file
     /// Adapted from `dmpierre/arkworks_backend`
+    // Note: only the constrained function, use `load_program_from_file` for
+    // the unconstrained functions its Brillig calls need
     pub fn load_circuit_from_file<F: ArkPrimeField, P: AsRef<Path>>(
         circuit_path: P,
         print_info: bool,
     ) -> Result<ACVMCircuit<GenericFieldElement<F>>, Error> {
-        let input_string = std::fs::read(&circuit_path).unwrap();
+        let input_string = std::fs::read(&circuit_path)?;
 
         load_circuit(&input_string, print_info)
     }
 
+    /// Load a noir program from a file, with its unconstrained functions
+    pub fn load_program_from_file<F: ArkPrimeField, P: AsRef<Path>>(
+        circuit_path: P,
+    ) -> Result<Program<GenericFieldElement<F>>, Error> {
+        let input_string = std::fs::read(&circuit_path)?;
+
+        load_program(&input_string, false)
+    }
+
+    /// Describe the program of a Nargo artifact, even if it is not supported
+    /// (see `ProgramMetadata`)
+    pub fn inspect_file<F: PrimeField, AF: ArkPrimeField, P: AsRef<Path>>(
+        circuit_path: P,
+    ) -> Result<ProgramMetadata, Error> {
+        let input_string = std::fs::read(&circuit_path)?;
+        let artifact: serde_json::Value =
+            serde_json::from_slice(&input_string).map_err(std::io::Error::from)?;
+        let abi = artifact["abi"].clone();
+        let noir_program: ProgramArtifactGeneric<AF> =
+            serde_json::from_value(artifact).map_err(std::io::Error::from)?;
+
+        let mut metadata = ProgramMetadata::new::<F, AF>(&noir_program.bytecode, Some(&abi))?;
+        if noir_program.noir_version != NOIR_VERSION_0_33 {
+            metadata.unsupported.get_or_insert_with(|| {
+                Error::UnsupportedVersion(noir_program.noir_version.clone()).to_string()
+            });
+        }
+        metadata.noir_version = Some(noir_program.noir_version);
+
+        Ok(metadata)
+    }
+
+    /// Load the ABI of a noir circuit from a file, to order its IO
+    pub fn load_abi_from_file<P: AsRef<Path>>(circuit_path: P) -> Result<serde_json::Value, Error> {
+        let input_string = std::fs::read(&circuit_path)?;
+        let mut artifact: serde_json::Value =
+            serde_json::from_slice(&input_string).map_err(|e| Error::AbiMismatch(e.to_string()))?;
+
+        Ok(artifact["abi"].take())
+    }
+
+    /// Load the source location of every opcode of a noir circuit from a file,
+    /// to set as `source_map` of its structure
+    pub fn load_debug_info_from_file<P: AsRef<Path>>(
+        circuit_path: P,
+    ) -> Result<BTreeMap<usize, SourceLocation>, Error> {
+        let input_string = std::fs::read(&circuit_path)?;
+        let artifact: serde_json::Value = serde_json::from_slice(&input_string)
+            .map_err(|e| Error::DebugInfoError(e.to_string()))?;
+
+        source_locations(&artifact)
+    }
+
     pub fn load_circuit_from_text<F: ArkPrimeField>(
         json_text: &str,
         print_info: bool,
     ) -> Result<ACVMCircuit<GenericFieldElement<F>>, Error> {
-        let input_string = json_text.to_string();
-        load_circuit(input_string.as_bytes(), print_info)
+        load_circuit(json_text.as_bytes(), print_info)
+    }
+
+    pub fn load_program_from_text<F: ArkPrimeField>(
+        json_text: &str,
+    ) -> Result<Program<GenericFieldElement<F>>, Error> {
+        load_program(json_text.as_bytes(), false)
     }
 
     /// Compile a noir circuit into
@@ -103,32 +415,473 @@ pub mod functions {
     /// 2. an IVC program
     /// 3. a trivial IVC witness
     #[allow(clippy::type_complexity)]
+    // Note: takes a `Program` or, as `load_circuit_from_file` loads, a
+    // single `Circuit`
     pub fn compile<F: PrimeField, AF: ArkPrimeField>(
-        noir_circuit: ACVMCircuit<GenericFieldElement<AF>>,
+        noir_circuit: impl TryInto<CircuitStructure<F>, Error = UnsupportedProgramError>,
+    ) -> Result<(CircuitStructure<F>, IVCProgram<F>), Error> {
+        check_acvm_field::<F, AF>()?;
+        let mut structure = noir_circuit.try_into()?;
+        let program = structure.compile()?;
+        Ok((structure, program))
+    }
+
+    /// Same as `compile`, lowering opcodes unknown to this crate with `lowerings`
+    #[allow(clippy::type_complexity)]
+    pub fn compile_with_lowerings<F: PrimeField, AF: ArkPrimeField>(
+        noir_circuit: Program<GenericFieldElement<AF>>,
+        lowerings: LoweringRegistry<F>,
+    ) -> Result<(CircuitStructure<F>, IVCProgram<F>), Error> {
+        check_acvm_field::<F, AF>()?;
+        let mut structure = CircuitStructure::from_program(noir_circuit, lowerings)?;
+        let program = structure.compile()?;
+        Ok((structure, program))
+    }
+
+    /// Same as `compile`, failing early if the circuit exceeds `limits`
+    #[allow(clippy::type_complexity)]
+    pub fn compile_with_limits<F: PrimeField, AF: ArkPrimeField>(
+        noir_circuit: Program<GenericFieldElement<AF>>,
+        limits: CompileLimits,
     ) -> Result<(CircuitStructure<F>, IVCProgram<F>), Error> {
-        let structure: CircuitStructure<F> = noir_circuit.into();
+        check_acvm_field::<F, AF>()?;
+        let mut structure =
+            CircuitStructure::from_program(noir_circuit, Default::default())?.with_limits(limits);
         let program = structure.compile()?;
         Ok((structure, program))
     }
 
     pub fn execute_steps<F: PrimeField, AF: ArkPrimeField>(
-        circuit: CircuitStructure<F>,
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
         first_public_input: Witness<F>,
         start_step_num: u64,
         private_inputs: impl Iterator<Item = Witness<F>>,
-    ) -> impl Iterator<Item = Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>> {
-        let mut circuit = UnexecutedCircuit::new(start_step_num, first_public_input, circuit);
+    ) -> impl Iterator<Item = Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        execute_steps_with::<F, AF, _, _>(
+            circuit,
+            first_public_input,
+            start_step_num,
+            private_inputs,
+            NoOracle,
+            Bn254BlackBoxSolver,
+        )
+    }
+
+    /// Execute the single step `step_num` of `circuit`
+    pub fn execute_step<F: PrimeField, AF: ArkPrimeField>(
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
+        public_input: Witness<F>,
+        private_input: Witness<F>,
+        step_num: u64,
+    ) -> Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let (exe_res, witness, next) = UnexecutedCircuit::new(step_num, public_input, circuit)
+            .execute::<AF, _, _>(private_input, &mut NoOracle, &Bn254BlackBoxSolver)?;
+
+        Ok((exe_res, witness, next.public_input.into()))
+    }
+
+    /// Execute the step `step_num` of `circuit` from the state `state`, with
+    /// the inputs in witness order
+    pub(crate) fn execute_io_step<F: PrimeField, AF: ArkPrimeField>(
+        circuit: &Arc<CircuitStructure<F>>,
+        state: &IO<F>,
+        private_input: &IO<F>,
+        step_num: u64,
+    ) -> Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let ids = |ids: &BTreeSet<WitnessID>| -> Vec<WitnessID> { ids.iter().cloned().collect() };
+        let public_input = IOLayout::make_witness(&ids(&circuit.program.public_inputs), state)?;
+        let private_input =
+            IOLayout::make_witness(&ids(&circuit.program.private_inputs), private_input)?;
+
+        execute_step::<F, AF>(circuit.clone(), public_input, private_input, step_num)
+    }
+
+    /// Run `step` on every input from `start_state`, numbering the steps from
+    /// `start_step_num`, the state of a step being the public output of the
+    /// previous one. Stops after the first failed step
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn chain_io_steps<'a, F: Clone + 'a, I: 'a, T: 'a>(
+        start_state: IO<F>,
+        start_step_num: u64,
+        mut inputs: impl Iterator<Item = I> + 'a,
+        mut step: impl FnMut(&IO<F>, I, u64) -> Result<(T, ExecutionResult<F>, Witness<F>, IO<F>), Error>
+            + 'a,
+    ) -> impl Iterator<Item = Result<(T, ExecutionResult<F>, Witness<F>, IO<F>), Error>> + 'a {
+        let mut state = Some(start_state);
+        let mut step_num = start_step_num;
+
+        std::iter::from_fn(move || {
+            let input = inputs.next()?;
+            let result = step(&state.take()?, input, step_num);
+
+            if let Ok((_, _, _, next)) = &result {
+                state = Some(IO(next.0.clone()));
+            }
+            step_num += 1;
+
+            Some(result)
+        })
+    }
+
+    /// Execute the first `n` steps, computing the private input of every step
+    /// from its number and the result of the previous step, if any. Stops
+    /// after the first failed step
+    pub fn execute_n_steps<F: PrimeField, AF: ArkPrimeField>(
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
+        first_public_input: Witness<F>,
+        n: u64,
+        mut private_input: impl FnMut(u64, Option<&ExecutionResult<F>>) -> Witness<F>,
+    ) -> impl Iterator<Item = Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let mut circuit = UnexecutedCircuit::new(0, first_public_input, circuit);
+        let mut previous: Option<ExecutionResult<F>> = None;
+        let mut failed = false;
+
+        std::iter::from_fn(move || {
+            if failed || circuit.iteration_number >= n {
+                return None;
+            }
+
+            let input = private_input(circuit.iteration_number, previous.as_ref());
+            let step = circuit
+                .execute_ref::<AF, _, _>(input, &mut NoOracle, &Bn254BlackBoxSolver)
+                .map(|(exe_res, witness)| {
+                    previous = Some(exe_res.clone());
+                    (exe_res, witness, circuit.public_input.clone().into())
+                });
+
+            failed = step.is_err();
+            Some(step)
+        })
+    }
+
+    /// Check `results` form a chain before proving it: iteration numbers are
+    /// consecutive and every public input is the public output of the
+    /// previous step, passed on as by `execute`
+    pub fn verify_chain<F: PrimeField>(
+        results: &[ExecutionResult<F>],
+        io: &IOProfile,
+    ) -> Result<(), Error> {
+        for pair in results.windows(2) {
+            let (previous, next) = (&pair[0], &pair[1]);
+            let broken = |reason| Error::BrokenChain {
+                iteration_number: next.iteration_number,
+                reason,
+            };
+
+            if next.iteration_number != previous.iteration_number + 1 {
+                return Err(broken("iteration numbers are not consecutive"));
+            }
+            if previous.public_output.make_next_input_witness(io).0 != next.public_input.0 {
+                return Err(broken("public input is not the previous public output"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-run a step from the inputs and the oracle calls recorded in
+    /// `result`, checking it produces the same outputs, e.g. to audit stored
+    /// results without their witness files. Returns the R1CS witness
+    pub fn replay<F: PrimeField, AF: ArkPrimeField>(
+        structure: impl Into<Arc<CircuitStructure<F>>>,
+        result: &ExecutionResult<F>,
+    ) -> Result<Witness<F>, Error>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let structure = structure.into();
+        if let Some(expected) = &result.program_digest {
+            let found = structure.digest()?;
+            if *expected != found {
+                return Err(Error::DigestMismatch {
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+        }
+
+        let mut oracle = RecordedOracle::new(result.oracle_calls.clone());
+        let (replayed, witness, _) = UnexecutedCircuit::new(
+            result.iteration_number,
+            result.public_input.clone(),
+            structure,
+        )
+        .execute::<AF, _, _>(
+            result.private_input.clone(),
+            &mut oracle,
+            &Bn254BlackBoxSolver,
+        )?;
+
+        if replayed.public_output.0 != result.public_output.0 {
+            return Err(Error::ReplayMismatch("public output"));
+        }
+        if replayed.private_output.0 != result.private_output.0 {
+            return Err(Error::ReplayMismatch("private output"));
+        }
+        if !oracle.is_exhausted() {
+            return Err(Error::ReplayMismatch("oracle calls"));
+        }
+
+        Ok(witness)
+    }
+
+    /// Execute a step per private input, keeping only the public input of
+    /// the step after the last one and the last result. The R1CS witness of
+    /// a step is only built if `verify` is set, to check it satisfies the
+    /// constraints, and dropped right after
+    pub fn run_to_end<F: PrimeField, AF: ArkPrimeField>(
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
+        first_public_input: Witness<F>,
+        private_inputs: impl IntoIterator<Item = Witness<F>>,
+        verify: bool,
+    ) -> Result<(IO<F>, Option<ExecutionResult<F>>), Error>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let mut circuit = UnexecutedCircuit::new(0, first_public_input, circuit);
+        circuit.check_constraints = verify;
+        let structure = circuit.structure.clone();
+        let mut last = None;
+
+        for private_input in private_inputs {
+            let solved =
+                circuit.solve::<AF, _, _>(private_input, &mut NoOracle, &Bn254BlackBoxSolver)?;
+
+            last = Some(if verify {
+                solved.finish(&structure)?.0
+            } else {
+                solved.result
+            });
+        }
+
+        Ok((circuit.public_input.into(), last))
+    }
+
+    /// Same as `execute_steps`, writing the witness of every step as JSON
+    /// to the writer `make_writer` returns for its step number, instead of
+    /// returning it. Stops after the first failed step
+    pub fn execute_steps_to<F, AF, W>(
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
+        first_public_input: Witness<F>,
+        start_step_num: u64,
+        private_inputs: impl Iterator<Item = Witness<F>>,
+        mut make_writer: impl FnMut(u64) -> Result<W, Error>,
+    ) -> impl Iterator<Item = Result<(ExecutionResult<F>, IO<F>), Error>>
+    where
+        F: PrimeField + Serialize,
+        AF: ArkPrimeField,
+        W: Write,
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let mut steps =
+            execute_steps::<F, AF>(circuit, first_public_input, start_step_num, private_inputs);
+        let mut failed = false;
+
+        std::iter::from_fn(move || {
+            if failed {
+                return None;
+            }
+
+            // Note: the witness is dropped once written, so at most the
+            // pipelined steps are held in memory
+            let step = steps.next()?.and_then(|(exe_res, witness, next)| {
+                let mut writer = BufWriter::new(make_writer(exe_res.iteration_number)?);
+                serde_json::to_writer(&mut writer, &witness).map_err(std::io::Error::from)?;
+                writer.flush()?;
+                Ok((exe_res, next))
+            });
+
+            failed = step.is_err();
+            Some(step)
+        })
+    }
+
+    /// Same as `execute_steps_to`, writing the witness of step `i` to
+    /// `dir/witness_{i}.json`
+    pub fn execute_steps_to_dir<F, AF, P>(
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
+        first_public_input: Witness<F>,
+        start_step_num: u64,
+        private_inputs: impl Iterator<Item = Witness<F>>,
+        dir: P,
+    ) -> Result<impl Iterator<Item = Result<(ExecutionResult<F>, IO<F>), Error>>, Error>
+    where
+        F: PrimeField + Serialize,
+        AF: ArkPrimeField,
+        P: AsRef<Path>,
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(execute_steps_to::<F, AF, _>(
+            circuit,
+            first_public_input,
+            start_step_num,
+            private_inputs,
+            move |i| Ok(File::create(dir.join(format!("witness_{}.json", i)))?),
+        ))
+    }
+
+    /// Same as `execute_steps`, resolving foreign calls with `oracle`
+    pub fn execute_steps_with_oracle<F: PrimeField, AF: ArkPrimeField, O: OracleResolver<F>>(
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
+        first_public_input: Witness<F>,
+        start_step_num: u64,
+        private_inputs: impl Iterator<Item = Witness<F>>,
+        oracle: O,
+    ) -> impl Iterator<Item = Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        execute_steps_with::<F, AF, _, _>(
+            circuit,
+            first_public_input,
+            start_step_num,
+            private_inputs,
+            oracle,
+            Bn254BlackBoxSolver,
+        )
+    }
+
+    /// Same as `execute_steps`, with a custom oracle and blackbox solver
+    pub fn execute_steps_with<F, AF, O, B>(
+        circuit: impl Into<Arc<CircuitStructure<F>>>,
+        first_public_input: Witness<F>,
+        start_step_num: u64,
+        private_inputs: impl Iterator<Item = Witness<F>>,
+        oracle: O,
+        blackbox_solver: B,
+    ) -> impl Iterator<Item = Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>>
+    where
+        F: PrimeField,
+        AF: ArkPrimeField,
+        O: OracleResolver<F>,
+        B: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        execute_steps_from::<F, AF, _, _>(
+            UnexecutedCircuit::new(start_step_num, first_public_input, circuit),
+            private_inputs,
+            oracle,
+            blackbox_solver,
+        )
+    }
+
+    /// Same as `execute_steps_with`, from a circuit at any step, e.g. with a
+    /// `next_input` hook.
+    /// Steps are solved one ahead of the returned ones, while a worker
+    /// thread builds the R1CS witness of the previous step. The iterator is
+    /// `Send` if the private inputs, `oracle` and `blackbox_solver` are, so it
+    /// can be moved to another thread or task
+    pub fn execute_steps_from<F, AF, O, B>(
+        mut circuit: UnexecutedCircuit<F>,
+        mut private_inputs: impl Iterator<Item = Witness<F>>,
+        mut oracle: O,
+        blackbox_solver: B,
+    ) -> impl Iterator<Item = Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>>
+    where
+        F: PrimeField,
+        AF: ArkPrimeField,
+        O: OracleResolver<F>,
+        B: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let (solved_sender, solved_receiver) = mpsc::channel::<SolvedStep<F>>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        // Note: stops once the iterator, holding the sender, is dropped
+        let structure = circuit.structure.clone();
+        std::thread::spawn(move || {
+            for solved in solved_receiver {
+                let next_input = solved.next_input.clone().into();
+                let result = solved
+                    .finish(&structure)
+                    .map(|(exe_res, witness)| (exe_res, witness, next_input));
+                if result_sender.send(result).is_err() {
+                    break;
+                }
+            }
+        });
 
-        private_inputs.map(move |private_input| {
-            let (exe_res, witness, next) = circuit.clone().execute::<AF>(private_input)?;
+        // steps in order, `None` for one whose witness is being built
+        let mut pending: VecDeque<Option<Error>> = VecDeque::new();
+        let mut cancelled = false;
 
-            let next_input = next.public_input.clone().into();
+        std::iter::from_fn(move || {
+            while pending.len() < 2 && !cancelled {
+                let Some(private_input) = private_inputs.next() else {
+                    break;
+                };
 
-            circuit = next;
+                // a failed step does not advance the circuit
+                match circuit.solve::<AF, _, _>(private_input, &mut oracle, &blackbox_solver) {
+                    Ok(solved) => {
+                        solved_sender.send(solved).expect("witness worker stopped");
+                        pending.push_back(None);
+                    }
+                    Err(e) => {
+                        // the steps after a cancelled one are not run
+                        cancelled = matches!(e, Error::Cancelled);
+                        pending.push_back(Some(e));
+                    }
+                }
+            }
 
-            Ok((exe_res, witness, next_input))
+            match pending.pop_front()? {
+                None => Some(result_receiver.recv().expect("witness worker stopped")),
+                Some(e) => Some(Err(e)),
+            }
         })
     }
 }
+pub use acvm::blackbox_solver::{BlackBoxFunctionSolver, StubbedBlackBoxSolver};
+#[cfg(feature = "ark")]
+pub use ark::ArkCircuit;
+pub use batch::BatchExecutor;
+pub use blinding::{Blinding, BlindingNonce};
+pub use bn254_blackbox_solver::Bn254BlackBoxSolver;
+pub use curve::Curve;
+pub use debug::SourceLocation;
+pub use dense::DenseWitness;
+pub use execute::{ExecutionCheckpoint, NextInput, OnStep, StepProgress, UnexecutedCircuit};
+pub use field::{ConversionDirection, ConversionSite, FieldConversionError};
 pub use functions::*;
-pub use program::CircuitStructure;
+pub use gate::AcirArithGate;
+#[cfg(feature = "halo2")]
+pub use halo2::{NoirHalo2Circuit, PlonkConfig};
+pub use layout::IOLayout;
+pub use load::{ProgramMetadata, R1CSSize};
+pub use lowering::{GateLowering, LoweredOpcode, LoweringRegistry};
+pub use merkle::{MerkleHasher, MerkleProof, MerkleTree, Poseidon2};
+#[cfg(feature = "nova")]
+pub use nova::{NoirStepCircuit, StateDigest};
+pub use oracle::{NoOracle, OracleCall, OracleResolver, OracleValue, RecordedOracle};
+pub use partition::CircuitPart;
+pub use plan::{ExecutionPlan, Phase};
+pub use plonk::{PlonkLayout, PlonkRow, Wire};
+pub use program::{program_digest, CircuitStructure, CompileLimits, InputMismatch};
+pub use program_set::ProgramSet;
+pub use r1cs::{Product, R1CSEncoding, R1CSTemplate};
+pub use renumber::Renumbering;
+pub use session::ExecutionSession;
+#[cfg(feature = "sonobe")]
+pub use sonobe::{fold_steps, fold_with, FoldingBackend, IsHyperNova, IsNova, PerBackend};
+#[cfg(feature = "async")]
+pub use stream::execute_steps_async;
+#[cfg(feature = "supernova")]
+pub use supernova::{
+    create_set_public_params, prove_set_steps, verify_set_steps, NonUniformProgramSet,
+    SetSecondaryCircuit, SetStepCircuit,
+};
+pub use transcript::{Transcript, TranscriptEntry};
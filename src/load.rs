@@ -1,19 +1,35 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
 use acvm::acir::{
     acir_field::GenericFieldElement,
     circuit::{Opcode, Program},
 };
 use ark_ff::PrimeField as ArkPrimeField;
+use ff::PrimeField;
+use ivc_program::program::{IOProfile, WitnessID};
+use serde::Serialize;
 
-use crate::program::extract_io;
+use crate::{functions::compile, memory::check_memory_op, program::extract_io};
 
 #[derive(Debug, thiserror::Error)]
 pub enum UnsupportedProgramError {
+    #[error("Program has no functions")]
+    NoFunctions,
     #[error("Program has more than one function ({0})")]
     MultipleFunctions(usize),
-    #[error("Program has unconstrained functions ({0})")]
-    UnconstrainedFunctions(usize),
-    #[error("Program has an opcode that is not an AssertZero ({0:?})")]
-    NonAssertZeroOpcode(String),
+    #[error("Program has an opcode that is not an AssertZero at index {0} ({1:?})")]
+    NonAssertZeroOpcode(usize, String),
+    #[error("Program has an unsupported memory operation ({0})")]
+    UnsupportedMemoryOp(String),
+    #[error("Program uses memory block {0} across the lowered opcode at index {1}")]
+    MemoryAcrossLowering(u32, usize),
+    #[error("Program has an opcode at index {0} that cannot be converted ({1})")]
+    UnconvertibleOpcode(usize, Box<crate::Error>),
+    #[error("Program cannot be serialized ({0})")]
+    Unserializable(String),
     #[error("Malformed program: {0}")]
     MalformedProgram(#[from] ivc_program::program::MalformedProgramError),
 }
@@ -71,33 +87,191 @@ pub fn print_metadata<F: ArkPrimeField>(program: &Program<GenericFieldElement<F>
     }
 }
 
+/// Size of the compiled R1CS
+#[derive(Clone, Debug, Serialize)]
+pub struct R1CSSize {
+    pub constraints: usize,
+    pub witnesses: u32,
+}
+
+/// Description of a program, supported or not
+#[derive(Clone, Serialize)]
+pub struct ProgramMetadata {
+    pub noir_version: Option<String>,
+    pub constrained_functions: usize,
+    pub unconstrained_functions: usize,
+    /// Number of opcodes of every kind, in the first function
+    pub opcodes: BTreeMap<String, usize>,
+    /// In the ACIR numbering
+    pub io: Option<IOProfile>,
+    /// Parameter names with their witnesses in the R1CS numbering, if the ABI
+    /// is known and the program compiles
+    pub parameters: Vec<(String, Vec<WitnessID>)>,
+    /// Set if the program compiles
+    pub r1cs: Option<R1CSSize>,
+    /// Why the program is not supported
+    pub unsupported: Option<String>,
+    /// Why the ABI does not match the program
+    pub abi_mismatch: Option<String>,
+}
+
+fn opcode_kind<F>(opcode: &Opcode<F>) -> String {
+    match opcode {
+        Opcode::AssertZero(_) => "AssertZero".to_string(),
+        Opcode::BlackBoxFuncCall(call) => format!("BlackBoxFuncCall({})", call.name()),
+        Opcode::MemoryInit { .. } => "MemoryInit".to_string(),
+        Opcode::MemoryOp { .. } => "MemoryOp".to_string(),
+        Opcode::BrilligCall { .. } => "BrilligCall".to_string(),
+        _ => "Other".to_string(),
+    }
+}
+
+impl ProgramMetadata {
+    /// Describe `program`, compiling it over `F` for the size of its R1CS
+    pub fn new<F: PrimeField, AF: ArkPrimeField>(
+        program: &Program<GenericFieldElement<AF>>,
+        abi: Option<&serde_json::Value>,
+    ) -> Result<Self, crate::Error> {
+        let mut opcodes = BTreeMap::new();
+        let circuit = program.functions.first();
+        for opcode in circuit.iter().flat_map(|circuit| &circuit.opcodes) {
+            *opcodes.entry(opcode_kind(opcode)).or_insert(0) += 1;
+        }
+
+        let mut metadata = Self {
+            noir_version: None,
+            constrained_functions: program.functions.len(),
+            unconstrained_functions: program.unconstrained_functions.len(),
+            opcodes,
+            io: circuit.map(|circuit| extract_io(circuit, &Default::default())),
+            parameters: vec![],
+            r1cs: None,
+            unsupported: None,
+            abi_mismatch: None,
+        };
+
+        if let Err(e) = check_supported(program) {
+            metadata.unsupported = Some(e.to_string());
+            return Ok(metadata);
+        }
+
+        match compile::<F, AF>(program.clone()) {
+            Ok((mut structure, ivc_program)) => {
+                metadata.r1cs = Some(R1CSSize {
+                    constraints: ivc_program.r1cs_constraints.len(),
+                    witnesses: ivc_program.num_witness,
+                });
+                if let Some(abi) = abi {
+                    match structure.set_abi(abi) {
+                        Ok(()) => {
+                            let layout = structure.layout.take().ok_or_else(|| {
+                                crate::Error::AbiMismatch("no layout was set".to_string())
+                            })?;
+                            metadata.parameters = layout.parameters;
+                        }
+                        Err(e) => metadata.abi_mismatch = Some(e.to_string()),
+                    }
+                }
+            }
+            Err(e) => metadata.unsupported = Some(e.to_string()),
+        }
+
+        Ok(metadata)
+    }
+}
+
+impl fmt::Display for ProgramMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = |ids: &BTreeSet<WitnessID>| {
+            ids.iter()
+                .map(|id| id.0.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        if let Some(version) = &self.noir_version {
+            writeln!(f, "Noir version: {}", version)?;
+        }
+        writeln!(
+            f,
+            "Functions: {} constrained, {} unconstrained",
+            self.constrained_functions, self.unconstrained_functions
+        )?;
+
+        writeln!(f, "Opcodes:")?;
+        for (kind, count) in &self.opcodes {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+
+        if let Some(io) = &self.io {
+            writeln!(f, "Public inputs: [{}]", ids(&io.public_inputs))?;
+            writeln!(f, "Private inputs: [{}]", ids(&io.private_inputs))?;
+            writeln!(f, "Public outputs: [{}]", ids(&io.public_outputs))?;
+        }
+        if !self.parameters.is_empty() {
+            writeln!(f, "Parameters:")?;
+            for (name, witnesses) in &self.parameters {
+                let witnesses: BTreeSet<WitnessID> = witnesses.iter().cloned().collect();
+                writeln!(f, "  {}: [{}]", name, ids(&witnesses))?;
+            }
+        }
+        if let Some(r1cs) = &self.r1cs {
+            writeln!(
+                f,
+                "R1CS: {} constraints, {} witnesses",
+                r1cs.constraints, r1cs.witnesses
+            )?;
+        }
+        if let Some(mismatch) = &self.abi_mismatch {
+            writeln!(f, "ABI mismatch: {}", mismatch)?;
+        }
+
+        match &self.unsupported {
+            Some(reason) => write!(f, "Supported: no, {}", reason),
+            None => write!(f, "Supported: yes"),
+        }
+    }
+}
+
 pub fn check_supported<F: ArkPrimeField>(
     program: &Program<GenericFieldElement<F>>,
 ) -> Result<(), UnsupportedProgramError> {
     {
         let num_functions = program.functions.len();
+        if num_functions == 0 {
+            return Err(UnsupportedProgramError::NoFunctions);
+        }
         if num_functions != 1 {
             return Err(UnsupportedProgramError::MultipleFunctions(num_functions));
         }
     }
 
-    {
-        let num_unconstrained_functions = program.unconstrained_functions.len();
-        if num_unconstrained_functions != 0 {
-            return Err(UnsupportedProgramError::UnconstrainedFunctions(
-                num_unconstrained_functions,
-            ));
-        }
-    }
-
     let circuit = &program.functions[0];
 
+    let mut memory_blocks = BTreeSet::new();
+
     for op in &circuit.opcodes {
-        if !matches!(op, Opcode::AssertZero(_)) {
-            return Err(UnsupportedProgramError::NonAssertZeroOpcode(format!(
-                "{:?}",
-                op
-            )));
+        match op {
+            Opcode::AssertZero(_) | Opcode::BrilligCall { .. } => {}
+            Opcode::MemoryInit { block_id, .. } => {
+                memory_blocks.insert(block_id.0);
+            }
+            Opcode::MemoryOp {
+                block_id,
+                op: mem_op,
+                predicate,
+            } => {
+                if !memory_blocks.contains(&block_id.0) {
+                    return Err(UnsupportedProgramError::UnsupportedMemoryOp(format!(
+                        "block {} is not initialized",
+                        block_id.0
+                    )));
+                }
+                check_memory_op(mem_op, predicate)?;
+            }
+            // other opcodes are rejected on conversion unless a registered
+            // lowering handles them, see `CircuitStructure::from_circuit`
+            _ => {}
         }
     }
 
@@ -0,0 +1,127 @@
+//! Extension point for opcodes the core crate does not lower itself.
+//!
+//! A `GateLowering` turns an opcode into additional `AcirArithGate`s (which
+//! may use fresh auxiliary witnesses) and later fills in the witnesses the
+//! opcode defines, since ACVM cannot solve an opcode it is not given.
+//!
+//! Note: solving restarts ACVM after every lowered opcode, which loses the
+//! state of memory blocks, so a circuit using a memory block across a
+//! lowered opcode is rejected (see `check_memory_segments`).
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use ivc_program::program::WitnessID;
+use serde::{Deserialize, Serialize};
+
+use crate::{gate::AcirArithGate, load::UnsupportedProgramError, memory::MemoryBlock, Error};
+
+pub trait GateLowering<F>: Send + Sync {
+    /// Unique name, stored with every lowered opcode to find its solver again
+    fn name(&self) -> String;
+
+    /// Lower the opcode (in its serialized ACIR form) into gates plus any
+    /// lowering-specific data, or `None` if it is not handled here.
+    /// Auxiliary witnesses are allocated by incrementing `next_witness`.
+    fn lower(
+        &self,
+        opcode: &serde_json::Value,
+        next_witness: &mut u32,
+    ) -> Option<(Vec<AcirArithGate<F>>, serde_json::Value)>;
+
+    /// Assign every witness defined by a lowered opcode
+    fn solve(
+        &self,
+        lowered: &LoweredOpcode<F>,
+        witness: &mut BTreeMap<WitnessID, F>,
+    ) -> Result<(), Error>;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LoweredOpcode<F> {
+    /// Index of the opcode in the original opcode list
+    pub position: usize,
+    pub lowering: String,
+    pub opcode: serde_json::Value,
+    pub gates: Vec<AcirArithGate<F>>,
+    pub data: serde_json::Value,
+}
+
+pub struct LoweringRegistry<F>(Vec<Arc<dyn GateLowering<F>>>);
+
+impl<F> Default for LoweringRegistry<F> {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl<F> Clone for LoweringRegistry<F> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<F> LoweringRegistry<F> {
+    pub fn register(&mut self, lowering: impl GateLowering<F> + 'static) {
+        self.0.push(Arc::new(lowering));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Lower with the first registered lowering that handles the opcode
+    pub(crate) fn lower(
+        &self,
+        position: usize,
+        opcode: serde_json::Value,
+        next_witness: &mut u32,
+    ) -> Option<LoweredOpcode<F>> {
+        self.0.iter().find_map(|lowering| {
+            let (gates, data) = lowering.lower(&opcode, next_witness)?;
+            Some(LoweredOpcode {
+                position,
+                lowering: lowering.name(),
+                opcode: opcode.clone(),
+                gates,
+                data,
+            })
+        })
+    }
+
+    pub(crate) fn solve(
+        &self,
+        lowered: &LoweredOpcode<F>,
+        witness: &mut BTreeMap<WitnessID, F>,
+    ) -> Result<(), Error> {
+        self.0
+            .iter()
+            .find(|lowering| lowering.name() == lowered.lowering)
+            .ok_or_else(|| Error::MissingLowering(lowered.lowering.clone()))?
+            .solve(lowered, witness)
+    }
+}
+
+/// Fails if a lowered opcode lies between the initialization of a memory
+/// block and its last access
+pub(crate) fn check_memory_segments<F>(
+    memory: &[MemoryBlock<F>],
+    lowered: &[LoweredOpcode<F>],
+) -> Result<(), UnsupportedProgramError> {
+    for block in memory {
+        let Some(last_access) = block.accesses.iter().map(|access| access.position).max() else {
+            continue;
+        };
+
+        if let Some(opcode) = lowered
+            .iter()
+            .find(|opcode| (block.position..last_access).contains(&opcode.position))
+        {
+            return Err(UnsupportedProgramError::MemoryAcrossLowering(
+                block.block_id,
+                opcode.position,
+            ));
+        }
+    }
+
+    Ok(())
+}
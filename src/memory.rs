@@ -0,0 +1,1004 @@
+//! Offline memory checking for ACIR memory blocks.
+//!
+//! Every block contributes a trace of entries `(addr, time, value, is_write)`:
+//! the initial values are writes at time 0 and the k-th `MemoryOp` of the
+//! block happens at time k + 1. The executor additionally supplies the same
+//! entries sorted by `(addr, time)`, and we constrain
+//! 1. the sorted trace to be a permutation of the program-order trace, via a
+//!    grand product over `gamma - fingerprint(entry)`;
+//! 2. addresses in the sorted trace to start at 0 and grow by 0 or 1, with
+//!    each address starting at its initial write (time 0);
+//! 3. times of the same address to strictly increase (range-checked gaps);
+//! 4. every read to return the value of the previous entry of its address.
+//!
+//! The permutation challenges are a hash of both traces, computed in the
+//! circuit (Fiat-Shamir), so they are fixed once the traces are: every value
+//! is hashed with the running state by the Poseidon of neptune, as in
+//! `StateDigest`. The gates are those of the neptune gadget: a permutation
+//! of width 3 has 81 S-boxes of three multiplications, so every absorbed
+//! value costs 243 multiplication gates, plus the linear gates of the round
+//! constants and MDS layers. A block absorbs two values per access and four
+//! per sorted entry.
+
+use std::collections::BTreeMap;
+
+use acvm::{
+    acir::{
+        acir_field::GenericFieldElement,
+        circuit::{
+            opcodes::{BlockId, BlockType, MemOp},
+            Opcode,
+        },
+        native_types::{Expression, Witness as ACVMWitness},
+    },
+    AcirField,
+};
+use ark_ff::PrimeField as ArkPrimeField;
+use bellpepper_core::{
+    num::AllocatedNum, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable,
+};
+use ff::PrimeField;
+use ivc_program::program::WitnessID;
+use neptune::poseidon::PoseidonConstants;
+use serde::{Deserialize, Serialize};
+use typenum::U2;
+
+use crate::{gate::AcirArithGate, load::UnsupportedProgramError, Error};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryAccess<F> {
+    /// Index of the `MemoryOp` in the original opcode list
+    pub position: usize,
+    pub is_write: bool,
+    pub index: AcirArithGate<F>,
+    pub value: AcirArithGate<F>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SortedEntry {
+    pub addr: WitnessID,
+    pub time: WitnessID,
+    pub value: WitnessID,
+    pub is_write: WitnessID,
+}
+
+/// Auxiliary witnesses linking two consecutive entries of the sorted trace
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Transition {
+    /// `addr[i] - addr[i - 1]`, either 0 or 1
+    pub addr_step: WitnessID,
+    /// 1 iff entry i is a read of the same address as entry i - 1
+    pub read_selector: WitnessID,
+    /// `time[i] - time[i - 1] - 1` for the same address, 0 otherwise
+    pub time_gap: WitnessID,
+    pub time_gap_bits: Vec<WitnessID>,
+}
+
+/// Witnesses absorbing a value into the challenge hash
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Absorb {
+    /// Variables of the Poseidon gadget in allocation order, starting with
+    /// the state and the value
+    pub witnesses: Vec<WitnessID>,
+    /// State after the value, the Poseidon hash of both
+    pub state: WitnessID,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryLayout {
+    /// Both traces absorbed in order, then the squeezes of `alpha` and
+    /// `gamma`, the states of the last two absorbs
+    pub challenge_hash: Vec<Absorb>,
+    pub alpha: WitnessID,
+    pub alpha_sq: WitnessID,
+    pub alpha_cube: WitnessID,
+    pub gamma: WitnessID,
+    pub sorted: Vec<SortedEntry>,
+    pub transitions: Vec<Transition>,
+    pub trace_fingerprints: Vec<WitnessID>,
+    pub trace_products: Vec<WitnessID>,
+    pub sorted_fingerprints: Vec<WitnessID>,
+    pub sorted_products: Vec<WitnessID>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryBlock<F> {
+    pub block_id: u32,
+    pub block_type: BlockType,
+    /// Index of the `MemoryInit` in the original opcode list
+    pub position: usize,
+    pub init: Vec<WitnessID>,
+    pub accesses: Vec<MemoryAccess<F>>,
+
+    // Note: filled by `allocate` once all accesses are known
+    pub layout: Option<MemoryLayout>,
+    pub constraints: Vec<AcirArithGate<F>>,
+}
+
+/// A program-order trace entry, `addr` and `value` are linear in the witness
+struct TraceEntry<F> {
+    addr: AcirArithGate<F>,
+    time: u64,
+    value: AcirArithGate<F>,
+    is_write: bool,
+}
+
+pub(crate) fn check_memory_op<AF: ArkPrimeField>(
+    op: &MemOp<GenericFieldElement<AF>>,
+    predicate: &Option<Expression<GenericFieldElement<AF>>>,
+) -> Result<(), UnsupportedProgramError> {
+    let is_const = |expr: &Expression<GenericFieldElement<AF>>| {
+        expr.mul_terms.is_empty() && expr.linear_combinations.is_empty()
+    };
+
+    if !is_const(&op.operation) || !(op.operation.q_c.is_zero() || op.operation.q_c.is_one()) {
+        return Err(UnsupportedProgramError::UnsupportedMemoryOp(format!(
+            "non-constant operation {:?}",
+            op.operation
+        )));
+    }
+
+    if !op.index.mul_terms.is_empty() || !op.value.mul_terms.is_empty() {
+        return Err(UnsupportedProgramError::UnsupportedMemoryOp(format!(
+            "non-linear operand {:?}",
+            op
+        )));
+    }
+
+    if let Some(predicate) = predicate {
+        if !is_const(predicate) || !predicate.q_c.is_one() {
+            return Err(UnsupportedProgramError::UnsupportedMemoryOp(format!(
+                "predicate {:?}",
+                predicate
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn gate<F>(
+    mul_terms: Vec<(F, WitnessID, WitnessID)>,
+    add_terms: Vec<(F, WitnessID)>,
+    constant_term: F,
+) -> AcirArithGate<F> {
+    AcirArithGate {
+        mul_terms,
+        add_terms,
+        constant_term,
+    }
+}
+
+fn constant<F: PrimeField>(value: F) -> AcirArithGate<F> {
+    gate(vec![], vec![], value)
+}
+
+fn evaluate_linear<F: PrimeField>(
+    expr: &AcirArithGate<F>,
+    witness: &BTreeMap<WitnessID, F>,
+) -> Result<F, Error> {
+    expr.add_terms
+        .iter()
+        .try_fold(expr.constant_term, |acc, (coeff, id)| {
+            let value = witness.get(id).ok_or_else(|| {
+                Error::MemoryCheckingError(format!("witness {} is not solved", id.0))
+            })?;
+            Ok(acc + *coeff * value)
+        })
+}
+
+fn variable<F: PrimeField>(id: WitnessID) -> AcirArithGate<F> {
+    gate(vec![], vec![(F::ONE, id)], F::ZERO)
+}
+
+/// A factor of a product in `PoseidonSystem`
+enum Factor<F> {
+    Constant(F),
+    /// A coefficient and the index of a variable
+    Scaled(F, usize),
+}
+
+/// Constraint system running the Poseidon gadget of neptune, either to
+/// record its constraints as gates or to compute the values of its variables
+struct PoseidonSystem<'a, F: PrimeField> {
+    /// Set when recording the gates, the witness of every new variable
+    alloc: Option<&'a mut (dyn FnMut() -> WitnessID + Send)>,
+    ids: Vec<WitnessID>,
+    gates: Vec<AcirArithGate<F>>,
+    values: Vec<F>,
+}
+
+impl<'a, F: PrimeField> PoseidonSystem<'a, F> {
+    fn new(alloc: Option<&'a mut (dyn FnMut() -> WitnessID + Send)>) -> Self {
+        Self {
+            alloc,
+            ids: vec![],
+            gates: vec![],
+            values: vec![],
+        }
+    }
+
+    /// The gate of `lc`, over the witnesses of its variables
+    fn linear_gate(&self, lc: &LinearCombination<F>) -> AcirArithGate<F> {
+        let mut add_terms = vec![];
+        let mut constant_term = F::ZERO;
+        for (var, coeff) in lc.iter() {
+            match var.get_unchecked() {
+                Index::Input(_) => constant_term += coeff,
+                Index::Aux(i) => add_terms.push((*coeff, self.ids[i])),
+            }
+        }
+        gate(vec![], add_terms, constant_term)
+    }
+
+    /// `lc` as a constant or a scaled variable, allocating a variable equal to
+    /// it if it is neither
+    fn factor(&mut self, lc: &LinearCombination<F>) -> Factor<F> {
+        let terms: Vec<(Variable, F)> = lc.iter().map(|(var, coeff)| (var, *coeff)).collect();
+        match terms.as_slice() {
+            [] => return Factor::Constant(F::ZERO),
+            [(var, coeff)] => {
+                return match var.get_unchecked() {
+                    Index::Input(_) => Factor::Constant(*coeff),
+                    Index::Aux(i) => Factor::Scaled(*coeff, i),
+                }
+            }
+            _ => {}
+        }
+
+        let index = match &mut self.alloc {
+            Some(alloc) => {
+                // id = lc
+                let id = alloc();
+                let sum = self.linear_gate(lc);
+                let mut add_terms = vec![(F::ONE, id)];
+                add_terms.extend(sum.add_terms.iter().map(|&(coeff, id)| (-coeff, id)));
+                self.gates.push(gate(vec![], add_terms, -sum.constant_term));
+                self.ids.push(id);
+                self.ids.len() - 1
+            }
+            None => {
+                let value = terms.iter().fold(F::ZERO, |acc, (var, coeff)| {
+                    acc + match var.get_unchecked() {
+                        Index::Input(_) => *coeff,
+                        Index::Aux(i) => *coeff * self.values[i],
+                    }
+                });
+                self.values.push(value);
+                self.values.len() - 1
+            }
+        };
+        Factor::Scaled(F::ONE, index)
+    }
+}
+
+impl<'a, F: PrimeField> ConstraintSystem<F> for PoseidonSystem<'a, F> {
+    type Root = Self;
+
+    fn alloc<V, A, AR>(&mut self, _: A, f: V) -> Result<Variable, SynthesisError>
+    where
+        V: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let index = match &mut self.alloc {
+            Some(alloc) => {
+                self.ids.push(alloc());
+                self.ids.len() - 1
+            }
+            None => {
+                self.values.push(f()?);
+                self.values.len() - 1
+            }
+        };
+        Ok(Variable::new_unchecked(Index::Aux(index)))
+    }
+
+    fn alloc_input<V, A, AR>(&mut self, annotation: A, f: V) -> Result<Variable, SynthesisError>
+    where
+        V: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.alloc(annotation, f)
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LB: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LC: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+    {
+        let a = self.factor(&a(LinearCombination::zero()));
+        let b = self.factor(&b(LinearCombination::zero()));
+        if self.alloc.is_none() {
+            return;
+        }
+
+        // a * b - c = 0
+        let c = self.linear_gate(&c(LinearCombination::zero()));
+        let mut add_terms: Vec<(F, WitnessID)> = c
+            .add_terms
+            .iter()
+            .map(|&(coeff, id)| (-coeff, id))
+            .collect();
+        let mut mul_terms = vec![];
+        let mut constant_term = -c.constant_term;
+        match (a, b) {
+            (Factor::Constant(x), Factor::Constant(y)) => constant_term += x * y,
+            (Factor::Constant(x), Factor::Scaled(y, i))
+            | (Factor::Scaled(y, i), Factor::Constant(x)) => add_terms.push((x * y, self.ids[i])),
+            (Factor::Scaled(x, i), Factor::Scaled(y, j)) => {
+                mul_terms.push((x * y, self.ids[i], self.ids[j]))
+            }
+        }
+        self.gates.push(gate(mul_terms, add_terms, constant_term));
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+fn synthesis_error(e: SynthesisError) -> Error {
+    Error::MemoryCheckingError(format!("challenge hash: {}", e))
+}
+
+/// The Poseidon gadget of neptune over the state and the value, returning the
+/// index of the hash
+fn poseidon_gadget<F: PrimeField>(
+    cs: &mut PoseidonSystem<'_, F>,
+    inputs: [Option<F>; 2],
+    constants: &PoseidonConstants<F, U2>,
+) -> Result<usize, Error> {
+    let inputs = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            AllocatedNum::alloc(cs.namespace(|| format!("input {}", i)), || {
+                x.ok_or(SynthesisError::AssignmentMissing)
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(synthesis_error)?;
+    let hash =
+        neptune::circuit2::poseidon_hash_allocated(cs.namespace(|| "hash"), inputs, constants)
+            .map_err(synthesis_error)?;
+    match hash.get_variable().get_unchecked() {
+        Index::Aux(i) => Ok(i),
+        Index::Input(_) => Err(Error::MemoryCheckingError(
+            "challenge hash is not a variable".to_string(),
+        )),
+    }
+}
+
+/// Constrain the absorption of `value` into `state`, both linear
+fn absorb_gates<F: PrimeField>(
+    state: &AcirArithGate<F>,
+    value: &AcirArithGate<F>,
+    constants: &PoseidonConstants<F, U2>,
+    alloc: &mut (dyn FnMut() -> WitnessID + Send),
+    constraints: &mut Vec<AcirArithGate<F>>,
+) -> Result<Absorb, Error> {
+    let mut cs = PoseidonSystem::new(Some(alloc));
+    let hash = poseidon_gadget(&mut cs, [None, None], constants)?;
+
+    // the inputs of the gadget are its first variables
+    for (input, id) in [state, value].into_iter().zip(&cs.ids) {
+        let mut add_terms = vec![(F::ONE, *id)];
+        add_terms.extend(input.add_terms.iter().map(|&(coeff, id)| (-coeff, id)));
+        constraints.push(gate(vec![], add_terms, -input.constant_term));
+    }
+    constraints.extend(cs.gates);
+
+    Ok(Absorb {
+        state: cs.ids[hash],
+        witnesses: cs.ids,
+    })
+}
+
+/// Assign the witnesses of `absorb`, returning the next state
+fn assign_absorb<F: PrimeField>(
+    absorb: &Absorb,
+    state: F,
+    value: F,
+    constants: &PoseidonConstants<F, U2>,
+    witness: &mut BTreeMap<WitnessID, F>,
+) -> Result<F, Error> {
+    let mut cs = PoseidonSystem::new(None);
+    let hash = poseidon_gadget(&mut cs, [Some(state), Some(value)], constants)?;
+    if cs.values.len() != absorb.witnesses.len() {
+        return Err(Error::MemoryCheckingError(
+            "challenge hash does not match its layout".to_string(),
+        ));
+    }
+
+    let next = cs.values[hash];
+    witness.extend(absorb.witnesses.iter().cloned().zip(cs.values));
+    Ok(next)
+}
+
+fn num_bits(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).max(1)
+}
+
+impl<F: PrimeField> MemoryAccess<F> {
+    pub fn from_mem_op<AF: ArkPrimeField>(
+        position: usize,
+        op: &MemOp<GenericFieldElement<AF>>,
+    ) -> Result<Self, Error> {
+        let operation: AcirArithGate<F> = op.operation.clone().try_into()?;
+
+        Ok(Self {
+            position,
+            is_write: operation.constant_term == F::ONE,
+            index: op.index.clone().try_into()?,
+            value: op.value.clone().try_into()?,
+        })
+    }
+}
+
+impl<F: PrimeField> MemoryBlock<F> {
+    pub fn new(
+        block_id: u32,
+        block_type: BlockType,
+        position: usize,
+        init: Vec<WitnessID>,
+    ) -> Self {
+        Self {
+            block_id,
+            block_type,
+            position,
+            init,
+            accesses: vec![],
+            layout: None,
+            constraints: vec![],
+        }
+    }
+
+    pub fn witnesses(&self) -> impl Iterator<Item = WitnessID> + '_ {
+        self.init.iter().cloned().chain(
+            self.accesses
+                .iter()
+                .flat_map(|access| access.index.witnesses().chain(access.value.witnesses())),
+        )
+    }
+
+    fn trace(&self) -> Vec<TraceEntry<F>> {
+        let init = self.init.iter().enumerate().map(|(addr, &id)| TraceEntry {
+            addr: constant(F::from(addr as u64)),
+            time: 0,
+            value: gate(vec![], vec![(F::ONE, id)], F::ZERO),
+            is_write: true,
+        });
+
+        let accesses = self
+            .accesses
+            .iter()
+            .enumerate()
+            .map(|(k, access)| TraceEntry {
+                addr: access.index.clone(),
+                time: k as u64 + 1,
+                value: access.value.clone(),
+                is_write: access.is_write,
+            });
+
+        init.chain(accesses).collect()
+    }
+
+    /// Allocate the auxiliary witnesses starting from `next_witness` and
+    /// generate the memory checking constraints over them
+    pub fn allocate(&mut self, next_witness: &mut u32) -> Result<(), Error> {
+        let trace = self.trace();
+        let len = trace.len();
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        let mut alloc = || {
+            let id = WitnessID(*next_witness);
+            *next_witness += 1;
+            id
+        };
+
+        let gap_bits = num_bits(self.accesses.len() as u64);
+
+        let sorted: Vec<SortedEntry> = (0..len)
+            .map(|_| SortedEntry {
+                addr: alloc(),
+                time: alloc(),
+                value: alloc(),
+                is_write: alloc(),
+            })
+            .collect();
+
+        let transitions: Vec<Transition> = (1..len)
+            .map(|_| Transition {
+                addr_step: alloc(),
+                read_selector: alloc(),
+                time_gap: alloc(),
+                time_gap_bits: (0..gap_bits).map(|_| alloc()).collect(),
+            })
+            .collect();
+
+        let trace_fingerprints: Vec<WitnessID> = (0..len).map(|_| alloc()).collect();
+        let trace_products: Vec<WitnessID> = (0..len).map(|_| alloc()).collect();
+        let sorted_fingerprints: Vec<WitnessID> = (0..len).map(|_| alloc()).collect();
+        let sorted_products: Vec<WitnessID> = (0..len).map(|_| alloc()).collect();
+
+        let one = F::ONE;
+        let neg = -F::ONE;
+        let mut constraints = vec![];
+
+        // challenges, hashing both traces
+        let constants = PoseidonConstants::<F, U2>::new();
+        let absorbed = trace
+            .iter()
+            .flat_map(|entry| [entry.addr.clone(), entry.value.clone()])
+            .chain(sorted.iter().flat_map(|entry| {
+                [entry.addr, entry.time, entry.value, entry.is_write].map(variable)
+            }))
+            .chain([constant(F::ONE), constant(F::from(2))]);
+
+        let mut state = constant(F::from(self.block_id as u64));
+        let mut challenge_hash = vec![];
+        for value in absorbed {
+            let absorb = absorb_gates(&state, &value, &constants, &mut alloc, &mut constraints)?;
+            state = variable(absorb.state);
+            challenge_hash.push(absorb);
+        }
+        let alpha = challenge_hash[challenge_hash.len() - 2].state;
+        let gamma = challenge_hash[challenge_hash.len() - 1].state;
+
+        let alpha_sq = alloc();
+        let alpha_cube = alloc();
+
+        // challenge powers
+        constraints.push(gate(
+            vec![(neg, alpha, alpha)],
+            vec![(one, alpha_sq)],
+            F::ZERO,
+        ));
+        constraints.push(gate(
+            vec![(neg, alpha_sq, alpha)],
+            vec![(one, alpha_cube)],
+            F::ZERO,
+        ));
+
+        // f - gamma + addr + alpha * value + alpha^2 * time + alpha^3 * is_write = 0
+        for (entry, &f) in trace.iter().zip(&trace_fingerprints) {
+            let mut add_terms = vec![(one, f), (neg, gamma)];
+            add_terms.extend(entry.addr.add_terms.iter().cloned());
+            add_terms.push((entry.value.constant_term, alpha));
+            add_terms.push((F::from(entry.time), alpha_sq));
+            if entry.is_write {
+                add_terms.push((one, alpha_cube));
+            }
+
+            let mul_terms = entry
+                .value
+                .add_terms
+                .iter()
+                .map(|&(coeff, id)| (coeff, alpha, id))
+                .collect();
+
+            constraints.push(gate(mul_terms, add_terms, entry.addr.constant_term));
+        }
+
+        for (entry, &f) in sorted.iter().zip(&sorted_fingerprints) {
+            constraints.push(gate(
+                vec![
+                    (one, alpha, entry.value),
+                    (one, alpha_sq, entry.time),
+                    (one, alpha_cube, entry.is_write),
+                ],
+                vec![(one, f), (neg, gamma), (one, entry.addr)],
+                F::ZERO,
+            ));
+
+            // is_write is boolean
+            constraints.push(gate(
+                vec![(one, entry.is_write, entry.is_write)],
+                vec![(neg, entry.is_write)],
+                F::ZERO,
+            ));
+        }
+
+        // grand products over both traces must agree
+        for (fingerprints, products) in [
+            (&trace_fingerprints, &trace_products),
+            (&sorted_fingerprints, &sorted_products),
+        ] {
+            constraints.push(gate(
+                vec![],
+                vec![(one, products[0]), (neg, fingerprints[0])],
+                F::ZERO,
+            ));
+            for i in 1..len {
+                constraints.push(gate(
+                    vec![(neg, products[i - 1], fingerprints[i])],
+                    vec![(one, products[i])],
+                    F::ZERO,
+                ));
+            }
+        }
+        constraints.push(gate(
+            vec![],
+            vec![
+                (one, trace_products[len - 1]),
+                (neg, sorted_products[len - 1]),
+            ],
+            F::ZERO,
+        ));
+
+        // the sorted trace starts with the initial write of address 0
+        constraints.push(gate(vec![], vec![(one, sorted[0].addr)], F::ZERO));
+        constraints.push(gate(vec![], vec![(one, sorted[0].time)], F::ZERO));
+
+        for (i, transition) in transitions.iter().enumerate() {
+            let prev = &sorted[i];
+            let cur = &sorted[i + 1];
+            let d = transition.addr_step;
+
+            // d = addr - prev_addr, d is boolean
+            constraints.push(gate(
+                vec![],
+                vec![(one, d), (neg, cur.addr), (one, prev.addr)],
+                F::ZERO,
+            ));
+            constraints.push(gate(vec![(one, d, d)], vec![(neg, d)], F::ZERO));
+
+            // a new address starts at time 0
+            constraints.push(gate(vec![(one, d, cur.time)], vec![], F::ZERO));
+
+            // gap = (1 - d) * (time - prev_time - 1)
+            constraints.push(gate(
+                vec![(one, d, cur.time), (neg, d, prev.time)],
+                vec![
+                    (one, transition.time_gap),
+                    (neg, cur.time),
+                    (one, prev.time),
+                    (neg, d),
+                ],
+                one,
+            ));
+
+            // gap fits in the bits, so times strictly increase
+            let mut bits_terms = vec![(one, transition.time_gap)];
+            let mut power = one;
+            for &bit in &transition.time_gap_bits {
+                constraints.push(gate(vec![(one, bit, bit)], vec![(neg, bit)], F::ZERO));
+                bits_terms.push((-power, bit));
+                power = power.double();
+            }
+            constraints.push(gate(vec![], bits_terms, F::ZERO));
+
+            // r = (1 - d) * (1 - is_write)
+            let r = transition.read_selector;
+            constraints.push(gate(
+                vec![(neg, d, cur.is_write)],
+                vec![(one, r), (one, d), (one, cur.is_write)],
+                neg,
+            ));
+
+            // r * (value - prev_value) = 0
+            constraints.push(gate(
+                vec![(one, r, cur.value), (neg, r, prev.value)],
+                vec![],
+                F::ZERO,
+            ));
+        }
+
+        self.layout = Some(MemoryLayout {
+            challenge_hash,
+            alpha,
+            alpha_sq,
+            alpha_cube,
+            gamma,
+            sorted,
+            transitions,
+            trace_fingerprints,
+            trace_products,
+            sorted_fingerprints,
+            sorted_products,
+        });
+        self.constraints = constraints;
+        Ok(())
+    }
+
+    /// Compute the auxiliary witnesses from the solved circuit witness
+    pub fn assign_aux(&self, witness: &mut BTreeMap<WitnessID, F>) -> Result<(), Error> {
+        let layout = match &self.layout {
+            Some(layout) => layout,
+            None => return Ok(()),
+        };
+
+        let addresses: BTreeMap<Vec<u8>, u64> = (0..self.init.len() as u64)
+            .map(|addr| (F::from(addr).to_repr().as_ref().to_vec(), addr))
+            .collect();
+
+        let entries = self
+            .trace()
+            .into_iter()
+            .map(|entry| {
+                let addr = evaluate_linear(&entry.addr, witness)?;
+                let addr = *addresses.get(addr.to_repr().as_ref()).ok_or_else(|| {
+                    Error::MemoryCheckingError(format!(
+                        "index out of bounds in block {}",
+                        self.block_id
+                    ))
+                })?;
+                let value = evaluate_linear(&entry.value, witness)?;
+                Ok((addr, entry.time, value, entry.is_write))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut sorted = entries.clone();
+        sorted.sort_by_key(|&(addr, time, _, _)| (addr, time));
+
+        let constants = PoseidonConstants::<F, U2>::new();
+        let absorbed = entries
+            .iter()
+            .flat_map(|&(addr, _, value, _)| [F::from(addr), value])
+            .chain(sorted.iter().flat_map(|&(addr, time, value, is_write)| {
+                [
+                    F::from(addr),
+                    F::from(time),
+                    value,
+                    F::from(is_write as u64),
+                ]
+            }))
+            .chain([F::ONE, F::from(2)]);
+
+        let mut state = F::from(self.block_id as u64);
+        let mut states = vec![];
+        for (absorb, value) in layout.challenge_hash.iter().zip(absorbed) {
+            state = assign_absorb(absorb, state, value, &constants, witness)?;
+            states.push(state);
+        }
+        let alpha = states[states.len() - 2];
+        let gamma = states[states.len() - 1];
+        let alpha_sq = alpha.square();
+        let alpha_cube = alpha_sq * alpha;
+
+        witness.insert(layout.alpha_sq, alpha_sq);
+        witness.insert(layout.alpha_cube, alpha_cube);
+
+        let fingerprint = |&(addr, time, value, is_write): &(u64, u64, F, bool)| {
+            let w = if is_write { alpha_cube } else { F::ZERO };
+            gamma - F::from(addr) - alpha * value - alpha_sq * F::from(time) - w
+        };
+
+        for (entries, fingerprints, products) in [
+            (&entries, &layout.trace_fingerprints, &layout.trace_products),
+            (
+                &sorted,
+                &layout.sorted_fingerprints,
+                &layout.sorted_products,
+            ),
+        ] {
+            let mut product = F::ONE;
+            for ((entry, &f_id), &p_id) in entries.iter().zip(fingerprints).zip(products) {
+                let f = fingerprint(entry);
+                product *= f;
+                witness.insert(f_id, f);
+                witness.insert(p_id, product);
+            }
+        }
+
+        for (&(addr, time, value, is_write), ids) in sorted.iter().zip(&layout.sorted) {
+            witness.insert(ids.addr, F::from(addr));
+            witness.insert(ids.time, F::from(time));
+            witness.insert(ids.value, value);
+            witness.insert(ids.is_write, F::from(is_write as u64));
+        }
+
+        for (i, transition) in layout.transitions.iter().enumerate() {
+            let (prev_addr, prev_time, _, _) = sorted[i];
+            let (addr, time, _, is_write) = sorted[i + 1];
+
+            let same_addr = addr == prev_addr;
+            let gap = if same_addr { time - prev_time - 1 } else { 0 };
+
+            witness.insert(transition.addr_step, F::from(addr - prev_addr));
+            witness.insert(
+                transition.read_selector,
+                F::from((same_addr && !is_write) as u64),
+            );
+            witness.insert(transition.time_gap, F::from(gap));
+            for (j, &bit) in transition.time_gap_bits.iter().enumerate() {
+                witness.insert(bit, F::from((gap >> j) & 1));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn init_opcode<AF: ArkPrimeField>(
+        &self,
+    ) -> (usize, Opcode<GenericFieldElement<AF>>) {
+        let opcode = Opcode::MemoryInit {
+            block_id: BlockId(self.block_id),
+            init: self.init.iter().map(|id| ACVMWitness(id.0)).collect(),
+            block_type: self.block_type.clone(),
+        };
+        (self.position, opcode)
+    }
+
+    pub(crate) fn access_opcodes<AF: ArkPrimeField>(
+        &self,
+    ) -> impl Iterator<Item = Result<(usize, Opcode<GenericFieldElement<AF>>), Error>> + '_ {
+        self.accesses.iter().map(|access| {
+            let operation = constant(F::from(access.is_write as u64));
+            let opcode = Opcode::MemoryOp {
+                block_id: BlockId(self.block_id),
+                op: MemOp {
+                    operation: operation.try_into()?,
+                    index: access.index.clone().try_into()?,
+                    value: access.value.clone().try_into()?,
+                },
+                predicate: None,
+            };
+            Ok((access.position, opcode))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ivc_program::witness::Witness;
+
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    fn witness_gate(id: u32) -> AcirArithGate<F> {
+        variable(WitnessID(id))
+    }
+
+    fn make_block() -> MemoryBlock<F> {
+        // mem = [w0, w1, w2]; mem[w3] = w4; w5 = mem[w3]; w6 = mem[0]
+        let mut block = MemoryBlock::new(
+            0,
+            BlockType::Memory,
+            0,
+            vec![WitnessID(0), WitnessID(1), WitnessID(2)],
+        );
+        block.accesses = vec![
+            MemoryAccess {
+                position: 1,
+                is_write: true,
+                index: witness_gate(3),
+                value: witness_gate(4),
+            },
+            MemoryAccess {
+                position: 2,
+                is_write: false,
+                index: witness_gate(3),
+                value: witness_gate(5),
+            },
+            MemoryAccess {
+                position: 3,
+                is_write: false,
+                index: constant(F::zero()),
+                value: witness_gate(6),
+            },
+        ];
+        let mut next_witness = 7;
+        block.allocate(&mut next_witness).unwrap();
+        block
+    }
+
+    fn assign(values: [u64; 7]) -> Witness<F> {
+        let block = make_block();
+        let mut witness = values
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (WitnessID(i as u32), F::from(v)))
+            .collect();
+        block.assign_aux(&mut witness).unwrap();
+        Witness(witness)
+    }
+
+    #[test]
+    fn test_memory_checking_satisfied() {
+        let block = make_block();
+        let witness = assign([10, 11, 12, 2, 42, 42, 10]);
+
+        for (i, gate) in block.constraints.iter().enumerate() {
+            assert!(gate.is_satisfied(&witness).unwrap(), "constraint {}", i);
+        }
+    }
+
+    #[test]
+    fn test_challenges_are_poseidon() {
+        let block = make_block();
+        let witness = assign([10, 11, 12, 2, 42, 42, 10]);
+        let layout = block.layout.as_ref().unwrap();
+
+        // gamma is the squeeze after alpha
+        let constants = PoseidonConstants::<F, U2>::new();
+        let alpha = witness.0[&layout.alpha];
+        let expected =
+            neptune::Poseidon::new_with_preimage(&[alpha, F::from(2)], &constants).hash();
+        assert_eq!(witness.0[&layout.gamma], expected);
+    }
+
+    #[test]
+    fn test_memory_checking_wrong_read() {
+        let block = make_block();
+        let witness = assign([10, 11, 12, 2, 42, 43, 10]);
+
+        assert!(block
+            .constraints
+            .iter()
+            .any(|gate| !gate.is_satisfied(&witness).unwrap()));
+    }
+
+    #[test]
+    fn test_memory_checking_forged_trace() {
+        let block = make_block();
+        let layout = block.layout.as_ref().unwrap();
+
+        // w5 reads 43 after the write of 42: the sorted trace claims the
+        // write was 43, and with alpha = 0 the fingerprints ignore values
+        let mut witness = assign([10, 11, 12, 2, 42, 43, 10]).0;
+        witness.insert(layout.sorted[4].value, F::from(43));
+        for id in [layout.alpha, layout.alpha_sq, layout.alpha_cube] {
+            witness.insert(id, F::zero());
+        }
+
+        let gamma = witness[&layout.gamma];
+        for (entries, fingerprints, products) in [
+            (
+                &[0, 1, 2, 2, 2, 0][..],
+                &layout.trace_fingerprints,
+                &layout.trace_products,
+            ),
+            (
+                &[0, 0, 1, 2, 2, 2][..],
+                &layout.sorted_fingerprints,
+                &layout.sorted_products,
+            ),
+        ] {
+            let mut product = F::one();
+            for ((&addr, &f_id), &p_id) in entries.iter().zip(fingerprints).zip(products) {
+                let f = gamma - F::from(addr);
+                product *= f;
+                witness.insert(f_id, f);
+                witness.insert(p_id, product);
+            }
+        }
+        let witness = Witness(witness);
+
+        // the forgery passes every check but the hash of the challenges
+        let hash_witnesses: Vec<WitnessID> = layout
+            .challenge_hash
+            .iter()
+            .flat_map(|absorb| absorb.witnesses.iter().skip(2).cloned())
+            .collect();
+        let (hash, checks): (Vec<_>, Vec<_>) = block
+            .constraints
+            .iter()
+            .partition(|gate| gate.witnesses().any(|id| hash_witnesses.contains(&id)));
+
+        assert!(checks
+            .iter()
+            .all(|gate| gate.is_satisfied(&witness).unwrap()));
+        assert!(hash
+            .iter()
+            .any(|gate| !gate.is_satisfied(&witness).unwrap()));
+    }
+}
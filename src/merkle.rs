@@ -0,0 +1,228 @@
+//! Merkle commitments to a large step state.
+//!
+//! Instead of chaining the whole state, a step takes the root as public
+//! input, and opens the few leaves it reads or writes with membership hints
+//! passed as private input. The tree is binary, with the leaves padded with
+//! zeros to a power of two.
+//!
+//! `Poseidon2` hashes a pair like `std::hash::poseidon2::Poseidon2::hash`
+//! of Noir on `[left, right]` with a length of 2, so the Noir circuit can
+//! recompute the root from a hint.
+
+use std::marker::PhantomData;
+
+use acvm::{acir::acir_field::GenericFieldElement, blackbox_solver::BlackBoxFunctionSolver};
+use ark_ff::PrimeField as ArkPrimeField;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::PrimeField;
+use ivc_program::input::IO;
+
+use crate::{
+    field::{ff_slice_to_ark_prime_field, generic_ark_ff_to_prime_field},
+    Error,
+};
+
+pub trait MerkleHasher<F> {
+    fn hash_pair(&self, left: F, right: F) -> Result<F, Error>;
+}
+
+/// The Poseidon2 sponge of Noir over the field `AF`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Poseidon2<AF>(PhantomData<AF>);
+
+impl<F: PrimeField, AF: ArkPrimeField> MerkleHasher<F> for Poseidon2<AF>
+where
+    Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+{
+    fn hash_pair(&self, left: F, right: F) -> Result<F, Error> {
+        // Note: a single duplex, the capacity being the length times 2^64
+        let two_pow_64 = F::from(u64::MAX) + F::ONE;
+        let state =
+            ff_slice_to_ark_prime_field::<F, AF>(&[left, right, F::ZERO, F::from(2) * two_pow_64])?;
+        let state: Vec<_> = state
+            .into_iter()
+            .map(GenericFieldElement::from_repr)
+            .collect();
+
+        let output = Bn254BlackBoxSolver
+            .poseidon2_permutation(&state, 4)
+            .map_err(|e| Error::ACVMSolveError(e.to_string()))?;
+        generic_ark_ff_to_prime_field(&output[0])
+    }
+}
+
+/// Opening of a leaf: its value and the siblings from the leaf to the root
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof<F> {
+    pub index: u64,
+    pub leaf: F,
+    pub siblings: Vec<F>,
+}
+
+impl<F: PrimeField> MerkleProof<F> {
+    /// The root of a tree with this opening
+    pub fn root(&self, hasher: &impl MerkleHasher<F>) -> Result<F, Error> {
+        self.siblings
+            .iter()
+            .enumerate()
+            .try_fold(self.leaf, |node, (level, sibling)| {
+                match (self.index >> level) & 1 {
+                    0 => hasher.hash_pair(node, *sibling),
+                    _ => hasher.hash_pair(*sibling, node),
+                }
+            })
+    }
+
+    /// The hint of the opening: the index, the leaf then the siblings
+    pub fn to_hint(&self) -> Vec<F> {
+        [F::from(self.index), self.leaf]
+            .into_iter()
+            .chain(self.siblings.iter().cloned())
+            .collect()
+    }
+}
+
+pub struct MerkleTree<F, H> {
+    /// The padded leaves first, the root last
+    layers: Vec<Vec<F>>,
+    len: usize,
+    hasher: H,
+}
+
+impl<F: PrimeField, H: MerkleHasher<F>> MerkleTree<F, H> {
+    pub fn new(leaves: Vec<F>, hasher: H) -> Result<Self, Error> {
+        let len = leaves.len();
+        let mut layer = leaves;
+        layer.resize(len.max(1).next_power_of_two(), F::ZERO);
+
+        let mut layers = vec![layer];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| hasher.hash_pair(pair[0], pair[1]))
+                .collect::<Result<_, _>>()?;
+            layers.push(next);
+        }
+
+        Ok(Self {
+            layers,
+            len,
+            hasher,
+        })
+    }
+
+    pub fn root(&self) -> F {
+        self.layers.last().unwrap()[0]
+    }
+
+    /// Number of siblings of an opening
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    /// The leaves, without padding
+    pub fn leaves(&self) -> &[F] {
+        &self.layers[0][..self.len]
+    }
+
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    pub fn open(&self, index: usize) -> Result<MerkleProof<F>, Error> {
+        if index >= self.len {
+            return Err(Error::InvalidInput);
+        }
+
+        let siblings = self.layers[..self.depth()]
+            .iter()
+            .enumerate()
+            .map(|(level, layer)| layer[(index >> level) ^ 1])
+            .collect();
+
+        Ok(MerkleProof {
+            index: index as u64,
+            leaf: self.layers[0][index],
+            siblings,
+        })
+    }
+
+    /// Set a leaf, updating the nodes up to the root
+    pub fn update(&mut self, index: usize, leaf: F) -> Result<(), Error> {
+        if index >= self.len {
+            return Err(Error::InvalidInput);
+        }
+
+        self.layers[0][index] = leaf;
+        let mut position = index;
+        for level in 0..self.depth() {
+            let left = self.layers[level][position & !1];
+            let right = self.layers[level][position | 1];
+            position >>= 1;
+            self.layers[level + 1][position] = self.hasher.hash_pair(left, right)?;
+        }
+        Ok(())
+    }
+
+    /// The public input committing to the state: the root
+    pub fn public_input(&self) -> IO<F> {
+        IO(vec![self.root()])
+    }
+
+    /// The hints of the openings of `indices`, one after the other, for the
+    /// private input of a step
+    pub fn hints(&self, indices: &[usize]) -> Result<IO<F>, Error> {
+        let mut hints = Vec::with_capacity(indices.len() * (self.depth() + 2));
+        for index in indices {
+            hints.extend(self.open(*index)?.to_hint());
+        }
+        Ok(IO(hints))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+    type AF = ark_bn254::Fr;
+
+    #[test]
+    fn test_merkle_tree() {
+        let hasher = Poseidon2::<AF>::default();
+        let leaves: Vec<F> = (0..5).map(F::from).collect();
+        let mut tree = MerkleTree::new(leaves.clone(), hasher).unwrap();
+
+        assert_eq!(tree.depth(), 3);
+        assert_eq!(tree.leaves(), leaves);
+        assert_eq!(tree.public_input().0, [tree.root()]);
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.open(index).unwrap();
+            assert_eq!(proof.leaf, *leaf);
+            assert_eq!(proof.root(&hasher).unwrap(), tree.root());
+
+            let wrong = MerkleProof {
+                leaf: F::from(7),
+                ..proof
+            };
+            assert_ne!(wrong.root(&hasher).unwrap(), tree.root());
+        }
+        assert!(tree.open(5).is_err());
+
+        tree.update(3, F::from(7)).unwrap();
+        let mut updated = leaves;
+        updated[3] = F::from(7);
+        assert_eq!(
+            tree.root(),
+            MerkleTree::new(updated, hasher).unwrap().root()
+        );
+
+        let hints = tree.hints(&[3, 0]).unwrap();
+        assert_eq!(hints.0.len(), 2 * 5);
+        assert_eq!(hints.0[..2], [F::from(3), F::from(7)]);
+        assert_eq!(hints.0[5..7], [F::from(0), F::from(0)]);
+    }
+}
@@ -0,0 +1,297 @@
+//! A nova-snark `StepCircuit` over a compiled circuit.
+//!
+//! `z_in` are the public inputs and `z_out` the public outputs, both in
+//! witness order. Every other witness is allocated from the witness of the
+//! step, which is missing when Nova only synthesizes the shape.
+//!
+//! With `with_state_digest`, the state is instead a single Poseidon digest
+//! of the public inputs, see `StateDigest`. The circuit then checks the
+//! digest of its public inputs and outputs the digest of its public
+//! outputs, so the folded instance has the same size for any state width.
+//!
+//! With `with_input_commitment`, the state ends with a chain of the
+//! commitments to the private inputs of the steps (see
+//! `StateDigest::commit` and `StateDigest::chain`), to check revealed hints
+//! against a proof. The randomness of every commitment is a witness of its
+//! step, set from the result of the execution.
+//!
+//! Note: the proofs of nova-snark are not zero-knowledge, a proof reveals
+//! as much as the witnesses of its steps
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError};
+use ff::PrimeField;
+use ivc_program::{
+    program::{Term, WitnessID, LC},
+    witness::Witness,
+};
+use neptune::{poseidon::PoseidonConstants, Poseidon};
+use nova_snark::traits::circuit::StepCircuit;
+use typenum::U2;
+
+use crate::program::CircuitStructure;
+
+/// Poseidon digest of a state: starting from its length, every element is
+/// hashed with the digest of the previous ones
+#[derive(Clone)]
+pub struct StateDigest<F: PrimeField> {
+    constants: Arc<PoseidonConstants<F, U2>>,
+}
+
+impl<F: PrimeField> Default for StateDigest<F> {
+    fn default() -> Self {
+        Self {
+            constants: Arc::new(PoseidonConstants::new()),
+        }
+    }
+}
+
+impl<F: PrimeField> StateDigest<F> {
+    pub fn hash(&self, state: &[F]) -> F {
+        state.iter().fold(F::from(state.len() as u64), |digest, x| {
+            Poseidon::new_with_preimage(&[digest, *x], &self.constants).hash()
+        })
+    }
+
+    /// Constraints of `hash`
+    pub fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        mut cs: CS,
+        state: &[AllocatedNum<F>],
+    ) -> Result<AllocatedNum<F>, SynthesisError> {
+        let len = F::from(state.len() as u64);
+        let mut digest = AllocatedNum::alloc(cs.namespace(|| "length"), || Ok(len))?;
+        cs.enforce(
+            || "length is constant",
+            |lc| lc + digest.get_variable(),
+            |lc| lc + CS::one(),
+            |lc| lc + (len, CS::one()),
+        );
+
+        for (i, x) in state.iter().enumerate() {
+            digest = neptune::circuit2::poseidon_hash_allocated(
+                cs.namespace(|| format!("hash {}", i)),
+                vec![digest, x.clone()],
+                &self.constants,
+            )?;
+        }
+        Ok(digest)
+    }
+
+    /// Commitment to `input` with `randomness`, hashed before the input so
+    /// the commitment hides it
+    pub fn commit(&self, input: &[F], randomness: F) -> F {
+        let preimage: Vec<F> = std::iter::once(randomness)
+            .chain(input.iter().cloned())
+            .collect();
+        self.hash(&preimage)
+    }
+
+    /// Constraints of `commit`
+    pub fn synthesize_commitment<CS: ConstraintSystem<F>>(
+        &self,
+        cs: CS,
+        input: &[AllocatedNum<F>],
+        randomness: AllocatedNum<F>,
+    ) -> Result<AllocatedNum<F>, SynthesisError> {
+        let preimage: Vec<AllocatedNum<F>> = std::iter::once(randomness)
+            .chain(input.iter().cloned())
+            .collect();
+        self.synthesize(cs, &preimage)
+    }
+
+    /// Chain of `commitments`, from zero: each one is hashed with the chain
+    /// of the previous ones
+    pub fn chain(&self, commitments: &[F]) -> F {
+        commitments.iter().fold(F::ZERO, |chain, commitment| {
+            self.hash(&[chain, *commitment])
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct NoirStepCircuit<F: PrimeField> {
+    pub structure: Arc<CircuitStructure<F>>,
+    pub witness: Option<Witness<F>>,
+    /// Set to chain the digest of the state instead of the state
+    pub digest: Option<StateDigest<F>>,
+    /// Set to chain the commitments to the private inputs
+    pub input_commitment: Option<StateDigest<F>>,
+    /// Randomness of the commitment to the private input of the step
+    pub input_randomness: Option<F>,
+}
+
+impl<F: PrimeField> NoirStepCircuit<F> {
+    /// The circuit without witness, for the public parameters.
+    /// Note: the structure is expected to be compiled
+    pub fn new(structure: Arc<CircuitStructure<F>>) -> Self {
+        Self {
+            structure,
+            witness: None,
+            digest: None,
+            input_commitment: None,
+            input_randomness: None,
+        }
+    }
+
+    /// The same circuit, with the digest of the state as state
+    pub fn with_state_digest(mut self) -> Self {
+        self.digest = Some(StateDigest::default());
+        self
+    }
+
+    /// The same circuit, with the chain of the commitments to the private
+    /// inputs at the end of the state
+    pub fn with_input_commitment(mut self) -> Self {
+        self.input_commitment = Some(StateDigest::default());
+        self
+    }
+
+    /// The circuit of a step, with the witness returned by the execution
+    pub fn with_witness(&self, witness: Witness<F>) -> Self {
+        Self {
+            witness: Some(witness),
+            ..self.clone()
+        }
+    }
+
+    /// The same circuit, with the `input_randomness` of the result of the step
+    pub fn with_input_randomness(mut self, randomness: Option<F>) -> Self {
+        self.input_randomness = randomness;
+        self
+    }
+
+    /// The initial state of Nova for the public inputs `state`
+    pub fn z(&self, state: &[F]) -> Vec<F> {
+        let mut z = match &self.digest {
+            Some(digest) => vec![digest.hash(state)],
+            None => state.to_vec(),
+        };
+        if self.input_commitment.is_some() {
+            z.push(F::ZERO);
+        }
+        z
+    }
+
+    fn state_len(&self) -> usize {
+        match self.digest {
+            Some(_) => 1,
+            None => self.structure.program.public_inputs.len(),
+        }
+    }
+}
+
+fn to_lc<F: PrimeField, CS: ConstraintSystem<F>>(
+    lc: &LC<F>,
+    variables: &BTreeMap<WitnessID, AllocatedNum<F>>,
+) -> LinearCombination<F> {
+    lc.0.iter()
+        .fold(LinearCombination::zero(), |result, term| match term {
+            Term::LC {
+                coefficient,
+                var_id,
+            } => result + (*coefficient, variables[var_id].get_variable()),
+            Term::Const(value) => result + (*value, CS::one()),
+        })
+}
+
+impl<F: PrimeField> StepCircuit<F> for NoirStepCircuit<F> {
+    fn arity(&self) -> usize {
+        self.state_len() + self.input_commitment.is_some() as usize
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        z: &[AllocatedNum<F>],
+    ) -> Result<Vec<AllocatedNum<F>>, SynthesisError> {
+        // Note: the structure is expected to be compiled, so neither fails
+        let template = self
+            .structure
+            .try_r1cs_template()
+            .map_err(|_| SynthesisError::AssignmentMissing)?;
+        let num_witness = template
+            .num_witness()
+            .map_err(|_| SynthesisError::AssignmentMissing)?;
+        let io = &self.structure.program;
+        assert_eq!(z.len(), self.arity());
+        let (z, chain) = z.split_at(self.state_len());
+
+        // Note: with a digest, the public inputs are allocated as the other
+        // witnesses
+        let mut variables: BTreeMap<WitnessID, AllocatedNum<F>> = match self.digest {
+            Some(_) => BTreeMap::new(),
+            None => io
+                .public_inputs
+                .iter()
+                .cloned()
+                .zip(z.iter().cloned())
+                .collect(),
+        };
+
+        for id in (0..num_witness).map(WitnessID) {
+            if variables.contains_key(&id) {
+                continue;
+            }
+            let value = self.witness.as_ref().and_then(|witness| witness.0.get(&id));
+            let variable = AllocatedNum::alloc(cs.namespace(|| format!("w{}", id.0)), || {
+                value.cloned().ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            variables.insert(id, variable);
+        }
+
+        for (i, constraint) in template.constraints.iter().enumerate() {
+            let a = to_lc::<F, CS>(&constraint.a, &variables);
+            let b = to_lc::<F, CS>(&constraint.b, &variables);
+            let c = to_lc::<F, CS>(&constraint.c, &variables);
+            cs.enforce(|| format!("constraint {}", i), |_| a, |_| b, |_| c);
+        }
+
+        let outputs: Vec<AllocatedNum<F>> = io
+            .public_outputs
+            .iter()
+            .map(|id| variables[id].clone())
+            .collect();
+
+        let ids = |ids: &BTreeSet<WitnessID>| -> Vec<AllocatedNum<F>> {
+            ids.iter().map(|id| variables[id].clone()).collect()
+        };
+
+        let mut z_out = match &self.digest {
+            None => outputs,
+            Some(digest) => {
+                let z_in = digest
+                    .synthesize(cs.namespace(|| "digest of z_in"), &ids(&io.public_inputs))?;
+                cs.enforce(
+                    || "z is the digest of the public inputs",
+                    |lc| lc + z_in.get_variable() - z[0].get_variable(),
+                    |lc| lc + CS::one(),
+                    |lc| lc,
+                );
+                vec![digest.synthesize(cs.namespace(|| "digest of z_out"), &outputs)?]
+            }
+        };
+
+        if let Some(commitment) = &self.input_commitment {
+            let randomness = AllocatedNum::alloc(cs.namespace(|| "input randomness"), || {
+                self.input_randomness
+                    .ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let private_input = commitment.synthesize_commitment(
+                cs.namespace(|| "commitment"),
+                &ids(&io.private_inputs),
+                randomness,
+            )?;
+            z_out.push(commitment.synthesize(
+                cs.namespace(|| "chain of commitments"),
+                &[chain[0].clone(), private_input],
+            )?);
+        }
+
+        Ok(z_out)
+    }
+}
@@ -0,0 +1,102 @@
+//! Helpers in the shape of Nova-Scotia, on top of `NoirStepCircuit`.
+//!
+//! The state is the public inputs, in witness order, and each step takes
+//! the private inputs of the Noir program, also in witness order. The
+//! secondary circuit is trivial, with a single zero as state.
+
+use std::sync::Arc;
+
+use acvm::{acir::acir_field::GenericFieldElement, blackbox_solver::BlackBoxFunctionSolver};
+use ark_ff::PrimeField as ArkPrimeField;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::Field;
+use ivc_program::{input::IO, program::WitnessID};
+use nova_snark::{
+    traits::{circuit::TrivialCircuit, snark::default_ck_hint, Engine},
+    PublicParams, RecursiveSNARK,
+};
+
+use crate::{
+    execute_steps, layout::IOLayout, nova::NoirStepCircuit, program::CircuitStructure,
+    prove::prove_steps, Error,
+};
+
+pub type C1<E1> = NoirStepCircuit<<E1 as Engine>::Scalar>;
+pub type C2<E2> = TrivialCircuit<<E2 as Engine>::Scalar>;
+
+pub type NoirPublicParams<E1, E2> = PublicParams<E1, E2, C1<E1>, C2<E2>>;
+pub type NoirRecursiveSNARK<E1, E2> = RecursiveSNARK<E1, E2, C1<E1>, C2<E2>>;
+
+/// Public parameters for folding `structure`, expected to be compiled
+pub fn create_public_params<E1, E2>(
+    structure: Arc<CircuitStructure<E1::Scalar>>,
+) -> Result<NoirPublicParams<E1, E2>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+{
+    create_public_params_with(&NoirStepCircuit::new(structure))
+}
+
+/// Public parameters for folding `circuit`, e.g. with a state digest
+pub fn create_public_params_with<E1, E2>(
+    circuit: &C1<E1>,
+) -> Result<NoirPublicParams<E1, E2>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+{
+    Ok(PublicParams::setup(
+        circuit,
+        &TrivialCircuit::default(),
+        &*default_ck_hint(),
+        &*default_ck_hint(),
+    )?)
+}
+
+/// Execute and fold one step per private input, from `start_state`
+pub fn create_recursive_circuit<E1, E2, AF>(
+    structure: Arc<CircuitStructure<E1::Scalar>>,
+    start_state: Vec<E1::Scalar>,
+    private_inputs: Vec<IO<E1::Scalar>>,
+    pp: &NoirPublicParams<E1, E2>,
+) -> Result<NoirRecursiveSNARK<E1, E2>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+    AF: ArkPrimeField,
+    Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+{
+    let io = &structure.program;
+    let public_inputs: Vec<WitnessID> = io.public_inputs.iter().cloned().collect();
+    let private_ids: Vec<WitnessID> = io.private_inputs.iter().cloned().collect();
+
+    let private_inputs = private_inputs
+        .iter()
+        .map(|input| IOLayout::make_witness(&private_ids, input))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let steps = execute_steps::<E1::Scalar, AF>(
+        structure.clone(),
+        IOLayout::make_witness(&public_inputs, &IO(start_state))?,
+        0,
+        private_inputs.into_iter(),
+    );
+
+    prove_steps(structure, pp, steps)
+}
+
+/// Verify `num_steps` steps from `start_state`, returning the final state
+pub fn verify<E1, E2>(
+    recursive_snark: &NoirRecursiveSNARK<E1, E2>,
+    pp: &NoirPublicParams<E1, E2>,
+    num_steps: usize,
+    start_state: &[E1::Scalar],
+) -> Result<Vec<E1::Scalar>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+{
+    let (state, _) = recursive_snark.verify(pp, num_steps, start_state, &[E2::Scalar::ZERO])?;
+    Ok(state)
+}
@@ -0,0 +1,207 @@
+use acvm::{
+    acir::{
+        acir_field::GenericFieldElement,
+        brillig::{ForeignCallParam, ForeignCallResult},
+    },
+    pwg::ForeignCallWaitInfo,
+};
+use ark_ff::PrimeField as ArkPrimeField;
+use ff::PrimeField;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    field::{ff_to_ark_prime_field, generic_ark_ff_to_prime_field, ConversionSite},
+    Error, ExecutionResult,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OracleValue<F> {
+    Single(F),
+    Array(Vec<F>),
+}
+
+/// A resolved foreign call, recorded so that the step can be replayed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OracleCall<F> {
+    pub function: String,
+    pub inputs: Vec<OracleValue<F>>,
+    pub outputs: Vec<OracleValue<F>>,
+}
+
+/// Supplies the values of foreign calls (oracles) met while solving
+pub trait OracleResolver<F> {
+    fn resolve(
+        &mut self,
+        function: &str,
+        inputs: &[OracleValue<F>],
+    ) -> Result<Vec<OracleValue<F>>, Error>;
+}
+
+impl<F, T> OracleResolver<F> for T
+where
+    T: FnMut(&str, &[OracleValue<F>]) -> Result<Vec<OracleValue<F>>, Error>,
+{
+    fn resolve(
+        &mut self,
+        function: &str,
+        inputs: &[OracleValue<F>],
+    ) -> Result<Vec<OracleValue<F>>, Error> {
+        self(function, inputs)
+    }
+}
+
+/// Rejects every foreign call
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOracle;
+
+impl<F> OracleResolver<F> for NoOracle {
+    fn resolve(
+        &mut self,
+        function: &str,
+        _inputs: &[OracleValue<F>],
+    ) -> Result<Vec<OracleValue<F>>, Error> {
+        Err(Error::UnresolvedOracle(function.to_string()))
+    }
+}
+
+/// Answers foreign calls with recorded ones in order, e.g. to replay a step.
+/// A call that does not match the next record is rejected
+#[derive(Clone, Debug)]
+pub struct RecordedOracle<F> {
+    calls: std::vec::IntoIter<OracleCall<F>>,
+}
+
+impl<F> RecordedOracle<F> {
+    pub fn new(calls: Vec<OracleCall<F>>) -> Self {
+        Self {
+            calls: calls.into_iter(),
+        }
+    }
+
+    /// The calls recorded in consecutive results, to replay a whole chain
+    /// (e.g. with `execute_steps_with_oracle`)
+    pub fn from_results<'a>(results: impl IntoIterator<Item = &'a ExecutionResult<F>>) -> Self
+    where
+        F: Clone + 'a,
+    {
+        Self::new(
+            results
+                .into_iter()
+                .flat_map(|result| result.oracle_calls.iter().cloned())
+                .collect(),
+        )
+    }
+
+    /// Whether every recorded call was answered
+    pub fn is_exhausted(&self) -> bool {
+        self.calls.as_slice().is_empty()
+    }
+}
+
+impl<F: PartialEq> OracleResolver<F> for RecordedOracle<F> {
+    fn resolve(
+        &mut self,
+        function: &str,
+        inputs: &[OracleValue<F>],
+    ) -> Result<Vec<OracleValue<F>>, Error> {
+        match self.calls.next() {
+            Some(call) if call.function == function && call.inputs == inputs => Ok(call.outputs),
+            _ => Err(Error::UnresolvedOracle(function.to_string())),
+        }
+    }
+}
+
+impl<F: PrimeField> OracleValue<F> {
+    pub(crate) fn from_param<AF: ArkPrimeField>(
+        param: &ForeignCallParam<GenericFieldElement<AF>>,
+    ) -> Result<Self, Error> {
+        let convert = |value: &GenericFieldElement<AF>| {
+            generic_ark_ff_to_prime_field(value).map_err(|e| e.at_site(ConversionSite::OracleValue))
+        };
+
+        Ok(match param {
+            ForeignCallParam::Single(value) => Self::Single(convert(value)?),
+            ForeignCallParam::Array(values) => {
+                Self::Array(values.iter().map(convert).collect::<Result<_, _>>()?)
+            }
+        })
+    }
+
+    pub(crate) fn to_param<AF: ArkPrimeField>(
+        &self,
+    ) -> Result<ForeignCallParam<GenericFieldElement<AF>>, Error> {
+        let convert = |value: &F| -> Result<GenericFieldElement<AF>, Error> {
+            Ok(GenericFieldElement::from_repr(ff_to_ark_prime_field(
+                value,
+            )?))
+        };
+
+        Ok(match self {
+            Self::Single(value) => ForeignCallParam::Single(convert(value)?),
+            Self::Array(values) => {
+                ForeignCallParam::Array(values.iter().map(convert).collect::<Result<_, _>>()?)
+            }
+        })
+    }
+}
+
+impl<F: PrimeField> OracleCall<F> {
+    /// Resolve a pending foreign call, returning the record and the ACVM result
+    pub(crate) fn resolve<AF: ArkPrimeField, O: OracleResolver<F>>(
+        info: &ForeignCallWaitInfo<GenericFieldElement<AF>>,
+        oracle: &mut O,
+    ) -> Result<(Self, ForeignCallResult<GenericFieldElement<AF>>), Error> {
+        let inputs = info
+            .inputs
+            .iter()
+            .map(OracleValue::from_param)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let outputs = oracle.resolve(&info.function, &inputs)?;
+
+        let result = ForeignCallResult {
+            values: outputs
+                .iter()
+                .map(OracleValue::to_param)
+                .collect::<Result<_, _>>()?,
+        };
+
+        let call = Self {
+            function: info.function.clone(),
+            inputs,
+            outputs,
+        };
+
+        Ok((call, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_recorded_oracle() {
+        let call = |input: u64, output: u64| OracleCall {
+            function: "get".to_string(),
+            inputs: vec![OracleValue::Single(F::from(input))],
+            outputs: vec![OracleValue::Array(vec![F::from(output)])],
+        };
+        let mut oracle = RecordedOracle::new(vec![call(1, 10), call(2, 20)]);
+
+        let outputs = oracle
+            .resolve("get", &[OracleValue::Single(F::from(1))])
+            .unwrap();
+        assert_eq!(outputs, vec![OracleValue::Array(vec![F::from(10)])]);
+        assert!(!oracle.is_exhausted());
+
+        // a call out of order is rejected
+        assert!(matches!(
+            oracle.resolve("get", &[OracleValue::Single(F::from(3))]),
+            Err(Error::UnresolvedOracle(_))
+        ));
+        assert!(oracle.is_exhausted());
+    }
+}
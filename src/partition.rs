@@ -0,0 +1,190 @@
+//! Partition of a circuit into a chain of smaller circuits, one IVC step
+//! each, for circuits too large to fold in a single step.
+//!
+//! The parts split the gates in order. The witnesses still needed after a
+//! part are its public outputs and the public inputs of the next part, both
+//! in the order of the circuit. A witness passed through a part is copied to
+//! a new output witness by a linear gate. The witness of every part is taken
+//! from the witness of the whole circuit, so parts are never solved alone.
+//!
+//! Note: private outputs are kept as plain witnesses of the parts.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ff::PrimeField;
+use ivc_program::{
+    program::{IOProfile, WitnessID},
+    witness::Witness,
+    Step,
+};
+
+use crate::{gate::AcirArithGate, program::CircuitStructure, r1cs::ConstraintCounter, Error};
+
+pub struct CircuitPart<F> {
+    pub structure: CircuitStructure<F>,
+
+    /// Witness of the whole circuit (in its R1CS numbering) of every
+    /// witness of the part, in order
+    pub sources: Vec<WitnessID>,
+}
+
+impl<F: PrimeField> CircuitPart<F> {
+    /// The witness of the part from the witness of the whole circuit
+    pub fn make_witness(&self, witness: &Witness<F>) -> Result<Witness<F>, Error> {
+        Ok(Witness(
+            self.sources
+                .iter()
+                .enumerate()
+                .map(|(i, source)| {
+                    let value = witness
+                        .0
+                        .get(source)
+                        .ok_or(Error::MissingWitness(source.0))?;
+                    Ok((WitnessID(i as u32), *value))
+                })
+                .collect::<Result<_, Error>>()?,
+        ))
+    }
+
+    pub fn make_step(&self, witness: &Witness<F>) -> Result<Step<F>, Error> {
+        self.structure.make_step(&self.make_witness(witness)?)
+    }
+}
+
+impl<F: PrimeField> CircuitStructure<F> {
+    /// Split the circuit into parts of at most `max_constraints` constraints,
+    /// besides the copies of passed through witnesses. A gate needing more
+    /// constraints gets a part of its own
+    pub fn partition(&self, max_constraints: usize) -> Result<Vec<CircuitPart<F>>, Error> {
+        let mut chunks: Vec<Vec<AcirArithGate<F>>> = vec![];
+        let mut counter = ConstraintCounter::new(self.encoding);
+        let mut num_constraints = 0;
+
+        for gate in self.r1cs_gates() {
+            if gate.is_trivial() {
+                continue;
+            }
+
+            let added = counter.add(&gate)?;
+            if chunks.is_empty() || num_constraints + added > max_constraints {
+                // Note: products are shared within a part only, recount
+                counter = ConstraintCounter::new(self.encoding);
+                num_constraints = counter.add(&gate)?;
+                chunks.push(vec![]);
+            } else {
+                num_constraints += added;
+            }
+            chunks.last_mut().unwrap().push(gate);
+        }
+
+        let referenced: Vec<BTreeSet<WitnessID>> = chunks
+            .iter()
+            .map(|gates| gates.iter().flat_map(|gate| gate.witnesses()).collect())
+            .collect();
+
+        // boundary k is between parts k - 1 and k
+        let io = &self.program;
+        let mut boundaries = vec![io.public_inputs.clone()];
+        let mut before = io.public_inputs.clone();
+        for k in 1..chunks.len() {
+            before.extend(referenced[k - 1].iter().cloned());
+            let after: BTreeSet<WitnessID> = referenced[k..]
+                .iter()
+                .flatten()
+                .chain(io.public_outputs.iter())
+                .cloned()
+                .collect();
+            boundaries.push(before.intersection(&after).cloned().collect());
+        }
+        boundaries.push(io.public_outputs.clone());
+
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(k, gates)| {
+                self.make_part(gates, &referenced[k], &boundaries[k], &boundaries[k + 1])
+            })
+            .collect())
+    }
+
+    fn make_part(
+        &self,
+        gates: Vec<AcirArithGate<F>>,
+        referenced: &BTreeSet<WitnessID>,
+        inputs: &BTreeSet<WitnessID>,
+        outputs: &BTreeSet<WitnessID>,
+    ) -> CircuitPart<F> {
+        let mut sources = vec![];
+        let mut alloc = |source: WitnessID| {
+            sources.push(source);
+            WitnessID(sources.len() as u32 - 1)
+        };
+
+        // witnesses of the gates, in the numbering of the part
+        let mut ids = BTreeMap::new();
+        let mut copies = vec![];
+
+        let public_inputs: BTreeSet<WitnessID> = inputs
+            .iter()
+            .map(|source| {
+                let id = alloc(*source);
+                ids.insert(*source, id);
+                id
+            })
+            .collect();
+
+        let public_outputs: BTreeSet<WitnessID> = outputs
+            .iter()
+            .map(|source| {
+                let id = alloc(*source);
+                match ids.get(source) {
+                    Some(input) => copies.push((id, *input)),
+                    None => {
+                        ids.insert(*source, id);
+                    }
+                }
+                id
+            })
+            .collect();
+
+        let mut private_inputs = BTreeSet::new();
+        for source in referenced.intersection(&self.program.private_inputs) {
+            if !ids.contains_key(source) {
+                let id = alloc(*source);
+                ids.insert(*source, id);
+                private_inputs.insert(id);
+            }
+        }
+
+        for source in referenced {
+            if !ids.contains_key(source) {
+                let id = alloc(*source);
+                ids.insert(*source, id);
+            }
+        }
+
+        let mut gates: Vec<AcirArithGate<F>> = gates
+            .iter()
+            .map(|gate| gate.map_witnesses(|id| ids[&id]))
+            .collect();
+
+        // output - input = 0
+        gates.extend(copies.into_iter().map(|(output, input)| AcirArithGate {
+            mul_terms: vec![],
+            add_terms: vec![(F::ONE, output), (-F::ONE, input)],
+            constant_term: F::ZERO,
+        }));
+
+        let io = IOProfile {
+            public_inputs,
+            private_inputs,
+            public_outputs,
+            private_outputs: Default::default(),
+        };
+
+        CircuitPart {
+            structure: CircuitStructure::from_gates(gates, io).with_encoding(self.encoding),
+            sources,
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Chains with a distinct first and last circuit.
+//!
+//! An `ExecutionPlan` runs an initialization circuit once, then the step
+//! circuit for every step, then a finalization circuit once, e.g. to set up
+//! the state from the inputs of the application and to decode and check the
+//! final state. The state passes from one circuit to the next as with a
+//! single circuit, so the public outputs of every circuit must match the
+//! public inputs of the next one, which is checked when building the plan.
+//! With the ABI of both circuits (see `CircuitStructure::set_abi`), the
+//! public return value must also have the shape of the public parameters
+//! of the next one, see `IOLayout::public_output_shape`.
+
+use std::sync::Arc;
+
+use acvm::{
+    acir::{acir_field::GenericFieldElement, circuit::Program},
+    blackbox_solver::BlackBoxFunctionSolver,
+};
+use ark_ff::PrimeField as ArkPrimeField;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::PrimeField;
+use ivc_program::{input::IO, witness::Witness};
+
+use crate::{
+    functions::{chain_io_steps, compile, execute_io_step},
+    program::CircuitStructure,
+    Error, ExecutionResult,
+};
+
+/// Fails if both circuits have a layout, and the public return value of
+/// `from` does not have the shape of the public parameters of `to`
+fn check_layouts<F>(
+    from: &CircuitStructure<F>,
+    to: &CircuitStructure<F>,
+    message: &'static str,
+) -> Result<(), Error> {
+    let (Some(from), Some(to)) = (&from.layout, &to.layout) else {
+        return Ok(());
+    };
+    match from.public_output_shape() {
+        Some(shape) if shape != to.public_input_shape() => {
+            Err(Error::InvalidExecutionPlan(message))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Init,
+    Step,
+    Final,
+}
+
+pub struct ExecutionPlan<F> {
+    pub init: Arc<CircuitStructure<F>>,
+    pub step: Arc<CircuitStructure<F>>,
+    pub finalize: Arc<CircuitStructure<F>>,
+}
+
+impl<F: PrimeField> ExecutionPlan<F> {
+    /// Fails unless the public outputs of `init` and `step` are as many as
+    /// the public inputs of `step` and `finalize`, and have the same layout
+    /// when it is known
+    pub fn new(
+        init: CircuitStructure<F>,
+        step: CircuitStructure<F>,
+        finalize: CircuitStructure<F>,
+    ) -> Result<Self, Error> {
+        let arity = step.program.public_inputs.len();
+        if init.program.public_outputs.len() != arity {
+            return Err(Error::InvalidExecutionPlan(
+                "init does not output the step state",
+            ));
+        }
+        if step.program.public_outputs.len() != arity {
+            return Err(Error::InvalidExecutionPlan(
+                "step does not output its state",
+            ));
+        }
+        if finalize.program.public_inputs.len() != arity {
+            return Err(Error::InvalidExecutionPlan(
+                "final does not take the step state",
+            ));
+        }
+
+        check_layouts(
+            &init,
+            &step,
+            "init does not output the layout of the step state",
+        )?;
+        check_layouts(&step, &step, "step does not output the layout of its state")?;
+        check_layouts(
+            &step,
+            &finalize,
+            "final does not take the layout of the step state",
+        )?;
+
+        Ok(Self {
+            init: Arc::new(init),
+            step: Arc::new(step),
+            finalize: Arc::new(finalize),
+        })
+    }
+
+    /// Compile the programs of the three circuits
+    pub fn compile<AF: ArkPrimeField>(
+        init: Program<GenericFieldElement<AF>>,
+        step: Program<GenericFieldElement<AF>>,
+        finalize: Program<GenericFieldElement<AF>>,
+    ) -> Result<Self, Error> {
+        Self::new(
+            compile::<F, AF>(init)?.0,
+            compile::<F, AF>(step)?.0,
+            compile::<F, AF>(finalize)?.0,
+        )
+    }
+
+    pub fn get(&self, phase: Phase) -> &Arc<CircuitStructure<F>> {
+        match phase {
+            Phase::Init => &self.init,
+            Phase::Step => &self.step,
+            Phase::Final => &self.finalize,
+        }
+    }
+
+    /// Execute `init` from `start_state`, a step per input of
+    /// `step_inputs`, then `finalize`, numbering them from 0. Yields the
+    /// phase with the results of each step, and stops after the first failed
+    /// step
+    pub fn execute<'a, AF: ArkPrimeField>(
+        &'a self,
+        start_state: IO<F>,
+        init_input: IO<F>,
+        step_inputs: impl Iterator<Item = IO<F>> + 'a,
+        final_input: IO<F>,
+    ) -> impl Iterator<Item = Result<(Phase, ExecutionResult<F>, Witness<F>, IO<F>), Error>> + 'a
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let inputs = std::iter::once((Phase::Init, init_input))
+            .chain(step_inputs.map(|input| (Phase::Step, input)))
+            .chain(std::iter::once((Phase::Final, final_input)));
+
+        chain_io_steps(
+            start_state,
+            0,
+            inputs,
+            move |state, (phase, input), step_num| {
+                let (exe_res, witness, next) =
+                    execute_io_step::<F, AF>(self.get(phase), state, &input, step_num)?;
+                Ok((phase, exe_res, witness, next))
+            },
+        )
+    }
+}
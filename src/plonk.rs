@@ -0,0 +1,281 @@
+//! Lowering of the arithmetic gates to the standard Plonk gate
+//! `q_a * a + q_b * b + q_c * c + q_m * a * b + q_const = 0`, as an
+//! alternative to the R1CS for halo2-based schemes.
+//!
+//! A gate with several products or more than three linear terms is split
+//! over several rows. Products and partial sums are carried by internal
+//! wires, each defined in `c` by the row creating it, with `q_c = -1`.
+//! Cells of the same wire are meant to be copy constrained.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use ff::PrimeField;
+use ivc_program::{program::WitnessID, witness::Witness};
+
+use crate::{gate::AcirArithGate, program::CircuitStructure, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wire {
+    /// A witness in the R1CS numbering
+    Witness(WitnessID),
+    Internal(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct PlonkRow<F> {
+    pub wires: [Option<Wire>; 3],
+    pub q_a: F,
+    pub q_b: F,
+    pub q_c: F,
+    pub q_m: F,
+    pub q_const: F,
+}
+
+impl<F: PrimeField> PlonkRow<F> {
+    fn empty() -> Self {
+        Self {
+            wires: [None; 3],
+            q_a: F::ZERO,
+            q_b: F::ZERO,
+            q_c: F::ZERO,
+            q_m: F::ZERO,
+            q_const: F::ZERO,
+        }
+    }
+
+    pub fn evaluate(&self, [a, b, c]: [F; 3]) -> F {
+        self.q_a * a + self.q_b * b + self.q_c * c + self.q_m * a * b + self.q_const
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlonkLayout<F> {
+    pub rows: Vec<PlonkRow<F>>,
+    pub num_internal: usize,
+
+    /// The public inputs then the public outputs, each with a cell
+    pub instance: Vec<WitnessID>,
+}
+
+impl<F: PrimeField> PlonkLayout<F> {
+    pub fn from_gates<'a>(gates: impl IntoIterator<Item = &'a AcirArithGate<F>>) -> Self {
+        let mut layout = Self {
+            rows: vec![],
+            num_internal: 0,
+            instance: vec![],
+        };
+
+        for gate in gates {
+            layout.push_gate(gate);
+        }
+
+        layout
+    }
+
+    /// Lower every gate of the structure, including memory checking and
+    /// custom lowerings
+    pub fn from_structure(structure: &CircuitStructure<F>) -> Self {
+        let mut layout = Self::from_gates(&structure.r1cs_gates());
+        let io = &structure.program;
+
+        layout.instance = io
+            .public_inputs
+            .iter()
+            .chain(io.public_outputs.iter())
+            .cloned()
+            .collect();
+
+        // Note: an IO witness unused by the gates gets an unconstrained row
+        let cells = layout.cells();
+        for id in layout.instance.clone() {
+            if !cells.contains_key(&Wire::Witness(id)) {
+                let mut row = PlonkRow::empty();
+                row.wires[0] = Some(Wire::Witness(id));
+                layout.rows.push(row);
+            }
+        }
+
+        layout
+    }
+
+    fn internal(&mut self) -> Wire {
+        self.num_internal += 1;
+        Wire::Internal(self.num_internal - 1)
+    }
+
+    fn push_gate(&mut self, gate: &AcirArithGate<F>) {
+        if gate.is_trivial() {
+            return;
+        }
+
+        let mut linear: VecDeque<(F, Wire)> = gate
+            .add_terms
+            .iter()
+            .map(|(coeff, id)| (*coeff, Wire::Witness(*id)))
+            .collect();
+
+        // every product but the last one gets its own row
+        let mut products = gate.mul_terms.clone();
+        let last_product = products.pop();
+
+        for (coeff, left, right) in products {
+            let product = self.internal();
+            self.rows.push(PlonkRow {
+                wires: [
+                    Some(Wire::Witness(left)),
+                    Some(Wire::Witness(right)),
+                    Some(product),
+                ],
+                q_m: coeff,
+                q_c: -F::ONE,
+                ..PlonkRow::empty()
+            });
+            linear.push_back((F::ONE, product));
+        }
+
+        // sum up the linear terms until they fit the last row
+        let free_cells = if last_product.is_some() { 1 } else { 3 };
+        while linear.len() > free_cells {
+            let (q_a, a) = linear.pop_front().unwrap();
+            let (q_b, b) = linear.pop_front().unwrap();
+            let sum = self.internal();
+            self.rows.push(PlonkRow {
+                wires: [Some(a), Some(b), Some(sum)],
+                q_a,
+                q_b,
+                q_c: -F::ONE,
+                ..PlonkRow::empty()
+            });
+            linear.push_back((F::ONE, sum));
+        }
+
+        let mut row = PlonkRow {
+            q_const: gate.constant_term,
+            ..PlonkRow::empty()
+        };
+
+        let first_linear = match last_product {
+            Some((coeff, left, right)) => {
+                row.wires[0] = Some(Wire::Witness(left));
+                row.wires[1] = Some(Wire::Witness(right));
+                row.q_m = coeff;
+                2
+            }
+            None => 0,
+        };
+
+        for (i, (coeff, wire)) in linear.into_iter().enumerate() {
+            let column = first_linear + i;
+            row.wires[column] = Some(wire);
+            match column {
+                0 => row.q_a = coeff,
+                1 => row.q_b = coeff,
+                _ => row.q_c = coeff,
+            }
+        }
+
+        self.rows.push(row);
+    }
+
+    /// Cells `(row, column)` of every wire
+    pub fn cells(&self) -> BTreeMap<Wire, Vec<(usize, usize)>> {
+        let mut cells: BTreeMap<Wire, Vec<(usize, usize)>> = BTreeMap::new();
+
+        for (i, row) in self.rows.iter().enumerate() {
+            for (column, wire) in row.wires.iter().enumerate() {
+                if let Some(wire) = wire {
+                    cells.entry(*wire).or_default().push((i, column));
+                }
+            }
+        }
+
+        cells
+    }
+
+    /// Values of the cells of every row, from a witness in the R1CS numbering
+    pub fn assign(&self, witness: &Witness<F>) -> Result<Vec<[F; 3]>, Error> {
+        let mut internal: Vec<F> = Vec::with_capacity(self.num_internal);
+
+        let mut values = Vec::with_capacity(self.rows.len());
+        for row in &self.rows {
+            let value = |wire: &Option<Wire>, internal: &[F]| match wire {
+                Some(Wire::Witness(id)) => witness
+                    .0
+                    .get(id)
+                    .cloned()
+                    .ok_or(Error::MissingWitness(id.0)),
+                Some(Wire::Internal(i)) => Ok(internal[*i]),
+                None => Ok(F::ZERO),
+            };
+
+            let a = value(&row.wires[0], &internal)?;
+            let b = value(&row.wires[1], &internal)?;
+
+            // internal wires are defined in order, by the row creating them
+            let c = match row.wires[2] {
+                Some(Wire::Internal(i)) if i == internal.len() => {
+                    let c = row.evaluate([a, b, F::ZERO]);
+                    internal.push(c);
+                    c
+                }
+                wire => value(&wire, &internal)?,
+            };
+
+            values.push([a, b, c]);
+        }
+
+        Ok(values)
+    }
+
+    pub fn is_satisfied(&self, values: &[[F; 3]]) -> bool {
+        self.rows
+            .iter()
+            .zip(values)
+            .all(|(row, values)| bool::from(row.evaluate(*values).is_zero()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_lower_gate() {
+        // w0 * w1 + 2 * w2 * w3 - w4 - w5 + w6 + w7 - 3
+        let gate = AcirArithGate {
+            mul_terms: vec![
+                (F::one(), WitnessID(0), WitnessID(1)),
+                (F::from(2), WitnessID(2), WitnessID(3)),
+            ],
+            add_terms: vec![
+                (-F::one(), WitnessID(4)),
+                (-F::one(), WitnessID(5)),
+                (F::one(), WitnessID(6)),
+                (F::one(), WitnessID(7)),
+            ],
+            constant_term: -F::from(3),
+        };
+
+        let layout = PlonkLayout::from_gates([&gate]);
+        assert!(layout.rows.len() > 1);
+
+        // 2 * 3 + 2 * 4 * 5 - 50 - 1 + 7 + 1 - 3 = 0
+        let witness = |values: [u64; 8]| {
+            Witness(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &v)| (WitnessID(i as u32), F::from(v)))
+                    .collect(),
+            )
+        };
+
+        let values = layout.assign(&witness([2, 3, 4, 5, 50, 1, 7, 1])).unwrap();
+        assert!(layout.is_satisfied(&values));
+
+        let values = layout.assign(&witness([2, 3, 4, 5, 50, 1, 7, 2])).unwrap();
+        assert!(!layout.is_satisfied(&values));
+    }
+}
@@ -1,25 +1,210 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use acvm::acir::{acir_field::GenericFieldElement, circuit::Circuit as ACVMCircuit};
+use acvm::acir::{
+    acir_field::GenericFieldElement,
+    circuit::{AssertionPayload, Circuit as ACVMCircuit, Opcode, OpcodeLocation, Program},
+};
 use ark_ff::PrimeField as ArkPrimeField;
 use ff::PrimeField;
 use ivc_program::{
-    program::{get_curve_name, IOProfile, IVCProgram, R1CSConstraint, Term, WitnessID, LC},
+    program::{IOProfile, IVCProgram, R1CSConstraint, WitnessID},
     witness::Witness,
     Step,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    brillig::{BrilligCall, BrilligHints},
+    curve::{check_acvm_field, Curve},
+    debug::SourceLocation,
+    dense::DenseWitness,
+    gate::AcirArithGate,
+    layout::IOLayout,
+    load::UnsupportedProgramError,
+    lowering::{check_memory_segments, LoweredOpcode, LoweringRegistry},
+    memory::{MemoryAccess, MemoryBlock},
+    r1cs::{lc_key, ConstraintCounter, R1CSEncoding, R1CSTemplate},
+    renumber::Renumbering,
+    Error,
+};
+
+/// Limits checked before the R1CS is generated, `None` for no limit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompileLimits {
+    pub max_constraints: Option<usize>,
+    pub max_witnesses: Option<usize>,
+}
+
+/// Hex SHA-256 of the constraints, the witness count and the IO profile,
+/// which identifies the circuit a witness or proof belongs to
+pub fn program_digest<F: PrimeField>(program: &IVCProgram<F>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"noir-ivc program v1");
+    hasher.update(program.num_witness.to_le_bytes());
+
+    let io = &program.io;
+    for ids in [
+        &io.public_inputs,
+        &io.private_inputs,
+        &io.public_outputs,
+        &io.private_outputs,
+    ] {
+        hasher.update((ids.len() as u32).to_le_bytes());
+        for id in ids {
+            hasher.update(id.0.to_le_bytes());
+        }
+    }
 
-use crate::{gate::AcirArithGate, Error};
+    hasher.update((program.r1cs_constraints.len() as u64).to_le_bytes());
+    let mut bytes = vec![];
+    for constraint in &program.r1cs_constraints {
+        bytes.clear();
+        for lc in [&constraint.a, &constraint.b, &constraint.c] {
+            lc_key(lc, F::ONE, &mut bytes);
+        }
+        hasher.update(&bytes);
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Witnesses of an input that differ from the IO profile, in the R1CS
+/// numbering
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputMismatch {
+    pub missing_public: Vec<WitnessID>,
+    pub unexpected_public: Vec<WitnessID>,
+    pub missing_private: Vec<WitnessID>,
+    pub unexpected_private: Vec<WitnessID>,
+    /// Parameters of the missing or unexpected witnesses, if the structure
+    /// has a layout
+    pub parameters: Vec<String>,
+}
+
+impl std::fmt::Display for InputMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = [
+            ("missing public inputs", &self.missing_public),
+            ("unexpected public inputs", &self.unexpected_public),
+            ("missing private inputs", &self.missing_private),
+            ("unexpected private inputs", &self.unexpected_private),
+        ]
+        .into_iter()
+        .filter(|(_, ids)| !ids.is_empty())
+        .map(|(what, ids)| {
+            format!(
+                "{} {:?}",
+                what,
+                ids.iter().map(|id| id.0).collect::<Vec<_>>()
+            )
+        })
+        .collect();
+
+        write!(f, "{}", parts.join(", "))?;
+        if !self.parameters.is_empty() {
+            write!(f, " (parameters {})", self.parameters.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InputMismatch {}
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct CircuitStructure<F> {
     pub gates: Vec<AcirArithGate<F>>,
 
-    // Note: num of witness and constraints in the program are unused
+    #[serde(default)]
+    pub memory: Vec<MemoryBlock<F>>,
+
+    #[serde(default)]
+    pub brillig: BrilligHints,
+
+    /// Opcodes lowered by a registered `GateLowering`
+    #[serde(default)]
+    pub custom: Vec<LoweredOpcode<F>>,
+
+    // Note: not serialized, register the lowerings again after loading
+    #[serde(skip)]
+    pub lowerings: LoweringRegistry<F>,
+
+    /// Assertion messages of the circuit, in their serialized ACIR form
+    #[serde(default)]
+    pub assert_messages: Vec<serde_json::Value>,
+
+    /// Noir source of the opcodes, by opcode index, to locate solving errors
+    /// (see `load_debug_info_from_file`)
+    #[serde(default)]
+    pub source_map: BTreeMap<usize, SourceLocation>,
+
+    /// Witnesses fixed to a constant by a single gate
+    #[serde(default)]
+    pub constants: BTreeMap<WitnessID, F>,
+
+    /// Witness indices left unused by the circuit, padded with zeros so the
+    /// witness stays contiguous
+    #[serde(default)]
+    pub padding: BTreeSet<WitnessID>,
+
+    #[serde(default)]
+    pub encoding: R1CSEncoding,
+
+    #[serde(default)]
+    pub limits: CompileLimits,
+
+    /// Curve of the scalar field `F`, `None` for another field or a
+    /// structure saved before it was recorded
+    // Note: `IVCProgram::curve` keeps the naming of `ivc_program`
+    #[serde(default)]
+    pub curve: Option<Curve>,
+
+    /// Set by `compile`
+    #[serde(default)]
+    pub r1cs: Option<R1CSTemplate<F>>,
+
+    /// `program_digest` of the compiled program, set by `compile`.
+    // Note: `IVCProgram` has no room for it, so it is recomputed from the
+    // program when checking one (see `check_program`)
+    #[serde(default)]
+    pub digest: Option<String>,
+
+    /// ACIR witnesses to R1CS witnesses, the identity if not set.
+    /// Gates, memory, hints and constants use the ACIR numbering, while the
+    /// IO, padding, R1CS and produced witnesses use the R1CS one
+    #[serde(default)]
+    pub renumbering: Option<Renumbering>,
+
+    /// IO in the order of the Noir signature, set by `set_abi`
+    #[serde(default)]
+    pub layout: Option<IOLayout>,
+
+    // Note: `num_witness` counts the product witnesses once compiled, the
+    // constraints are only kept in `r1cs` to not store them twice
     pub program: IVCProgram<F>,
 }
 
+// Note: a summary, the gates and hints are too large to print
+impl<F> std::fmt::Debug for CircuitStructure<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitStructure")
+            .field("gates", &self.gates.len())
+            .field("memory", &self.memory.len())
+            .field("num_witness", &self.program.num_witness)
+            .field("public_inputs", &self.program.public_inputs)
+            .field("public_outputs", &self.program.public_outputs)
+            .field("digest", &self.digest)
+            .finish()
+    }
+}
+
 pub(crate) fn extract_io<AF: ArkPrimeField>(
     acvm_circuit: &ACVMCircuit<GenericFieldElement<AF>>,
     private_outputs: &BTreeSet<WitnessID>,
@@ -68,35 +253,365 @@ pub(crate) fn extract_io<AF: ArkPrimeField>(
     }
 }
 
-impl<F: PrimeField, AF: ArkPrimeField> From<ACVMCircuit<GenericFieldElement<AF>>>
+impl<F: PrimeField, AF: ArkPrimeField> TryFrom<ACVMCircuit<GenericFieldElement<AF>>>
     for CircuitStructure<F>
 {
-    fn from(acvm_circuit: ACVMCircuit<GenericFieldElement<AF>>) -> Self {
-        let gates = acvm_circuit
-            .opcodes
-            .iter()
-            .cloned()
-            .map(|x| x.into())
-            .collect();
+    type Error = UnsupportedProgramError;
+
+    fn try_from(acvm_circuit: ACVMCircuit<GenericFieldElement<AF>>) -> Result<Self, Self::Error> {
+        Self::from_circuit(acvm_circuit, Default::default())
+    }
+}
+
+impl<F: PrimeField, AF: ArkPrimeField> TryFrom<Program<GenericFieldElement<AF>>>
+    for CircuitStructure<F>
+{
+    type Error = UnsupportedProgramError;
+
+    fn try_from(program: Program<GenericFieldElement<AF>>) -> Result<Self, Self::Error> {
+        Self::from_program(program, Default::default())
+    }
+}
+
+impl<F: PrimeField> CircuitStructure<F> {
+    /// Convert a circuit, lowering opcodes unknown to this crate with `lowerings`
+    pub fn from_circuit<AF: ArkPrimeField>(
+        acvm_circuit: ACVMCircuit<GenericFieldElement<AF>>,
+        lowerings: LoweringRegistry<F>,
+    ) -> Result<Self, UnsupportedProgramError> {
+        let mut gates: Vec<AcirArithGate<F>> = vec![];
+        let mut memory: Vec<MemoryBlock<F>> = vec![];
+        let mut brillig = BrilligHints::default();
+        let mut unknown = vec![];
+
+        for (position, opcode) in acvm_circuit.opcodes.iter().enumerate() {
+            match opcode {
+                Opcode::AssertZero(_) => gates.push((position, opcode.clone()).try_into()?),
+                Opcode::MemoryInit {
+                    block_id,
+                    init,
+                    block_type,
+                } => memory.push(MemoryBlock::new(
+                    block_id.0,
+                    block_type.clone(),
+                    position,
+                    init.iter().map(|x| x.0.into()).collect(),
+                )),
+                Opcode::MemoryOp { block_id, op, .. } => {
+                    let block = memory
+                        .iter_mut()
+                        .find(|block| block.block_id == block_id.0)
+                        .ok_or_else(|| {
+                            UnsupportedProgramError::UnsupportedMemoryOp(format!(
+                                "block {} is not initialized",
+                                block_id.0
+                            ))
+                        })?;
+                    let access = MemoryAccess::from_mem_op(position, op).map_err(|e| {
+                        UnsupportedProgramError::UnconvertibleOpcode(position, Box::new(e))
+                    })?;
+                    block.accesses.push(access);
+                }
+                Opcode::BrilligCall { .. } => {
+                    let call = BrilligCall::from_opcode(position, opcode).map_err(|e| {
+                        UnsupportedProgramError::UnconvertibleOpcode(position, Box::new(e))
+                    })?;
+                    brillig.calls.push(call);
+                }
+                _ => unknown.push(position),
+            }
+        }
 
         let io = extract_io(&acvm_circuit, &Default::default());
 
-        let curve = get_curve_name::<F>();
+        // auxiliary witnesses go after all circuit witnesses
+        let mut next_witness = {
+            let max_witness = gates
+                .iter()
+                .flat_map(|gate| gate.witnesses())
+                .chain(memory.iter().flat_map(|block| block.witnesses()))
+                .chain(brillig.outputs())
+                .chain(io.public_inputs.iter().cloned())
+                .chain(io.private_inputs.iter().cloned())
+                .chain(io.public_outputs.iter().cloned())
+                .max()
+                .map_or(0, |id| id.0 + 1);
+
+            // witnesses only defined by unknown opcodes are not visible here
+            if unknown.is_empty() {
+                max_witness
+            } else {
+                max_witness.max(acvm_circuit.current_witness_index + 1)
+            }
+        };
+
+        for block in &mut memory {
+            block.allocate(&mut next_witness).map_err(|e| {
+                UnsupportedProgramError::UnconvertibleOpcode(block.position, Box::new(e))
+            })?;
+        }
+
+        let mut custom = vec![];
+        for position in unknown {
+            let opcode = &acvm_circuit.opcodes[position];
+            let value = serde_json::to_value(opcode)
+                .map_err(|e| UnsupportedProgramError::Unserializable(e.to_string()))?;
+            let lowered = lowerings
+                .lower(position, value, &mut next_witness)
+                .ok_or_else(|| {
+                    UnsupportedProgramError::NonAssertZeroOpcode(position, format!("{:?}", opcode))
+                })?;
+            custom.push(lowered);
+        }
+        check_memory_segments(&memory, &custom)?;
+
+        let assert_messages = acvm_circuit
+            .assert_messages
+            .iter()
+            .map(|message| {
+                serde_json::to_value(message)
+                    .map_err(|e| UnsupportedProgramError::Unserializable(e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut structure = Self {
+            memory,
+            brillig,
+            custom,
+            lowerings,
+            assert_messages,
+            ..Self::from_gates(gates, io)
+        };
+
+        structure.padding = structure.unreferenced_witnesses();
+        structure.reset_r1cs();
+
+        Ok(structure)
+    }
+
+    /// A structure made of arithmetic gates only, e.g. imported from another
+    /// toolchain
+    pub fn from_gates(gates: Vec<AcirArithGate<F>>, io: IOProfile) -> Self {
+        let constants = gates
+            .iter()
+            .filter_map(|gate| gate.constant_assignment())
+            .collect();
 
         let program = IVCProgram {
             io,
             num_witness: 0,
             r1cs_constraints: Default::default(),
-            curve,
+            curve: Curve::program_name::<F>(),
             version: ivc_program::program::VERSION_0_1.to_string(),
         };
 
-        Self { gates, program }
+        let mut structure = Self {
+            gates,
+            memory: vec![],
+            brillig: Default::default(),
+            custom: vec![],
+            lowerings: Default::default(),
+            assert_messages: vec![],
+            source_map: Default::default(),
+            constants,
+            padding: Default::default(),
+            encoding: Default::default(),
+            limits: Default::default(),
+            curve: Curve::of::<F>(),
+            r1cs: None,
+            digest: None,
+            renumbering: None,
+            layout: None,
+            program,
+        };
+
+        structure.padding = structure.unreferenced_witnesses();
+        structure.reset_r1cs();
+
+        structure
     }
-}
 
-impl<F: PrimeField> CircuitStructure<F> {
-    pub fn make_trivial_witness(&self) -> Witness<F> {
+    pub fn from_program<AF: ArkPrimeField>(
+        program: Program<GenericFieldElement<AF>>,
+        lowerings: LoweringRegistry<F>,
+    ) -> Result<Self, UnsupportedProgramError> {
+        let circuit = program
+            .functions
+            .first()
+            .ok_or(UnsupportedProgramError::NoFunctions)?;
+        let mut structure = Self::from_circuit(circuit.clone(), lowerings)?;
+        structure
+            .brillig
+            .set_functions(&program.unconstrained_functions);
+        Ok(structure)
+    }
+
+    /// All gates to be lowered into R1CS, including memory checking
+    pub fn constraint_gates(&self) -> impl Iterator<Item = &AcirArithGate<F>> {
+        self.gates
+            .iter()
+            .chain(
+                self.memory
+                    .iter()
+                    .flat_map(|block| block.constraints.iter()),
+            )
+            .chain(self.custom.iter().flat_map(|lowered| lowered.gates.iter()))
+    }
+
+    /// Rebuild the ACIR opcodes in their original order for solving, split
+    /// into segments each ending at an opcode lowered by a registered lowering,
+    /// given by its index in `custom`
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn acir_segments<AF: ArkPrimeField>(
+        &self,
+    ) -> Result<Vec<(Vec<Opcode<GenericFieldElement<AF>>>, Option<usize>)>, Error> {
+        let mut placed: BTreeMap<usize, Opcode<GenericFieldElement<AF>>> = BTreeMap::new();
+
+        for block in &self.memory {
+            let (position, opcode) = block.init_opcode();
+            placed.insert(position, opcode);
+            for access in block.access_opcodes() {
+                let (position, opcode) = access?;
+                placed.insert(position, opcode);
+            }
+        }
+
+        for call in &self.brillig.calls {
+            let (position, opcode) = call.opcode()?;
+            placed.insert(position, opcode);
+        }
+
+        let mut gates = self
+            .gates
+            .iter()
+            .cloned()
+            .map(Opcode::<GenericFieldElement<AF>>::try_from)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter();
+
+        let custom: BTreeMap<usize, usize> = self
+            .custom
+            .iter()
+            .enumerate()
+            .map(|(index, lowered)| (lowered.position, index))
+            .collect();
+
+        let mut segments = vec![(vec![], None)];
+
+        for position in 0..self.gates.len() + placed.len() + custom.len() {
+            let segment = segments.last_mut().expect("segments are never empty");
+
+            if let Some(&index) = custom.get(&position) {
+                segment.1 = Some(index);
+                segments.push((vec![], None));
+            } else {
+                let opcode = placed
+                    .remove(&position)
+                    .unwrap_or_else(|| gates.next().expect("inconsistent opcode positions"));
+                segment.0.push(opcode);
+            }
+        }
+
+        Ok(segments)
+    }
+
+    /// Assertion messages of the opcodes `start..start + len`, relative to `start`
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn assert_messages<AF: ArkPrimeField>(
+        &self,
+        start: usize,
+        len: usize,
+    ) -> Result<Vec<(OpcodeLocation, AssertionPayload<GenericFieldElement<AF>>)>, Error> {
+        let mut messages = vec![];
+
+        for message in &self.assert_messages {
+            let (location, payload): (OpcodeLocation, AssertionPayload<GenericFieldElement<AF>>) =
+                serde_json::from_value(message.clone())
+                    .map_err(|e| Error::ACVMSolveError(e.to_string()))?;
+
+            let location = match location {
+                OpcodeLocation::Acir(index) if (start..start + len).contains(&index) => {
+                    OpcodeLocation::Acir(index - start)
+                }
+                OpcodeLocation::Brillig {
+                    acir_index,
+                    brillig_index,
+                } if (start..start + len).contains(&acir_index) => OpcodeLocation::Brillig {
+                    acir_index: acir_index - start,
+                    brillig_index,
+                },
+                _ => continue,
+            };
+
+            messages.push((location, payload));
+        }
+
+        Ok(messages)
+    }
+
+    /// The R1CS witness of an ACIR witness, `None` if it was pruned
+    pub fn r1cs_witness(&self, id: WitnessID) -> Option<WitnessID> {
+        match &self.renumbering {
+            Some(renumbering) => renumbering.get(id),
+            None => Some(id),
+        }
+    }
+
+    /// Constraint gates in the R1CS numbering
+    pub fn r1cs_gates(&self) -> Vec<AcirArithGate<F>> {
+        self.constraint_gates()
+            .map(|gate| match &self.renumbering {
+                Some(renumbering) => gate.map_witnesses(|id| {
+                    renumbering
+                        .get(id)
+                        .expect("constrained witnesses are never pruned")
+                }),
+                None => gate.clone(),
+            })
+            .collect()
+    }
+
+    /// Map a witness in the R1CS numbering back to the ACIR one, for solving
+    pub(crate) fn to_acir_witness(&self, witness: &Witness<F>) -> BTreeMap<WitnessID, F> {
+        match &self.renumbering {
+            Some(renumbering) => {
+                let inverse = renumbering.inverse();
+                witness
+                    .iter()
+                    .filter_map(|(id, value)| Some((*inverse.get(id)?, *value)))
+                    .collect()
+            }
+            None => witness.0.clone(),
+        }
+    }
+
+    /// The witnesses `ids` (in the R1CS numbering) of a witness solved in
+    /// the ACIR numbering
+    pub(crate) fn solved_subset(
+        &self,
+        solved_witness: &Witness<F>,
+        ids: &BTreeSet<WitnessID>,
+    ) -> Result<Witness<F>, Error> {
+        let subset: BTreeMap<WitnessID, F> = match &self.renumbering {
+            Some(renumbering) => renumbering
+                .0
+                .iter()
+                .filter(|(_, new)| ids.contains(new))
+                .filter_map(|(old, new)| Some((*new, *solved_witness.get(old)?)))
+                .collect(),
+            None => ids
+                .iter()
+                .filter_map(|id| Some((*id, *solved_witness.get(id)?)))
+                .collect(),
+        };
+
+        match ids.iter().find(|id| !subset.contains_key(id)) {
+            Some(id) => Err(Error::MissingWitness(id.0)),
+            None => Ok(Witness(subset)),
+        }
+    }
+
+    fn io_witnesses(&self) -> BTreeSet<WitnessID> {
         let mut witness_set = BTreeSet::new();
 
         witness_set.extend(self.program.public_inputs.iter().cloned());
@@ -104,125 +619,706 @@ impl<F: PrimeField> CircuitStructure<F> {
         witness_set.extend(self.program.public_outputs.iter().cloned());
         witness_set.extend(self.program.private_outputs.iter().cloned());
 
-        for gate in &self.gates {
-            for (_, left, right) in &gate.mul_terms {
-                witness_set.insert(*left);
-                witness_set.insert(*right);
-            }
+        witness_set
+    }
 
-            for (_, id) in &gate.add_terms {
-                witness_set.insert(*id);
-            }
+    /// Witnesses used by the IO, the constraints or as hints, in the R1CS
+    /// numbering
+    pub fn referenced_witnesses(&self) -> BTreeSet<WitnessID> {
+        let mut witness_set = self.io_witnesses();
+
+        witness_set.extend(
+            self.brillig
+                .outputs()
+                .filter_map(|id| self.r1cs_witness(id)),
+        );
+
+        for gate in self.r1cs_gates() {
+            witness_set.extend(gate.witnesses());
+        }
+
+        witness_set
+    }
+
+    /// Drop the witnesses used by no constraint and no IO set (including
+    /// padding and unused hints), and number the remaining ones contiguously.
+    /// Returns the renumbering of the R1CS witnesses, to migrate existing
+    /// input files with `Renumbering::apply`
+    pub fn prune(&mut self) -> Renumbering {
+        let mut live = self.io_witnesses();
+
+        for gate in self.r1cs_gates() {
+            live.extend(gate.witnesses());
         }
 
-        assert_eq!(
-            witness_set.iter().max().unwrap().0,
-            witness_set.len() as u32 - 1
+        let remap = Renumbering(
+            live.into_iter()
+                .enumerate()
+                .map(|(new, old)| (old, WitnessID(new as u32)))
+                .collect(),
         );
 
-        Witness(witness_set.into_iter().map(|id| (id, F::ZERO)).collect())
+        self.renumber(&remap);
+
+        remap
     }
 
-    pub fn is_valid_input(&self, public_inputs: &Witness<F>, private_inputs: &Witness<F>) -> bool {
-        let public_inputs_set_1: BTreeSet<WitnessID> = public_inputs.keys().cloned().collect();
-        let private_inputs_set_1: BTreeSet<WitnessID> = private_inputs.keys().cloned().collect();
+    /// Renumber the witnesses so that `public_inputs ∥ public_outputs` come
+    /// first, as several folding backends expect. The other witnesses keep
+    /// their relative order. Returns the renumbering, as `prune` does
+    pub fn renumber_io_first(&mut self) -> Renumbering {
+        let public: Vec<WitnessID> = self
+            .program
+            .public_inputs
+            .iter()
+            .chain(self.program.public_outputs.iter())
+            .cloned()
+            .collect();
+
+        let rest = self.circuit_witnesses().filter(|id| !public.contains(id));
 
-        let public_inputs_set_2: BTreeSet<WitnessID> =
-            self.program.public_inputs.iter().cloned().collect();
-        let private_inputs_set_2: BTreeSet<WitnessID> =
-            self.program.private_inputs.iter().cloned().collect();
+        let remap = Renumbering(
+            public
+                .iter()
+                .cloned()
+                .chain(rest)
+                .enumerate()
+                .map(|(new, old)| (old, WitnessID(new as u32)))
+                .collect(),
+        );
 
-        public_inputs_set_1 == public_inputs_set_2 || private_inputs_set_1 == private_inputs_set_2
+        self.renumber(&remap);
+
+        remap
     }
 
-    pub fn compile(&self) -> Result<IVCProgram<F>, Error> {
-        let solved_witness = self.make_trivial_witness();
-        let step = self.make_step(&solved_witness)?;
-        Ok(step.program)
+    /// Renumber the R1CS witnesses, dropping the ones `remap` does not map
+    fn renumber(&mut self, remap: &Renumbering) {
+        self.renumbering = Some(match &self.renumbering {
+            Some(renumbering) => renumbering.then(remap),
+            None => remap.clone(),
+        });
+
+        self.program.io = IOProfile {
+            public_inputs: remap.apply_to_set(&self.program.public_inputs),
+            private_inputs: remap.apply_to_set(&self.program.private_inputs),
+            public_outputs: remap.apply_to_set(&self.program.public_outputs),
+            private_outputs: remap.apply_to_set(&self.program.private_outputs),
+        };
+
+        self.layout = self
+            .layout
+            .as_ref()
+            .map(|layout| layout.map_witnesses(|id| remap.get(id)));
+
+        self.padding = self.unreferenced_witnesses();
+        self.reset_r1cs();
     }
 
-    pub fn make_step(&self, solved_witness: &Witness<F>) -> Result<Step<F>, Error> {
-        let mut witness: BTreeMap<_, _> = solved_witness
+    /// Order the IO as in the Noir signature, from the `abi` of the compiled
+    /// program (see `load_abi_from_file`)
+    pub fn set_abi(&mut self, abi: &serde_json::Value) -> Result<(), Error> {
+        let inverse = self.renumbering.as_ref().map(Renumbering::inverse);
+        let to_acir = |ids: &BTreeSet<WitnessID>| -> BTreeSet<WitnessID> {
+            match &inverse {
+                Some(inverse) => ids
+                    .iter()
+                    .filter_map(|id| inverse.get(id).cloned())
+                    .collect(),
+                None => ids.clone(),
+            }
+        };
+
+        let private_outputs = to_acir(&self.program.private_outputs);
+        let mut return_values = to_acir(&self.program.public_outputs);
+        return_values.extend(private_outputs.iter().cloned());
+
+        let layout = IOLayout::from_abi(abi, &return_values, &private_outputs)?
+            .map_witnesses(|id| self.r1cs_witness(id));
+
+        let check = |name: &str, ordered: &[WitnessID], set: &BTreeSet<WitnessID>| {
+            if ordered.len() == set.len() && ordered.iter().all(|id| set.contains(id)) {
+                Ok(())
+            } else {
+                Err(Error::AbiMismatch(format!("{} differ", name)))
+            }
+        };
+
+        check(
+            "public inputs",
+            &layout.public_inputs,
+            &self.program.public_inputs,
+        )?;
+        check(
+            "private inputs",
+            &layout.private_inputs,
+            &self.program.private_inputs,
+        )?;
+        check(
+            "public outputs",
+            &layout.public_outputs,
+            &self.program.public_outputs,
+        )?;
+        check(
+            "private outputs",
+            &layout.private_outputs,
+            &self.program.private_outputs,
+        )?;
+
+        self.layout = Some(layout);
+
+        Ok(())
+    }
+
+    fn unreferenced_witnesses(&self) -> BTreeSet<WitnessID> {
+        let referenced = self.referenced_witnesses();
+        let len = referenced.iter().max().map_or(0, |id| id.0 + 1);
+        (0..len)
+            .map(WitnessID)
+            .filter(|id| !referenced.contains(id))
+            .collect()
+    }
+
+    /// Substitute constant witnesses into the gates until no new constant is
+    /// found. Gates reduced to `0 = 0` are kept in place to preserve opcode
+    /// positions, but produce no constraint. Returns the number of such gates
+    pub fn fold_constants(&mut self) -> usize {
+        loop {
+            let mut found = false;
+
+            for gate in &mut self.gates {
+                // keep the gates fixing a constant, so the witness stays constrained
+                if let Some((id, _)) = gate.constant_assignment() {
+                    if self.constants.contains_key(&id) {
+                        continue;
+                    }
+                }
+
+                *gate = gate.substitute(&self.constants);
+
+                if let Some((id, value)) = gate.constant_assignment() {
+                    found |= self.constants.insert(id, value).is_none();
+                }
+            }
+
+            if !found {
+                break;
+            }
+        }
+
+        self.padding = self.unreferenced_witnesses();
+        self.reset_r1cs();
+
+        self.gates.iter().filter(|gate| gate.is_trivial()).count()
+    }
+
+    /// Opcode positions of the gates, which fill the positions not taken by
+    /// memory, hints and lowered opcodes
+    fn gate_positions(&self) -> Vec<usize> {
+        let taken: BTreeSet<usize> = self
+            .memory
             .iter()
-            .map(|(&k, &v)| (WitnessID(k.0), v))
+            .flat_map(|block| {
+                std::iter::once(block.position)
+                    .chain(block.accesses.iter().map(|access| access.position))
+            })
+            .chain(self.brillig.calls.iter().map(|call| call.position))
+            .chain(self.custom.iter().map(|lowered| lowered.position))
             .collect();
 
-        let mut num_witness = witness.len() as u32;
-        let mut r1cs_constraints = Vec::new();
-
-        self.gates.iter().for_each(|gate| {
-            let mut big_lc_a = LC::default();
-
-            for (coeff, left, right) in &gate.mul_terms {
-                let left_id = WitnessID(left.0);
-                let right_id = WitnessID(right.0);
-
-                // todo: return error
-                let left_val = *witness.get(&left_id).expect("left not found");
-                let right_val = *witness.get(&right_id).expect("right not found");
-
-                let prod_val = left_val * right_val;
-                let prod_id = num_witness.into();
-                num_witness += 1;
-
-                witness.insert(prod_id, prod_val);
-
-                {
-                    let a = LC(vec![Term::LC {
-                        coefficient: left_val,
-                        var_id: left_id,
-                    }]);
-                    let b = LC(vec![Term::LC {
-                        coefficient: right_val,
-                        var_id: right_id,
-                    }]);
-                    let c = LC(vec![Term::LC {
-                        coefficient: prod_val,
-                        var_id: prod_id,
-                    }]);
-
-                    let constraint = R1CSConstraint { a, b, c };
-                    r1cs_constraints.push(constraint);
-                }
+        (0..)
+            .filter(|position| !taken.contains(position))
+            .take(self.gates.len())
+            .collect()
+    }
 
-                big_lc_a.0.push(Term::LC {
-                    coefficient: *coeff,
-                    var_id: prod_id,
-                });
+    /// Add the source location of the failing opcode to a solving error
+    pub(crate) fn locate(&self, error: Error) -> Error {
+        match error {
+            Error::AssertionFailed {
+                message,
+                opcode_index: Some(opcode_index),
+                location: None,
+            } => Error::AssertionFailed {
+                message,
+                opcode_index: Some(opcode_index),
+                location: self.source_map.get(&opcode_index).cloned(),
+            },
+            Error::SolveStalled {
+                opcode_index,
+                missing_witnesses,
+                location: None,
+            } => Error::SolveStalled {
+                opcode_index,
+                missing_witnesses,
+                location: self.source_map.get(&opcode_index).cloned(),
+            },
+            error => error,
+        }
+    }
+
+    /// The assertion message of the opcode at `position`, if any
+    fn assert_message_at(&self, position: usize) -> Option<String> {
+        let location = serde_json::to_value(OpcodeLocation::Acir(position)).ok()?;
+        self.assert_messages.iter().find_map(|message| {
+            let (at, payload) = message.as_array()?.split_first()?;
+            if *at != location {
+                return None;
             }
+            let payload = payload.first()?;
+            Some(match payload.get("StaticString").and_then(|x| x.as_str()) {
+                Some(message) => message.to_string(),
+                None => payload.to_string(),
+            })
+        })
+    }
 
-            for (coeff, id) in &gate.add_terms {
-                let id = WitnessID(id.0);
+    /// Fail on a gate that no witness satisfies: a nonzero constant, either
+    /// as is or once the constant witnesses are substituted (e.g. two gates
+    /// fixing a witness to different values)
+    pub fn check_satisfiable(&self) -> Result<(), Error> {
+        for (gate_index, gate) in self.gates.iter().enumerate() {
+            let folded = gate.substitute(&self.constants);
+            if !folded.mul_terms.is_empty()
+                || !folded.add_terms.is_empty()
+                || bool::from(folded.constant_term.is_zero())
+            {
+                continue;
+            }
+
+            let opcode_index = self.gate_positions()[gate_index];
+            return Err(Error::UnsatisfiableGate {
+                gate_index,
+                opcode_index,
+                message: self.assert_message_at(opcode_index),
+            });
+        }
 
-                big_lc_a.0.push(Term::LC {
-                    coefficient: *coeff,
-                    var_id: id,
-                });
+        Ok(())
+    }
+
+    /// Bake public inputs that are the same at every step (in the R1CS
+    /// numbering, e.g. a verification key hash) into the gates and remove
+    /// them from the IO. Public outputs that become constant, e.g. the fixed
+    /// inputs returned as is, are removed as well so steps still chain.
+    /// Execution still assigns the fixed witnesses, for the hints. Returns the
+    /// number of trivial gates, as `fold_constants`
+    pub fn specialize(&mut self, fixed: &BTreeMap<WitnessID, F>) -> Result<usize, Error> {
+        if !fixed
+            .keys()
+            .all(|id| self.program.public_inputs.contains(id))
+        {
+            return Err(Error::InvalidInput);
+        }
+
+        let inverse = self.renumbering.as_ref().map(|r| r.inverse());
+        let to_acir = |id: &WitnessID| match &inverse {
+            Some(inverse) => inverse.get(id).cloned().ok_or(Error::InvalidInput),
+            None => Ok(*id),
+        };
+        let fixed_acir = fixed
+            .iter()
+            .map(|(id, value)| Ok((to_acir(id)?, *value)))
+            .collect::<Result<BTreeMap<WitnessID, F>, Error>>()?;
+
+        // memory checking and custom gates are not folded, substitute here
+        for gate in self
+            .memory
+            .iter_mut()
+            .flat_map(|block| block.constraints.iter_mut())
+            .chain(
+                self.custom
+                    .iter_mut()
+                    .flat_map(|lowered| lowered.gates.iter_mut()),
+            )
+        {
+            *gate = gate.substitute(&fixed_acir);
+        }
+
+        let before: BTreeSet<WitnessID> = self.constants.keys().cloned().collect();
+        self.constants.extend(fixed_acir);
+        let num_trivial = self.fold_constants();
+
+        let mut removed: BTreeSet<WitnessID> = fixed.keys().cloned().collect();
+        for id in self.program.public_outputs.iter() {
+            let acir = to_acir(id)?;
+            if self.constants.contains_key(&acir) && !before.contains(&acir) {
+                removed.insert(*id);
             }
+        }
 
-            big_lc_a.0.push(Term::Const(gate.constant_term));
+        let io = &mut self.program.io;
+        io.public_inputs.retain(|id| !removed.contains(id));
+        io.public_outputs.retain(|id| !removed.contains(id));
 
-            {
-                let a = big_lc_a;
-                let b = LC(vec![Term::Const(F::ONE)]);
-                let c = Default::default();
-                let constraint = R1CSConstraint { a, b, c };
+        if let Some(layout) = &mut self.layout {
+            layout.public_inputs.retain(|id| !removed.contains(id));
+            layout.public_outputs.retain(|id| !removed.contains(id));
+        }
 
-                r1cs_constraints.push(constraint);
+        self.padding = self.unreferenced_witnesses();
+        self.reset_r1cs();
+
+        Ok(num_trivial)
+    }
+
+    /// Number of witnesses before any product witness, including padding
+    pub fn num_circuit_witness(&self) -> Result<u32, Error> {
+        match self.referenced_witnesses().iter().max() {
+            Some(id) => {
+                id.0.checked_add(1)
+                    .ok_or(Error::TooManyWitnesses(id.0 as u64 + 1))
             }
-        });
+            None => Ok(0),
+        }
+    }
+
+    /// The witnesses before any product witness, in order
+    fn circuit_witnesses(&self) -> impl Iterator<Item = WitnessID> {
+        let max = self.referenced_witnesses().iter().max().map(|id| id.0);
+        max.into_iter().flat_map(|max| 0..=max).map(WitnessID)
+    }
+
+    /// Assign zero (or the constant value) to every index up to the largest
+    /// referenced witness, so gaps in the numbering are padded
+    pub fn make_trivial_witness(&self) -> Witness<F> {
+        let constants: BTreeMap<_, _> = self
+            .constants
+            .iter()
+            .filter_map(|(id, value)| Some((self.r1cs_witness(*id)?, *value)))
+            .collect();
+
+        Witness(
+            self.circuit_witnesses()
+                .map(|id| (id, constants.get(&id).cloned().unwrap_or(F::ZERO)))
+                .collect(),
+        )
+    }
+
+    /// Check the inputs assign exactly the public and private inputs of the
+    /// IO profile, listing the witnesses that differ
+    pub fn validate_input(
+        &self,
+        public_inputs: &Witness<F>,
+        private_inputs: &Witness<F>,
+    ) -> Result<(), InputMismatch> {
+        let diff = |given: &Witness<F>, expected: &BTreeSet<WitnessID>| {
+            let missing: Vec<WitnessID> = expected
+                .iter()
+                .filter(|id| !given.contains_key(id))
+                .cloned()
+                .collect();
+            let unexpected: Vec<WitnessID> = given
+                .keys()
+                .filter(|id| !expected.contains(id))
+                .cloned()
+                .collect();
+            (missing, unexpected)
+        };
+
+        let (missing_public, unexpected_public) = diff(public_inputs, &self.program.public_inputs);
+        let (missing_private, unexpected_private) =
+            diff(private_inputs, &self.program.private_inputs);
+
+        let mut mismatch = InputMismatch {
+            missing_public,
+            unexpected_public,
+            missing_private,
+            unexpected_private,
+            parameters: vec![],
+        };
+        if mismatch == InputMismatch::default() {
+            return Ok(());
+        }
+
+        if let Some(layout) = &self.layout {
+            let differing: BTreeSet<WitnessID> = mismatch
+                .missing_public
+                .iter()
+                .chain(&mismatch.unexpected_public)
+                .chain(&mismatch.missing_private)
+                .chain(&mismatch.unexpected_private)
+                .cloned()
+                .collect();
+            mismatch.parameters = layout
+                .parameters
+                .iter()
+                .filter(|(_, ids)| ids.iter().any(|id| differing.contains(id)))
+                .map(|(name, _)| name.clone())
+                .collect();
+        }
+
+        Err(mismatch)
+    }
+
+    /// Whether the inputs assign exactly the public and the private inputs,
+    /// see `validate_input` for the differing witnesses
+    pub fn is_valid_input(&self, public_inputs: &Witness<F>, private_inputs: &Witness<F>) -> bool {
+        self.is_valid_public_input(public_inputs) && self.is_valid_private_input(private_inputs)
+    }
+
+    pub fn is_valid_public_input(&self, public_inputs: &Witness<F>) -> bool {
+        public_inputs.keys().eq(self.program.public_inputs.iter())
+    }
+
+    pub fn is_valid_private_input(&self, private_inputs: &Witness<F>) -> bool {
+        private_inputs.keys().eq(self.program.private_inputs.iter())
+    }
+
+    pub fn with_encoding(mut self, encoding: R1CSEncoding) -> Self {
+        self.encoding = encoding;
+        self.reset_r1cs();
+        self
+    }
+
+    pub fn with_limits(mut self, limits: CompileLimits) -> Self {
+        self.limits = limits;
+        self
+    }
 
-        let ivc_program = IVCProgram {
+    /// Check the limits, counting the constraints and witnesses gate by gate
+    /// so an oversized circuit is rejected before its R1CS is allocated
+    pub fn check_limits(&self) -> Result<(), Error> {
+        let limits = self.limits;
+        if limits == CompileLimits::default() {
+            return Ok(());
+        }
+
+        let check = |what, count: usize, limit: Option<usize>| match limit {
+            Some(limit) if count > limit => Err(Error::LimitExceeded { what, count, limit }),
+            _ => Ok(()),
+        };
+
+        if let Some(template) = &self.r1cs {
+            check(
+                "constraints",
+                template.constraints.len(),
+                limits.max_constraints,
+            )?;
+            return check(
+                "witnesses",
+                template.num_witness()? as usize,
+                limits.max_witnesses,
+            );
+        }
+
+        let num_circuit_witness = self.num_circuit_witness()? as usize;
+        check("witnesses", num_circuit_witness, limits.max_witnesses)?;
+
+        // Note: the count does not depend on the numbering, skip renumbering
+        let mut counter = ConstraintCounter::new(self.encoding);
+        let mut num_constraints = 0;
+        for gate in self.constraint_gates() {
+            num_constraints += counter.add(gate)?;
+            check("constraints", num_constraints, limits.max_constraints)?;
+            check(
+                "witnesses",
+                num_circuit_witness + counter.num_products(),
+                limits.max_witnesses,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The compiled R1CS template, or a freshly generated one if `compile`
+    /// has not been called. Fails if the R1CS cannot be generated
+    pub fn try_r1cs_template(&self) -> Result<Cow<'_, R1CSTemplate<F>>, Error> {
+        Ok(match &self.r1cs {
+            Some(template) => Cow::Borrowed(template),
+            None => Cow::Owned(self.generate_r1cs()?),
+        })
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn generate_r1cs(&self) -> Result<R1CSTemplate<F>, Error> {
+        R1CSTemplate::generate(
+            &self.r1cs_gates(),
+            self.num_circuit_witness()?,
+            self.encoding,
+        )
+    }
+
+    #[cfg(feature = "parallel")]
+    fn generate_r1cs(&self) -> Result<R1CSTemplate<F>, Error> {
+        R1CSTemplate::generate_parallel(
+            &self.r1cs_gates(),
+            self.num_circuit_witness()?,
+            self.encoding,
+        )
+    }
+
+    fn ivc_program(&self, template: &R1CSTemplate<F>) -> Result<IVCProgram<F>, Error> {
+        Ok(IVCProgram {
             io: self.program.io.clone(),
-            num_witness,
-            r1cs_constraints,
+            num_witness: template.num_witness()?,
+            r1cs_constraints: template.constraints.clone(),
             curve: self.program.curve.clone(),
             version: self.program.version.clone(),
-        };
+        })
+    }
+
+    /// Drop the compiled R1CS after the circuit changed
+    fn reset_r1cs(&mut self) {
+        self.r1cs = None;
+        self.digest = None;
+        // Note: saturated on overflow, which `compile` reports
+        self.program.num_witness = self.num_circuit_witness().unwrap_or(u32::MAX);
+    }
+
+    /// Number of R1CS constraints, generating them if not compiled
+    pub fn num_constraints(&self) -> Result<usize, Error> {
+        Ok(self.try_r1cs_template()?.constraints.len())
+    }
 
-        Ok(Step {
-            witness: Witness(witness),
-            program: ivc_program,
+    /// Generate the R1CS once, later steps only extend the witness
+    pub fn compile(&mut self) -> Result<IVCProgram<F>, Error> {
+        self.check_limits()?;
+        self.check_satisfiable()?;
+
+        let template = self.generate_r1cs()?;
+        let program = self.ivc_program(&template)?;
+
+        self.program.num_witness = program.num_witness;
+        self.r1cs = Some(template);
+        self.digest = Some(program_digest(&program));
+
+        Ok(program)
+    }
+
+    /// The digest of the compiled program, or of a freshly generated one
+    pub fn digest(&self) -> Result<String, Error> {
+        Ok(match &self.digest {
+            Some(digest) => digest.clone(),
+            None => program_digest(&self.ivc_program(&self.try_r1cs_template()?)?),
         })
     }
+
+    /// Check the structure, and its `IVCProgram`, were made over `F`, e.g.
+    /// after loading it from a file. Passes if the curve was not recorded
+    pub fn check_curve(&self) -> Result<(), Error> {
+        let found = Curve::of::<F>();
+        if self.curve.is_some() && self.curve != found {
+            return Err(Error::CurveMismatch {
+                expected: self.curve,
+                found,
+            });
+        }
+        Curve::check_program_name::<F>(&self.program.curve)
+    }
+
+    /// Same as `check_curve`, also checking ACVM solves it over `AF`
+    pub fn check_acvm_curve<AF: ArkPrimeField>(&self) -> Result<(), Error> {
+        self.check_curve()?;
+        check_acvm_field::<F, AF>()
+    }
+
+    /// Check `program` was compiled from this circuit, e.g. after loading
+    /// both from files
+    pub fn check_program(&self, program: &IVCProgram<F>) -> Result<(), Error> {
+        self.check_curve()?;
+        Curve::check_program_name::<F>(&program.curve)?;
+        let expected = self.digest()?;
+        let found = program_digest(program);
+        if expected != found {
+            return Err(Error::DigestMismatch { expected, found });
+        }
+        Ok(())
+    }
+
+    /// Renumber the solved witness (in the ACIR numbering), pad it and
+    /// extend it with the product witnesses
+    pub fn make_witness(&self, solved_witness: &Witness<F>) -> Result<Witness<F>, Error> {
+        self.make_dense_witness(solved_witness).map(Into::into)
+    }
+
+    /// Same as `make_witness`, as a `DenseWitness`
+    pub fn make_dense_witness(
+        &self,
+        solved_witness: &Witness<F>,
+    ) -> Result<DenseWitness<F>, Error> {
+        match &self.r1cs {
+            Some(template) => self.fill_witness(template, solved_witness),
+            None => self.make_dense_step_streaming(solved_witness, |_| {}),
+        }
+    }
+
+    /// Same as `make_step`, but hands every constraint to `sink` instead of
+    /// collecting them, e.g. to write them to disk. Returns the witness
+    pub fn make_step_streaming(
+        &self,
+        solved_witness: &Witness<F>,
+        sink: impl FnMut(R1CSConstraint<F>),
+    ) -> Result<Witness<F>, Error> {
+        self.make_dense_step_streaming(solved_witness, sink)
+            .map(Into::into)
+    }
+
+    fn make_dense_step_streaming(
+        &self,
+        solved_witness: &Witness<F>,
+        mut sink: impl FnMut(R1CSConstraint<F>),
+    ) -> Result<DenseWitness<F>, Error> {
+        match &self.r1cs {
+            Some(template) => {
+                template.constraints.iter().cloned().for_each(&mut sink);
+                self.fill_witness(template, solved_witness)
+            }
+            None => {
+                let template = R1CSTemplate::generate_streaming(
+                    &self.r1cs_gates(),
+                    self.num_circuit_witness()?,
+                    self.encoding,
+                    sink,
+                )?;
+                self.fill_witness(&template, solved_witness)
+            }
+        }
+    }
+
+    fn fill_witness(
+        &self,
+        template: &R1CSTemplate<F>,
+        solved_witness: &Witness<F>,
+    ) -> Result<DenseWitness<F>, Error> {
+        // Note: witnesses no constraint refers to are dropped, the product
+        // witnesses are numbered right after the circuit witnesses
+        let mut slots = vec![None; template.num_circuit_witness as usize];
+        for (&id, &value) in solved_witness.iter() {
+            if let Some(slot) = self
+                .r1cs_witness(id)
+                .and_then(|id| slots.get_mut(id.0 as usize))
+            {
+                *slot = Some(value);
+            }
+        }
+
+        for id in &self.padding {
+            if let Some(slot) = slots.get_mut(id.0 as usize) {
+                slot.get_or_insert(F::ZERO);
+            }
+        }
+
+        // Note: sized for the product witnesses too, so extending it does not
+        // reallocate
+        let mut witness = Vec::with_capacity(template.num_witness()? as usize);
+        for (i, value) in slots.into_iter().enumerate() {
+            witness.push(value.ok_or(Error::MissingWitness(i as u32))?);
+        }
+
+        template.extend_dense_witness(&mut witness)?;
+
+        Ok(DenseWitness(witness))
+    }
+
+    pub fn make_step(&self, solved_witness: &Witness<F>) -> Result<Step<F>, Error> {
+        self.check_limits()?;
+
+        // Note: an uncompiled structure generates its R1CS once for both
+        let template = self.try_r1cs_template()?;
+        let witness = self.fill_witness(&template, solved_witness)?.into();
+        let program = self.ivc_program(&template)?;
+
+        Ok(Step { witness, program })
+    }
 }
@@ -0,0 +1,122 @@
+//! Families of circuits, for non-uniform IVC.
+//!
+//! The circuits of a set share their state: the public inputs of a step, in
+//! witness order, with the public outputs as the state of the next step.
+//! The first element of the state is the program counter, the index of the
+//! circuit that runs the step, so every step selects the circuit of the
+//! next one, e.g. the handler of the next opcode of a VM.
+//!
+//! Every executed step yields the index of its circuit, which selects the
+//! circuit that folds it: with the `supernova` feature, a set is the
+//! `NonUniformCircuit` of SuperNova (see `prove_set_steps`).
+
+use std::sync::Arc;
+
+use acvm::{
+    acir::{acir_field::GenericFieldElement, circuit::Program},
+    blackbox_solver::BlackBoxFunctionSolver,
+};
+use ark_ff::PrimeField as ArkPrimeField;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::PrimeField;
+use ivc_program::{input::IO, witness::Witness};
+
+use crate::{
+    functions::{chain_io_steps, compile, execute_io_step},
+    program::CircuitStructure,
+    Error, ExecutionResult,
+};
+
+pub struct ProgramSet<F> {
+    pub circuits: Vec<Arc<CircuitStructure<F>>>,
+}
+
+impl<F: PrimeField> ProgramSet<F> {
+    /// Fails unless all circuits have as many public inputs as public
+    /// outputs, the same for all of them and at least one for the counter
+    pub fn new(circuits: Vec<CircuitStructure<F>>) -> Result<Self, Error> {
+        let arity = circuits
+            .first()
+            .map(|circuit| circuit.program.public_inputs.len())
+            .ok_or(Error::InvalidProgramSet("no circuit"))?;
+        if arity == 0 {
+            return Err(Error::InvalidProgramSet("no program counter"));
+        }
+
+        for circuit in &circuits {
+            let io = &circuit.program;
+            if io.public_inputs.len() != arity || io.public_outputs.len() != arity {
+                return Err(Error::InvalidProgramSet("different states"));
+            }
+        }
+
+        Ok(Self {
+            circuits: circuits.into_iter().map(Arc::new).collect(),
+        })
+    }
+
+    /// Compile every program, its index being its program counter
+    pub fn compile<AF: ArkPrimeField>(
+        programs: Vec<Program<GenericFieldElement<AF>>>,
+    ) -> Result<Self, Error> {
+        let circuits = programs
+            .into_iter()
+            .map(|program| Ok(compile::<F, AF>(program)?.0))
+            .collect::<Result<_, Error>>()?;
+        Self::new(circuits)
+    }
+
+    pub fn len(&self) -> usize {
+        self.circuits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.circuits.is_empty()
+    }
+
+    /// Size of the state, including the program counter
+    pub fn arity(&self) -> usize {
+        self.circuits[0].program.public_inputs.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Arc<CircuitStructure<F>>> {
+        self.circuits.get(index)
+    }
+
+    /// Index of the circuit selected by `state`, if any
+    pub fn program_counter(&self, state: &IO<F>) -> Option<usize> {
+        let pc = state.0.first()?;
+        (0..self.len()).find(|&index| F::from(index as u64) == *pc)
+    }
+
+    /// Execute a step per private input from `start_state`, each step with
+    /// the circuit selected by its state. Yields the index of the circuit
+    /// with the results of each step, and stops after the first failed step
+    pub fn execute_steps<'a, AF: ArkPrimeField>(
+        &'a self,
+        start_state: IO<F>,
+        start_step_num: u64,
+        private_inputs: impl Iterator<Item = IO<F>> + 'a,
+    ) -> impl Iterator<Item = Result<(usize, ExecutionResult<F>, Witness<F>, IO<F>), Error>> + 'a
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        chain_io_steps(
+            start_state,
+            start_step_num,
+            private_inputs,
+            move |state, private_input, step_num| {
+                let index = self
+                    .program_counter(state)
+                    .ok_or(Error::InvalidProgramCounter(step_num))?;
+                let (exe_res, witness, next) = execute_io_step::<F, AF>(
+                    &self.circuits[index],
+                    state,
+                    &private_input,
+                    step_num,
+                )?;
+                Ok((index, exe_res, witness, next))
+            },
+        )
+    }
+}
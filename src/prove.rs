@@ -0,0 +1,406 @@
+//! Folding of executed steps with nova-snark.
+//!
+//! Takes the steps of `execute_steps` as they are produced, so a chain is
+//! executed and folded in a single pass. The recursive proof is then
+//! compressed by `finalize`, e.g. with Spartan as `S1` and `S2`.
+//!
+//! `ProvingConfig` picks the commitment scheme at runtime, for chains over
+//! BN254 with Grumpkin as secondary curve.
+//!
+//! The parameters, keys and proofs are stored with `write_artifact`: a
+//! header of the magic `NIVC`, the format version and the kind of artifact,
+//! then the artifact in bincode. Loading fails on another version or kind.
+
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use ff::Field;
+use ivc_program::{input::IO, witness::Witness};
+use nova_snark::{
+    provider::{Bn256EngineIPA, Bn256EngineKZG, GrumpkinEngine},
+    traits::{snark::RelaxedR1CSSNARKTrait, Engine},
+    CompressedSNARK, ProverKey, RecursiveSNARK, VerifierKey,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    nova::NoirStepCircuit,
+    nova_scotia::{create_public_params, verify, NoirPublicParams, NoirRecursiveSNARK, C1, C2},
+    program::CircuitStructure,
+    Error, ExecutionResult,
+};
+
+/// Fold every step of `steps` in order, returning the recursive proof.
+/// The initial state is the public input of the first step, and `structure`
+/// is expected to be compiled
+pub fn prove_steps<E1, E2, I>(
+    structure: Arc<CircuitStructure<E1::Scalar>>,
+    pp: &NoirPublicParams<E1, E2>,
+    steps: I,
+) -> Result<NoirRecursiveSNARK<E1, E2>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+    I: IntoIterator<
+        Item = Result<
+            (
+                ExecutionResult<E1::Scalar>,
+                Witness<E1::Scalar>,
+                IO<E1::Scalar>,
+            ),
+            Error,
+        >,
+    >,
+{
+    prove_steps_with(&NoirStepCircuit::new(structure), pp, steps)
+}
+
+/// Same as `prove_steps`, folding `circuit`, e.g. with a state digest. The
+/// initial state is then `circuit.z` of the public input of the first step
+pub fn prove_steps_with<E1, E2, I>(
+    circuit: &C1<E1>,
+    pp: &NoirPublicParams<E1, E2>,
+    steps: I,
+) -> Result<NoirRecursiveSNARK<E1, E2>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+    I: IntoIterator<
+        Item = Result<
+            (
+                ExecutionResult<E1::Scalar>,
+                Witness<E1::Scalar>,
+                IO<E1::Scalar>,
+            ),
+            Error,
+        >,
+    >,
+{
+    let structure = &circuit.structure;
+    let circuit_secondary = C2::<E2>::default();
+    let z0_secondary = vec![E2::Scalar::ZERO];
+
+    let mut recursive_snark: Option<NoirRecursiveSNARK<E1, E2>> = None;
+
+    for step in steps {
+        let (result, witness, _) = step?;
+        let z0 = recursive_snark
+            .is_none()
+            .then(|| circuit.z(&public_input_state(structure, &witness)));
+        let step_circuit = circuit
+            .with_witness(witness)
+            .with_input_randomness(result.input_randomness);
+
+        if let Some(z0) = z0 {
+            recursive_snark = Some(RecursiveSNARK::new(
+                pp,
+                &step_circuit,
+                &circuit_secondary,
+                &z0,
+                &z0_secondary,
+            )?);
+        }
+
+        let snark = recursive_snark.as_mut().unwrap();
+        snark.prove_step(pp, &step_circuit, &circuit_secondary)?;
+    }
+
+    recursive_snark.ok_or(Error::InvalidInput)
+}
+
+/// The public inputs of a step, in witness order
+fn public_input_state<F: Copy>(structure: &CircuitStructure<F>, witness: &Witness<F>) -> Vec<F> {
+    structure
+        .program
+        .public_inputs
+        .iter()
+        .map(|id| witness.0[id])
+        .collect()
+}
+
+/// A chain proven by `prove_chains`
+pub struct ChainProof<E1, E2>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+{
+    /// Public input of the first step, as claimed by the prover
+    pub start_state: Vec<E1::Scalar>,
+    pub proof: NoirRecursiveSNARK<E1, E2>,
+}
+
+/// Fold the steps of independent chains of the same structure, e.g. the
+/// shards of workers, with the same public parameters. The start state of
+/// a chain is the public input of its first step.
+// Note: a recursive proof of nova-snark folds steps into a single running
+// instance, and cannot absorb another one, so the chains are not folded
+// together into a tree (PCD) but proven side by side
+pub fn prove_chains<E1, E2, I>(
+    structure: Arc<CircuitStructure<E1::Scalar>>,
+    pp: &NoirPublicParams<E1, E2>,
+    chains: impl IntoIterator<Item = I>,
+) -> Result<Vec<ChainProof<E1, E2>>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+    I: IntoIterator<
+        Item = Result<
+            (
+                ExecutionResult<E1::Scalar>,
+                Witness<E1::Scalar>,
+                IO<E1::Scalar>,
+            ),
+            Error,
+        >,
+    >,
+{
+    chains
+        .into_iter()
+        .map(|steps| {
+            let mut start_state = None;
+            let steps = steps.into_iter().inspect(|step| {
+                if let (true, Ok((_, witness, _))) = (start_state.is_none(), step) {
+                    start_state = Some(public_input_state(&structure, witness));
+                }
+            });
+
+            let proof = prove_steps(structure.clone(), pp, steps)?;
+            Ok(ChainProof {
+                start_state: start_state.ok_or(Error::InvalidInput)?,
+                proof,
+            })
+        })
+        .collect()
+}
+
+/// Verify every chain of `chains` from the expected `start_states`, in the
+/// same order, returning their final states. Fails if a chain claims
+/// another start state
+pub fn verify_chains<E1, E2>(
+    pp: &NoirPublicParams<E1, E2>,
+    chains: &[ChainProof<E1, E2>],
+    start_states: &[Vec<E1::Scalar>],
+) -> Result<Vec<Vec<E1::Scalar>>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+{
+    if chains.len() != start_states.len() {
+        return Err(Error::InvalidInput);
+    }
+
+    chains
+        .iter()
+        .zip(start_states)
+        .map(|(chain, start_state)| {
+            if chain.start_state != *start_state {
+                return Err(Error::InvalidInput);
+            }
+            verify(&chain.proof, pp, chain.proof.num_steps(), start_state)
+        })
+        .collect()
+}
+
+/// Commitment scheme of the primary curve
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CommitmentScheme {
+    /// Transparent, with a larger verifier
+    #[default]
+    Ipa,
+    /// Needs a trusted setup, with a cheaper verifier
+    Kzg,
+}
+
+/// Choices of the proving backend.
+// Note: the hash of the random oracle is Poseidon, fixed by the engines of
+// nova-snark
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvingConfig {
+    pub commitment: CommitmentScheme,
+}
+
+type Bn254Scalar = <Bn256EngineKZG as Engine>::Scalar;
+
+/// Public parameters and recursive proof of a chain, for the engines of a
+/// `ProvingConfig`
+pub enum ProvedChain {
+    Ipa {
+        pp: NoirPublicParams<Bn256EngineIPA, GrumpkinEngine>,
+        proof: NoirRecursiveSNARK<Bn256EngineIPA, GrumpkinEngine>,
+    },
+    Kzg {
+        pp: NoirPublicParams<Bn256EngineKZG, GrumpkinEngine>,
+        proof: NoirRecursiveSNARK<Bn256EngineKZG, GrumpkinEngine>,
+    },
+}
+
+impl ProvingConfig {
+    pub fn with_commitment(mut self, commitment: CommitmentScheme) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Set up the parameters of `structure` and fold `steps` (see
+    /// `prove_steps`)
+    pub fn prove<I>(
+        &self,
+        structure: Arc<CircuitStructure<Bn254Scalar>>,
+        steps: I,
+    ) -> Result<ProvedChain, Error>
+    where
+        I: IntoIterator<
+            Item = Result<
+                (
+                    ExecutionResult<Bn254Scalar>,
+                    Witness<Bn254Scalar>,
+                    IO<Bn254Scalar>,
+                ),
+                Error,
+            >,
+        >,
+    {
+        Ok(match self.commitment {
+            CommitmentScheme::Ipa => {
+                let pp = create_public_params(structure.clone())?;
+                let proof = prove_steps(structure, &pp, steps)?;
+                ProvedChain::Ipa { pp, proof }
+            }
+            CommitmentScheme::Kzg => {
+                let pp = create_public_params(structure.clone())?;
+                let proof = prove_steps(structure, &pp, steps)?;
+                ProvedChain::Kzg { pp, proof }
+            }
+        })
+    }
+}
+
+impl ProvedChain {
+    pub fn commitment(&self) -> CommitmentScheme {
+        match self {
+            Self::Ipa { .. } => CommitmentScheme::Ipa,
+            Self::Kzg { .. } => CommitmentScheme::Kzg,
+        }
+    }
+
+    /// Verify `num_steps` steps from `start_state`, returning the final state
+    pub fn verify(
+        &self,
+        num_steps: usize,
+        start_state: &[Bn254Scalar],
+    ) -> Result<Vec<Bn254Scalar>, Error> {
+        match self {
+            Self::Ipa { pp, proof } => verify(proof, pp, num_steps, start_state),
+            Self::Kzg { pp, proof } => verify(proof, pp, num_steps, start_state),
+        }
+    }
+}
+
+pub type NoirCompressedSNARK<E1, E2, S1, S2> = CompressedSNARK<E1, E2, C1<E1>, C2<E2>, S1, S2>;
+pub type NoirProverKey<E1, E2, S1, S2> = ProverKey<E1, E2, C1<E1>, C2<E2>, S1, S2>;
+pub type NoirVerifierKey<E1, E2, S1, S2> = VerifierKey<E1, E2, C1<E1>, C2<E2>, S1, S2>;
+
+/// Keys to compress the recursive proofs of `pp` and verify them
+#[allow(clippy::type_complexity)]
+pub fn setup_compression<E1, E2, S1, S2>(
+    pp: &NoirPublicParams<E1, E2>,
+) -> Result<
+    (
+        NoirProverKey<E1, E2, S1, S2>,
+        NoirVerifierKey<E1, E2, S1, S2>,
+    ),
+    Error,
+>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+    S1: RelaxedR1CSSNARKTrait<E1>,
+    S2: RelaxedR1CSSNARKTrait<E2>,
+{
+    Ok(CompressedSNARK::setup(pp)?)
+}
+
+/// Compress a recursive proof into a proof of constant size
+pub fn finalize<E1, E2, S1, S2>(
+    pp: &NoirPublicParams<E1, E2>,
+    pk: &NoirProverKey<E1, E2, S1, S2>,
+    recursive_snark: &NoirRecursiveSNARK<E1, E2>,
+) -> Result<NoirCompressedSNARK<E1, E2, S1, S2>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+    S1: RelaxedR1CSSNARKTrait<E1>,
+    S2: RelaxedR1CSSNARKTrait<E2>,
+{
+    Ok(CompressedSNARK::prove(pp, pk, recursive_snark)?)
+}
+
+/// Verify a compressed proof of `num_steps` steps from `start_state`,
+/// returning the final state
+pub fn verify_compressed<E1, E2, S1, S2>(
+    proof: &NoirCompressedSNARK<E1, E2, S1, S2>,
+    vk: &NoirVerifierKey<E1, E2, S1, S2>,
+    num_steps: usize,
+    start_state: &[E1::Scalar],
+) -> Result<Vec<E1::Scalar>, Error>
+where
+    E1: Engine<Base = <E2 as Engine>::Scalar>,
+    E2: Engine<Base = <E1 as Engine>::Scalar>,
+    S1: RelaxedR1CSSNARKTrait<E1>,
+    S2: RelaxedR1CSSNARKTrait<E2>,
+{
+    let (state, _) = proof.verify(vk, num_steps, start_state, &[E2::Scalar::ZERO])?;
+    Ok(state)
+}
+
+const ARTIFACT_MAGIC: &[u8; 4] = b"NIVC";
+
+/// Version of the format of `write_artifact`
+pub const ARTIFACT_FORMAT_VERSION: u8 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ArtifactKind {
+    PublicParams = 0,
+    RecursiveSNARK = 1,
+    CompressedSNARK = 2,
+    ProverKey = 3,
+    VerifierKey = 4,
+}
+
+fn bincode_error(e: bincode::Error) -> Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e).into()
+}
+
+/// Write `artifact` as a `kind` artifact
+pub fn write_artifact<T: Serialize>(
+    mut writer: impl Write,
+    kind: ArtifactKind,
+    artifact: &T,
+) -> Result<(), Error> {
+    writer.write_all(ARTIFACT_MAGIC)?;
+    writer.write_all(&[ARTIFACT_FORMAT_VERSION, kind as u8])?;
+    bincode::serialize_into(writer, artifact).map_err(bincode_error)
+}
+
+/// Read a `kind` artifact written by `write_artifact`
+pub fn read_artifact<T: DeserializeOwned>(
+    mut reader: impl Read,
+    kind: ArtifactKind,
+) -> Result<T, Error> {
+    let mut header = [0u8; 6];
+    reader.read_exact(&mut header)?;
+
+    if header[..4] != ARTIFACT_MAGIC[..] {
+        return Err(Error::InvalidArtifact("not an artifact"));
+    }
+    if header[4] != ARTIFACT_FORMAT_VERSION {
+        return Err(Error::UnsupportedArtifactVersion(header[4]));
+    }
+    if header[5] != kind as u8 {
+        return Err(Error::InvalidArtifact("wrong kind"));
+    }
+
+    bincode::deserialize_from(reader).map_err(bincode_error)
+}
@@ -0,0 +1,716 @@
+//! Lowering of gates into R1CS constraints.
+//!
+//! Constraints only depend on the gates, never on witness values, so every
+//! step shares the same R1CS shape. Mul terms that are not encoded directly
+//! introduce product witnesses, numbered after the circuit witnesses.
+//! Constraints identical up to the order of `a` and `b`, or linear
+//! constraints equal up to a factor, are emitted once.
+
+use std::collections::{BTreeMap, HashSet};
+
+use ff::PrimeField;
+use ivc_program::{
+    program::{R1CSConstraint, Term, WitnessID, LC},
+    witness::Witness,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{gate::AcirArithGate, Error};
+
+/// How the mul terms of a gate are lowered into R1CS
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum R1CSEncoding {
+    /// A product witness and constraint per mul term, plus one linear
+    /// constraint per gate. A gate whose only mul term is a square is still
+    /// encoded as one quadratic constraint
+    #[default]
+    ProductWitness,
+
+    /// A gate with a single mul term becomes one quadratic constraint,
+    /// other gates are encoded as in `ProductWitness`
+    Quadratic,
+}
+
+/// A witness introduced for a mul term: `id = left * right`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Product {
+    pub id: WitnessID,
+    pub left: WitnessID,
+    pub right: WitnessID,
+}
+
+impl Product {
+    pub fn is_square(&self) -> bool {
+        self.left == self.right
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct R1CSTemplate<F> {
+    /// Product witnesses are numbered from here
+    pub num_circuit_witness: u32,
+    pub products: Vec<Product>,
+    pub constraints: Vec<R1CSConstraint<F>>,
+}
+
+fn var<F>(coefficient: F, var_id: WitnessID) -> Term<F> {
+    Term::LC {
+        coefficient,
+        var_id,
+    }
+}
+
+/// Merge the terms of the same witness and the constants, dropping zeros
+pub fn normalize<F: PrimeField>(lc: LC<F>) -> LC<F> {
+    let mut coefficients: BTreeMap<WitnessID, F> = BTreeMap::new();
+    let mut constant = F::ZERO;
+
+    for term in lc.0 {
+        match term {
+            Term::LC {
+                coefficient,
+                var_id,
+            } => *coefficients.entry(var_id).or_insert(F::ZERO) += coefficient,
+            Term::Const(value) => constant += value,
+        }
+    }
+
+    let mut terms: Vec<Term<F>> = coefficients
+        .into_iter()
+        .filter(|(_, coefficient)| !bool::from(coefficient.is_zero()))
+        .map(|(var_id, coefficient)| var(coefficient, var_id))
+        .collect();
+
+    if !bool::from(constant.is_zero()) {
+        terms.push(Term::Const(constant));
+    }
+
+    LC(terms)
+}
+
+fn constraint<F: PrimeField>(a: LC<F>, b: LC<F>, c: LC<F>) -> R1CSConstraint<F> {
+    R1CSConstraint {
+        a: normalize(a),
+        b: normalize(b),
+        c: normalize(c),
+    }
+}
+
+pub(crate) fn lc_key<F: PrimeField>(lc: &LC<F>, scale: F, key: &mut Vec<u8>) {
+    key.extend((lc.0.len() as u32).to_le_bytes());
+    for term in &lc.0 {
+        let (id, value) = match term {
+            Term::LC {
+                coefficient,
+                var_id,
+            } => (var_id.0, *coefficient),
+            Term::Const(value) => (u32::MAX, *value),
+        };
+        key.extend(id.to_le_bytes());
+        key.extend((value * scale).to_repr().as_ref());
+    }
+}
+
+/// Canonical bytes of a normalized constraint: `a` and `b` in a fixed
+/// order, and a linear constraint `a * 1 = 0` scaled to a leading one
+fn constraint_key<F: PrimeField>(constraint: &R1CSConstraint<F>) -> Vec<u8> {
+    let bytes = |lc: &LC<F>, scale: F| {
+        let mut key = vec![];
+        lc_key(lc, scale, &mut key);
+        key
+    };
+
+    let is_linear = constraint.c.0.is_empty()
+        && matches!(constraint.b.0[..], [Term::Const(one)] if one == F::ONE);
+    let scale = match constraint.a.0.first() {
+        Some(Term::LC { coefficient, .. }) if is_linear => {
+            Option::<F>::from(coefficient.invert()).unwrap_or(F::ONE)
+        }
+        _ => F::ONE,
+    };
+
+    let a = bytes(&constraint.a, scale);
+    let b = bytes(&constraint.b, F::ONE);
+    let mut key = if a <= b {
+        [a, b].concat()
+    } else {
+        [b, a].concat()
+    };
+    lc_key(&constraint.c, scale, &mut key);
+    key
+}
+
+/// Drops the constraints already emitted
+#[derive(Default)]
+struct Dedup(HashSet<Vec<u8>>);
+
+impl Dedup {
+    fn is_new<F: PrimeField>(&mut self, constraint: &R1CSConstraint<F>) -> bool {
+        self.0.insert(constraint_key(constraint))
+    }
+}
+
+/// The mul term of a gate encoded as one quadratic constraint, if any
+fn quadratic_term<F: PrimeField>(
+    gate: &AcirArithGate<F>,
+    encoding: R1CSEncoding,
+) -> Option<(F, WitnessID, WitnessID)> {
+    match gate.mul_terms[..] {
+        [(coeff, left, right)] if encoding == R1CSEncoding::Quadratic || left == right => {
+            Some((coeff, left, right))
+        }
+        _ => None,
+    }
+}
+
+/// Product witnesses of a gate, allocated before its constraints are built
+#[derive(Default)]
+struct GatePlan {
+    /// Product witness of each mul term, empty for a quadratic gate
+    products: Vec<WitnessID>,
+
+    /// Products first introduced by this gate, constrained along with it
+    new_products: Vec<Product>,
+}
+
+/// Allocates the product witnesses, the only sequential part of generation
+struct Allocator<F> {
+    template: R1CSTemplate<F>,
+    encoding: R1CSEncoding,
+
+    /// Every product is introduced once, keyed by its (sorted) factors
+    product_ids: BTreeMap<(WitnessID, WitnessID), WitnessID>,
+}
+
+impl<F: PrimeField> Allocator<F> {
+    fn new(num_circuit_witness: u32, encoding: R1CSEncoding) -> Self {
+        Self {
+            template: R1CSTemplate {
+                num_circuit_witness,
+                products: vec![],
+                constraints: vec![],
+            },
+            encoding,
+            product_ids: BTreeMap::new(),
+        }
+    }
+
+    fn plan(&mut self, gate: &AcirArithGate<F>) -> Result<GatePlan, Error> {
+        let mut plan = GatePlan::default();
+
+        if quadratic_term(gate, self.encoding).is_some() {
+            return Ok(plan);
+        }
+
+        for (_, left, right) in &gate.mul_terms {
+            let key = (*left.min(right), *left.max(right));
+
+            let id = match self.product_ids.get(&key) {
+                Some(id) => *id,
+                None => {
+                    // Note: counted in u64, a witness index must be below
+                    // u32::MAX so the number of witnesses fits a u32
+                    let next = self.template.num_circuit_witness as u64
+                        + self.template.products.len() as u64;
+                    let id = u32::try_from(next)
+                        .ok()
+                        .filter(|id| *id < u32::MAX)
+                        .map(WitnessID)
+                        .ok_or(Error::TooManyWitnesses(next))?;
+                    let product = Product {
+                        id,
+                        left: *left,
+                        right: *right,
+                    };
+
+                    self.template.products.push(product);
+                    plan.new_products.push(product);
+                    self.product_ids.insert(key, id);
+                    id
+                }
+            };
+
+            plan.products.push(id);
+        }
+
+        Ok(plan)
+    }
+}
+
+/// Constraints of a planned gate: one per new product, then the gate itself
+fn gate_constraints<F: PrimeField>(
+    gate: &AcirArithGate<F>,
+    plan: &GatePlan,
+    encoding: R1CSEncoding,
+) -> Vec<R1CSConstraint<F>> {
+    // (c * x_l) * x_r = -(sum of add terms + constant)
+    if let Some((coeff, left, right)) = quadratic_term(gate, encoding) {
+        let mut c = LC(gate
+            .add_terms
+            .iter()
+            .map(|(coeff, id)| var(-*coeff, *id))
+            .collect());
+        c.0.push(Term::Const(-gate.constant_term));
+
+        return vec![constraint(
+            LC(vec![var(coeff, left)]),
+            LC(vec![var(F::ONE, right)]),
+            c,
+        )];
+    }
+
+    let mut constraints: Vec<_> = plan
+        .new_products
+        .iter()
+        .map(|product| {
+            constraint(
+                LC(vec![var(F::ONE, product.left)]),
+                LC(vec![var(F::ONE, product.right)]),
+                LC(vec![var(F::ONE, product.id)]),
+            )
+        })
+        .collect();
+
+    let mut big_lc_a = LC::default();
+
+    for ((coeff, _, _), id) in gate.mul_terms.iter().zip(&plan.products) {
+        big_lc_a.0.push(var(*coeff, *id));
+    }
+
+    for (coeff, id) in &gate.add_terms {
+        big_lc_a.0.push(var(*coeff, *id));
+    }
+
+    big_lc_a.0.push(Term::Const(gate.constant_term));
+
+    constraints.push(constraint(
+        big_lc_a,
+        LC(vec![Term::Const(F::ONE)]),
+        Default::default(),
+    ));
+
+    constraints
+}
+
+impl<F: PrimeField> R1CSTemplate<F> {
+    pub fn generate<'a>(
+        gates: impl IntoIterator<Item = &'a AcirArithGate<F>>,
+        num_circuit_witness: u32,
+        encoding: R1CSEncoding,
+    ) -> Result<Self, Error>
+    where
+        F: 'a,
+    {
+        // Note: most gates lower to a single constraint
+        let gates = gates.into_iter();
+        let mut constraints = Vec::with_capacity(gates.size_hint().0);
+        let mut template =
+            Self::generate_streaming(gates, num_circuit_witness, encoding, |constraint| {
+                constraints.push(constraint)
+            })?;
+        template.constraints = constraints;
+        Ok(template)
+    }
+
+    /// Same as `generate`, but hands every constraint to `sink` instead of
+    /// storing it, so the returned template has no constraints
+    pub fn generate_streaming<'a>(
+        gates: impl IntoIterator<Item = &'a AcirArithGate<F>>,
+        num_circuit_witness: u32,
+        encoding: R1CSEncoding,
+        mut sink: impl FnMut(R1CSConstraint<F>),
+    ) -> Result<Self, Error>
+    where
+        F: 'a,
+    {
+        let mut allocator = Allocator::new(num_circuit_witness, encoding);
+        let mut dedup = Dedup::default();
+
+        // trivial gates (e.g. left by constant folding) need no constraint
+        for gate in gates.into_iter().filter(|gate| !gate.is_trivial()) {
+            let plan = allocator.plan(gate)?;
+            gate_constraints(gate, &plan, encoding)
+                .into_iter()
+                .filter(|constraint| dedup.is_new(constraint))
+                .for_each(&mut sink);
+        }
+
+        Ok(allocator.template)
+    }
+
+    /// Same as `generate`, building the constraints of the gates in parallel
+    /// once all product witnesses are allocated
+    #[cfg(feature = "parallel")]
+    pub fn generate_parallel(
+        gates: &[AcirArithGate<F>],
+        num_circuit_witness: u32,
+        encoding: R1CSEncoding,
+    ) -> Result<Self, Error> {
+        use rayon::prelude::*;
+
+        let mut allocator = Allocator::new(num_circuit_witness, encoding);
+
+        let plans: Vec<_> = gates
+            .iter()
+            .filter(|gate| !gate.is_trivial())
+            .map(|gate| Ok((gate, allocator.plan(gate)?)))
+            .collect::<Result<_, Error>>()?;
+
+        let constraints: Vec<Vec<_>> = plans
+            .par_iter()
+            .map(|(gate, plan)| gate_constraints(gate, plan, encoding))
+            .collect();
+
+        let mut dedup = Dedup::default();
+        let mut template = allocator.template;
+        template.constraints = constraints
+            .into_iter()
+            .flatten()
+            .filter(|constraint| dedup.is_new(constraint))
+            .collect();
+        Ok(template)
+    }
+
+    /// Circuit witnesses plus product witnesses
+    pub fn num_witness(&self) -> Result<u32, Error> {
+        let num_witness = self.num_circuit_witness as u64 + self.products.len() as u64;
+        u32::try_from(num_witness).map_err(|_| Error::TooManyWitnesses(num_witness))
+    }
+
+    /// Assign the product witnesses from the circuit witnesses
+    pub fn extend_witness(&self, witness: &mut BTreeMap<WitnessID, F>) -> Result<(), Error> {
+        for product in &self.products {
+            let value =
+                |id: &WitnessID| witness.get(id).cloned().ok_or(Error::MissingWitness(id.0));
+            let value = if product.is_square() {
+                value(&product.left)?.square()
+            } else {
+                value(&product.left)? * value(&product.right)?
+            };
+            witness.insert(product.id, value);
+        }
+
+        Ok(())
+    }
+
+    /// Same as `extend_witness`, on the circuit witnesses in witness order
+    pub fn extend_dense_witness(&self, witness: &mut Vec<F>) -> Result<(), Error> {
+        for product in &self.products {
+            let value = |id: &WitnessID| {
+                witness
+                    .get(id.0 as usize)
+                    .cloned()
+                    .ok_or(Error::MissingWitness(id.0))
+            };
+            let value = if product.is_square() {
+                value(&product.left)?.square()
+            } else {
+                value(&product.left)? * value(&product.right)?
+            };
+
+            // Note: product witnesses are numbered in allocation order
+            debug_assert_eq!(product.id.0 as usize, witness.len());
+            witness.push(value);
+        }
+
+        Ok(())
+    }
+
+    /// Check a full witness satisfies every constraint, failing with the
+    /// index of the first unsatisfied one
+    pub fn check_witness(&self, witness: &Witness<F>) -> Result<(), Error> {
+        self.check_values(|id| witness.0.get(id))
+    }
+
+    /// Same as `check_witness`, on a witness in witness order
+    pub fn check_dense_witness(&self, witness: &[F]) -> Result<(), Error> {
+        self.check_values(|id| witness.get(id.0 as usize))
+    }
+
+    fn check_values<'a>(&self, value: impl Fn(&WitnessID) -> Option<&'a F>) -> Result<(), Error>
+    where
+        F: 'a,
+    {
+        let evaluate = |lc: &LC<F>| {
+            lc.0.iter().try_fold(F::ZERO, |sum, term| match term {
+                Term::LC {
+                    coefficient,
+                    var_id,
+                } => {
+                    let value = value(var_id).ok_or(Error::MissingWitness(var_id.0))?;
+                    Ok(sum + *coefficient * value)
+                }
+                Term::Const(value) => Ok(sum + value),
+            })
+        };
+
+        for (i, constraint) in self.constraints.iter().enumerate() {
+            if evaluate(&constraint.a)? * evaluate(&constraint.b)? != evaluate(&constraint.c)? {
+                return Err(Error::UnsatisfiedConstraint(i));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Counts the constraints of gates added one by one, as `generate` would
+/// lower them together
+pub(crate) struct ConstraintCounter<F> {
+    allocator: Allocator<F>,
+    dedup: Dedup,
+}
+
+impl<F: PrimeField> ConstraintCounter<F> {
+    pub(crate) fn new(encoding: R1CSEncoding) -> Self {
+        Self {
+            allocator: Allocator::new(0, encoding),
+            dedup: Dedup::default(),
+        }
+    }
+
+    /// Number of constraints added by `gate`
+    pub(crate) fn add(&mut self, gate: &AcirArithGate<F>) -> Result<usize, Error> {
+        if gate.is_trivial() {
+            return Ok(0);
+        }
+
+        let plan = self.allocator.plan(gate)?;
+        Ok(gate_constraints(gate, &plan, self.allocator.encoding)
+            .iter()
+            .filter(|constraint| self.dedup.is_new(*constraint))
+            .count())
+    }
+
+    pub(crate) fn num_products(&self) -> usize {
+        self.allocator.template.products.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type F = halo2curves::bn256::Fr;
+
+    // 2 * w0 * w1 + w0 * w2 - w3 = 0
+    fn gate() -> AcirArithGate<F> {
+        AcirArithGate {
+            mul_terms: vec![
+                (F::from(2), WitnessID(0), WitnessID(1)),
+                (F::one(), WitnessID(0), WitnessID(2)),
+            ],
+            add_terms: vec![(-F::one(), WitnessID(3))],
+            constant_term: F::zero(),
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let template = R1CSTemplate::generate(&[gate()], 4, R1CSEncoding::ProductWitness).unwrap();
+
+        assert_eq!(template.constraints.len(), 3);
+        assert_eq!(template.num_witness().unwrap(), 6);
+        assert_eq!(
+            template.products,
+            vec![
+                Product {
+                    id: WitnessID(4),
+                    left: WitnessID(0),
+                    right: WitnessID(1),
+                },
+                Product {
+                    id: WitnessID(5),
+                    left: WitnessID(0),
+                    right: WitnessID(2),
+                },
+            ]
+        );
+
+        let mut witness: BTreeMap<_, _> = [2u64, 3, 5, 22]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (WitnessID(i as u32), F::from(v)))
+            .collect();
+        template.extend_witness(&mut witness).unwrap();
+        assert_eq!(witness[&WitnessID(4)], F::from(6));
+        assert_eq!(witness[&WitnessID(5)], F::from(10));
+
+        let mut full = Witness(witness.clone());
+        template.check_witness(&full).unwrap();
+        let dense: Vec<F> = witness.values().cloned().collect();
+        template.check_dense_witness(&dense).unwrap();
+        full.0.insert(WitnessID(3), F::from(23));
+        assert!(matches!(
+            template.check_witness(&full),
+            Err(Error::UnsatisfiedConstraint(_))
+        ));
+
+        witness.remove(&WitnessID(2));
+        assert!(matches!(
+            template.extend_witness(&mut witness),
+            Err(Error::MissingWitness(2))
+        ));
+    }
+
+    #[test]
+    fn test_quadratic_encoding() {
+        let mut single = gate();
+        single.mul_terms.pop();
+
+        let template =
+            R1CSTemplate::generate(&[single, gate()], 4, R1CSEncoding::Quadratic).unwrap();
+
+        // one constraint for the single mul term gate, three for the other
+        assert_eq!(template.constraints.len(), 4);
+        assert_eq!(template.products.len(), 2);
+    }
+
+    #[test]
+    fn test_repeated_products() {
+        // w1 * w0 - w3 = 0, sharing the product w0 * w1 with `gate`
+        let swapped = AcirArithGate {
+            mul_terms: vec![(F::one(), WitnessID(1), WitnessID(0))],
+            add_terms: vec![(-F::one(), WitnessID(3))],
+            constant_term: F::zero(),
+        };
+
+        let template =
+            R1CSTemplate::generate(&[gate(), swapped], 4, R1CSEncoding::ProductWitness).unwrap();
+
+        assert_eq!(template.products.len(), 2);
+        assert_eq!(template.num_witness().unwrap(), 6);
+        // two products and two linear constraints
+        assert_eq!(template.constraints.len(), 4);
+    }
+
+    #[test]
+    fn test_square() {
+        // 3 * w0 * w0 - w3 + 1 = 0
+        let square = AcirArithGate {
+            mul_terms: vec![(F::from(3), WitnessID(0), WitnessID(0))],
+            add_terms: vec![(-F::one(), WitnessID(3))],
+            constant_term: F::one(),
+        };
+
+        let template = R1CSTemplate::generate(&[square], 4, R1CSEncoding::ProductWitness).unwrap();
+        assert_eq!(template.constraints.len(), 1);
+        assert!(template.products.is_empty());
+
+        // a square among several mul terms still gets a product witness
+        let mut gate = gate();
+        gate.mul_terms.push((F::one(), WitnessID(2), WitnessID(2)));
+
+        let template = R1CSTemplate::generate(&[gate], 4, R1CSEncoding::ProductWitness).unwrap();
+        assert!(template.products[2].is_square());
+
+        let mut witness: BTreeMap<_, _> = [2u64, 3, 5, 47]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (WitnessID(i as u32), F::from(v)))
+            .collect();
+        template.extend_witness(&mut witness).unwrap();
+        assert_eq!(witness[&WitnessID(6)], F::from(25));
+    }
+
+    #[test]
+    fn test_normalize() {
+        // w1 - w0 + 2 * w1 + w0 + 3 - 3
+        let lc = LC(vec![
+            var(F::one(), WitnessID(1)),
+            var(-F::one(), WitnessID(0)),
+            var(F::from(2), WitnessID(1)),
+            var(F::one(), WitnessID(0)),
+            Term::Const(F::from(3)),
+            Term::Const(-F::from(3)),
+        ]);
+
+        let normalized = normalize(lc);
+        assert_eq!(normalized.0.len(), 1);
+        assert!(matches!(
+            normalized.0[0],
+            Term::LC { coefficient, var_id } if coefficient == F::from(3) && var_id == WitnessID(1)
+        ));
+    }
+
+    #[test]
+    fn test_dedup() {
+        // w3 - w0 = 0 and 2 * w0 - 2 * w3 = 0
+        let linear = |coeff: F| AcirArithGate {
+            mul_terms: vec![],
+            add_terms: vec![(coeff, WitnessID(3)), (-coeff, WitnessID(0))],
+            constant_term: F::zero(),
+        };
+
+        let gates = [gate(), gate(), linear(F::one()), linear(-F::from(2))];
+        let template = R1CSTemplate::generate(&gates, 4, R1CSEncoding::ProductWitness).unwrap();
+
+        // the constraints of `gate` once, and one linear constraint
+        assert_eq!(template.constraints.len(), 4);
+    }
+
+    #[test]
+    fn test_too_many_witnesses() {
+        // the first product takes the last index, the second would make
+        // u32::MAX + 1 witnesses
+        assert!(matches!(
+            R1CSTemplate::generate(&[gate()], u32::MAX - 1, R1CSEncoding::ProductWitness),
+            Err(Error::TooManyWitnesses(next)) if next == u32::MAX as u64
+        ));
+
+        let template = R1CSTemplate::<F> {
+            num_circuit_witness: u32::MAX,
+            products: vec![Product {
+                id: WitnessID(0),
+                left: WitnessID(0),
+                right: WitnessID(0),
+            }],
+            constraints: vec![],
+        };
+        assert!(matches!(
+            template.num_witness(),
+            Err(Error::TooManyWitnesses(next)) if next == 1 << 32
+        ));
+    }
+
+    #[test]
+    fn test_generate_streaming() {
+        let gates = [gate(), gate()];
+
+        let mut count = 0;
+        let template =
+            R1CSTemplate::generate_streaming(&gates, 4, R1CSEncoding::ProductWitness, |_| {
+                count += 1
+            })
+            .unwrap();
+
+        assert!(template.constraints.is_empty());
+        assert_eq!(template.products.len(), 2);
+        assert_eq!(
+            count,
+            R1CSTemplate::generate(&gates, 4, R1CSEncoding::ProductWitness)
+                .unwrap()
+                .constraints
+                .len()
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_generate_parallel() {
+        let mut square = gate();
+        square.mul_terms = vec![(F::one(), WitnessID(2), WitnessID(2))];
+        let gates = [gate(), square, gate()];
+
+        for encoding in [R1CSEncoding::ProductWitness, R1CSEncoding::Quadratic] {
+            let sequential = R1CSTemplate::generate(&gates, 4, encoding).unwrap();
+            let parallel = R1CSTemplate::generate_parallel(&gates, 4, encoding).unwrap();
+
+            assert_eq!(parallel.products, sequential.products);
+            assert_eq!(
+                serde_json::to_value(&parallel.constraints).unwrap(),
+                serde_json::to_value(&sequential.constraints).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Renumbering of witnesses.
+//!
+//! ACVM always solves in the ACIR numbering. A `Renumbering` stored in the
+//! circuit structure maps it to the numbering of the R1CS, which is also the
+//! one of the IO, the input files and the produced witnesses.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use ivc_program::{program::WitnessID, witness::Witness};
+use serde::{Deserialize, Serialize};
+
+/// Old witness to new witness, witnesses missing here are dropped
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Renumbering(pub BTreeMap<WitnessID, WitnessID>);
+
+impl Renumbering {
+    pub fn get(&self, id: WitnessID) -> Option<WitnessID> {
+        self.0.get(&id).cloned()
+    }
+
+    pub fn inverse(&self) -> BTreeMap<WitnessID, WitnessID> {
+        self.0.iter().map(|(&old, &new)| (new, old)).collect()
+    }
+
+    /// Apply `self`, then `next`
+    pub fn then(&self, next: &Renumbering) -> Self {
+        Self(
+            self.0
+                .iter()
+                .filter_map(|(&old, &id)| Some((old, next.get(id)?)))
+                .collect(),
+        )
+    }
+
+    pub fn apply_to_set(&self, ids: &BTreeSet<WitnessID>) -> BTreeSet<WitnessID> {
+        ids.iter().filter_map(|id| self.get(*id)).collect()
+    }
+
+    /// Renumber an existing witness, e.g. an input file
+    pub fn apply<F: Clone>(&self, witness: &Witness<F>) -> Witness<F> {
+        Witness(
+            witness
+                .0
+                .iter()
+                .filter_map(|(id, value)| Some((self.get(*id)?, value.clone())))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renumbering(pairs: &[(u32, u32)]) -> Renumbering {
+        Renumbering(
+            pairs
+                .iter()
+                .map(|&(old, new)| (WitnessID(old), WitnessID(new)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_then() {
+        let first = renumbering(&[(1, 0), (3, 1), (4, 2)]);
+        let second = renumbering(&[(0, 1), (2, 0)]);
+
+        assert_eq!(first.then(&second), renumbering(&[(1, 1), (4, 0)]));
+        assert_eq!(first.inverse(), renumbering(&[(0, 1), (1, 3), (2, 4)]).0);
+    }
+}
@@ -0,0 +1,283 @@
+//! Standard layout of the files of an execution.
+//!
+//! The outputs of a Nargo project go to `target/noir-ivc`, or the directory
+//! of `with_output_dir`:
+//! - `noir_ivc_program.json`, the compiled `CircuitStructure`
+//! - `ivc_program.json`, the `IVCProgram`
+//! - `io_k.json`, the public input of step `k > 0` as a witness
+//! - `step_k.wit` and `step_k.res`, the witness and the `ExecutionResult` of
+//!   step `k`
+//! - `step_k.nonce`, with `with_blinding`, the nonce of step `k`
+//! - `proof.bin` and `proof.json`, a proof of the steps and what it proves,
+//!   written by `noir-ivc prove`
+//!
+//! Every file is JSON. With `with_blinding`, the witnesses and results are
+//! stored blinded (see `Blinding`) and unblinded when read.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use acvm::{acir::acir_field::GenericFieldElement, blackbox_solver::BlackBoxFunctionSolver};
+use ark_ff::PrimeField as ArkPrimeField;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::PrimeField;
+use ivc_program::{program::IVCProgram, witness::Witness};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    blinding::{Blinding, BlindingNonce},
+    execute::UnexecutedCircuit,
+    functions::{compile, execute_steps, load_program_from_file},
+    program::CircuitStructure,
+    Error, ExecutionResult,
+};
+
+/// Write `data` as JSON, as every file of the layout
+pub fn write_json<T: Serialize>(path: &Path, data: &T) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, data).map_err(std::io::Error::from)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read a file of the layout
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader).map_err(std::io::Error::from)?)
+}
+
+/// Compiles and executes the program of a Nargo project, reading and
+/// writing the files of the standard layout
+pub struct ExecutionSession {
+    project_dir: PathBuf,
+    output_dir: Option<PathBuf>,
+    blinding: Option<Blinding>,
+}
+
+impl ExecutionSession {
+    pub fn new<P: AsRef<Path>>(project_dir: P) -> Self {
+        Self {
+            project_dir: project_dir.as_ref().to_path_buf(),
+            output_dir: None,
+            blinding: None,
+        }
+    }
+
+    /// Write the outputs to `output_dir` instead of `target/noir-ivc`
+    pub fn with_output_dir<P: AsRef<Path>>(mut self, output_dir: P) -> Self {
+        self.output_dir = Some(output_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Blind the witnesses and results with `key`, which is needed to read
+    /// them back
+    pub fn with_blinding(mut self, key: [u8; 32]) -> Self {
+        self.blinding = Some(Blinding::new(key));
+        self
+    }
+
+    /// The package name from `Nargo.toml`
+    pub fn package_name(&self) -> Result<String, Error> {
+        let manifest = std::fs::read_to_string(self.project_dir.join("Nargo.toml"))?;
+
+        // Note: the first `name` key, i.e. the one of `[package]`
+        manifest
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.trim() == "name")
+            .map(|(_, value)| value.trim().trim_matches('"').to_string())
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "no package name").into()
+            })
+    }
+
+    /// The program compiled by `nargo compile`
+    pub fn artifact_path(&self) -> Result<PathBuf, Error> {
+        Ok(self
+            .project_dir
+            .join("target")
+            .join(format!("{}.json", self.package_name()?)))
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        match &self.output_dir {
+            Some(output_dir) => output_dir.clone(),
+            None => self.project_dir.join("target").join("noir-ivc"),
+        }
+    }
+
+    pub fn structure_path(&self) -> PathBuf {
+        self.output_dir().join("noir_ivc_program.json")
+    }
+
+    pub fn ivc_program_path(&self) -> PathBuf {
+        self.output_dir().join("ivc_program.json")
+    }
+
+    pub fn io_path(&self, step_num: u64) -> PathBuf {
+        self.output_dir().join(format!("io_{}.json", step_num))
+    }
+
+    pub fn witness_path(&self, step_num: u64) -> PathBuf {
+        self.output_dir().join(format!("step_{}.wit", step_num))
+    }
+
+    pub fn result_path(&self, step_num: u64) -> PathBuf {
+        self.output_dir().join(format!("step_{}.res", step_num))
+    }
+
+    pub fn nonce_path(&self, step_num: u64) -> PathBuf {
+        self.output_dir().join(format!("step_{}.nonce", step_num))
+    }
+
+    pub fn proof_path(&self) -> PathBuf {
+        self.output_dir().join("proof.bin")
+    }
+
+    pub fn proof_claim_path(&self) -> PathBuf {
+        self.output_dir().join("proof.json")
+    }
+
+    /// Compile the artifact, writing the structure and the IVC program
+    pub fn compile<F, AF>(&self) -> Result<(CircuitStructure<F>, IVCProgram<F>), Error>
+    where
+        F: PrimeField,
+        AF: ArkPrimeField,
+        CircuitStructure<F>: Serialize,
+        IVCProgram<F>: Serialize,
+    {
+        let noir_circuit = load_program_from_file::<AF, _>(self.artifact_path()?)?;
+        let (structure, program) = compile::<F, AF>(noir_circuit)?;
+
+        std::fs::create_dir_all(self.output_dir())?;
+        write_json(&self.structure_path(), &structure)?;
+        write_json(&self.ivc_program_path(), &program)?;
+
+        Ok((structure, program))
+    }
+
+    /// Execute a step per private input, writing the witness, the result and
+    /// the next public input of every step. Stops at the first failed step
+    pub fn execute<F, AF>(
+        &self,
+        structure: impl Into<Arc<CircuitStructure<F>>>,
+        first_public_input: Witness<F>,
+        start_step_num: u64,
+        private_inputs: impl Iterator<Item = Witness<F>>,
+    ) -> Result<Vec<ExecutionResult<F>>, Error>
+    where
+        F: PrimeField + Serialize,
+        AF: ArkPrimeField,
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let structure = structure.into();
+        let io_profile = structure.program.io.clone();
+        std::fs::create_dir_all(self.output_dir())?;
+
+        execute_steps::<F, AF>(
+            structure,
+            first_public_input,
+            start_step_num,
+            private_inputs,
+        )
+        .map(|step| {
+            let (exe_res, witness, next) = step?;
+            let step_num = exe_res.iteration_number;
+
+            write_json(&self.io_path(step_num + 1), &next.make_witness(&io_profile))?;
+            match &self.blinding {
+                Some(blinding) => {
+                    let nonce = Blinding::nonce();
+                    write_json(&self.nonce_path(step_num), &nonce)?;
+                    let witness = blinding.blind_witness(&nonce, step_num, &witness);
+                    write_json(&self.witness_path(step_num), &witness)?;
+                    write_json(
+                        &self.result_path(step_num),
+                        &blinding.blind_result(&nonce, &exe_res),
+                    )?;
+                }
+                None => {
+                    write_json(&self.witness_path(step_num), &witness)?;
+                    write_json(&self.result_path(step_num), &exe_res)?;
+                }
+            }
+
+            Ok(exe_res)
+        })
+        .collect()
+    }
+
+    /// Continue an interrupted chain at step `step_num > 0`, from the stored
+    /// structure and `io_k.json`. Fails if the result of the previous step
+    /// was produced by another program
+    pub fn resume_from_artifacts<F>(&self, step_num: u64) -> Result<UnexecutedCircuit<F>, Error>
+    where
+        F: PrimeField + DeserializeOwned,
+        CircuitStructure<F>: DeserializeOwned,
+    {
+        let structure = self.read_structure::<F>()?;
+        let public_input: Witness<F> = read_json(&self.io_path(step_num))?;
+
+        if let Some(previous) = step_num.checked_sub(1) {
+            let result = self.read_result::<F>(previous)?;
+            if let Some(expected) = result.program_digest {
+                let found = structure.digest()?;
+                if expected != found {
+                    return Err(Error::DigestMismatch { expected, found });
+                }
+            }
+        }
+
+        Ok(UnexecutedCircuit::new(step_num, public_input, structure))
+    }
+
+    /// Fails if the structure was made over another field than `F`
+    pub fn read_structure<F>(&self) -> Result<CircuitStructure<F>, Error>
+    where
+        F: PrimeField,
+        CircuitStructure<F>: DeserializeOwned,
+    {
+        let structure: CircuitStructure<F> = read_json(&self.structure_path())?;
+        structure.check_curve()?;
+        Ok(structure)
+    }
+
+    pub fn read_ivc_program<F>(&self) -> Result<IVCProgram<F>, Error>
+    where
+        IVCProgram<F>: DeserializeOwned,
+    {
+        read_json(&self.ivc_program_path())
+    }
+
+    pub fn read_witness<F: PrimeField + DeserializeOwned>(
+        &self,
+        step_num: u64,
+    ) -> Result<Witness<F>, Error> {
+        let witness = read_json(&self.witness_path(step_num))?;
+        Ok(match &self.blinding {
+            Some(blinding) => {
+                let nonce: BlindingNonce = read_json(&self.nonce_path(step_num))?;
+                blinding.unblind_witness(&nonce, step_num, &witness)
+            }
+            None => witness,
+        })
+    }
+
+    pub fn read_result<F: PrimeField + DeserializeOwned>(
+        &self,
+        step_num: u64,
+    ) -> Result<ExecutionResult<F>, Error> {
+        let result = read_json(&self.result_path(step_num))?;
+        Ok(match &self.blinding {
+            Some(blinding) => {
+                let nonce: BlindingNonce = read_json(&self.nonce_path(step_num))?;
+                blinding.unblind_result(&nonce, &result)
+            }
+            None => result,
+        })
+    }
+}
@@ -0,0 +1,278 @@
+//! A sonobe `FCircuit` over a compiled circuit.
+//!
+//! The state is the public inputs, mapped to the public outputs, and the
+//! external inputs are the private inputs, all in witness order. Both the
+//! native step and the witness of the constraints are solved by ACVM.
+//!
+//! A chain is folded by any folding scheme of sonobe (see `fold_steps`), and
+//! `fold_with` picks Nova or HyperNova at runtime to compare them on the
+//! same program. HyperNova folds the CCS of the step circuit, the one of
+//! `export::ccs` extended with the folding verifier.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use acvm::{acir::acir_field::GenericFieldElement, blackbox_solver::BlackBoxFunctionSolver};
+use ark_ec::CurveGroup;
+use ark_ff::PrimeField as ArkPrimeField;
+use ark_r1cs_std::{
+    eq::EqGadget,
+    fields::fp::{AllocatedFp, FpVar},
+    groups::CurveVar,
+    R1CSVar, ToConstraintFieldGadget,
+};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+use ark_std::rand::RngCore;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::PrimeField;
+use folding_schemes::{
+    commitment::CommitmentScheme,
+    folding::{circuits::CF2, hypernova::HyperNova, nova::Nova},
+    frontend::FCircuit,
+    FoldingScheme,
+};
+use ivc_program::{input::IO, program::WitnessID, witness::Witness};
+
+use crate::{
+    ark::{enforce_r1cs, value},
+    execute_step,
+    field::{ff_to_ark_prime_field, generic_ark_ff_to_prime_field},
+    layout::IOLayout,
+    program::CircuitStructure,
+    Error,
+};
+
+impl<F: PrimeField> CircuitStructure<F> {
+    /// Execute step `i` on field elements of the ACVM field, returning the
+    /// witness of the step
+    fn execute_ark<AF: ArkPrimeField>(
+        &self,
+        i: usize,
+        z_i: &[AF],
+        external_inputs: &[AF],
+    ) -> Result<Witness<F>, Error>
+    where
+        Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+    {
+        let to_witness = |ids: &BTreeSet<WitnessID>, values: &[AF]| {
+            let values = values
+                .iter()
+                .map(|x| generic_ark_ff_to_prime_field(&GenericFieldElement::from_repr(*x)))
+                .collect::<Result<_, _>>()?;
+            let ids: Vec<WitnessID> = ids.iter().cloned().collect();
+            IOLayout::make_witness(&ids, &IO(values))
+        };
+
+        let io = &self.program;
+        let public_input = to_witness(&io.public_inputs, z_i)?;
+        let private_input = to_witness(&io.private_inputs, external_inputs)?;
+
+        let (_, witness, _) =
+            execute_step::<F, AF>(self.clone(), public_input, private_input, i as u64)?;
+
+        Ok(witness)
+    }
+}
+
+fn to_variable<AF: ArkPrimeField>(
+    cs: &ConstraintSystemRef<AF>,
+    var: &FpVar<AF>,
+) -> Result<Variable, SynthesisError> {
+    match var {
+        FpVar::Var(var) => Ok(var.variable),
+        FpVar::Constant(_) => {
+            let allocated = AllocatedFp::new_witness(cs.clone(), || var.value())?;
+            FpVar::Var(allocated.clone()).enforce_equal(var)?;
+            Ok(allocated.variable)
+        }
+    }
+}
+
+impl<F: PrimeField, AF: ArkPrimeField> FCircuit<AF> for CircuitStructure<F>
+where
+    Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+{
+    /// Note: the structure is expected to be compiled
+    type Params = Self;
+
+    fn new(params: Self::Params) -> Result<Self, folding_schemes::Error> {
+        Ok(params)
+    }
+
+    fn state_len(&self) -> usize {
+        self.program.public_inputs.len()
+    }
+
+    fn external_inputs_len(&self) -> usize {
+        self.program.private_inputs.len()
+    }
+
+    fn step_native(
+        &self,
+        i: usize,
+        z_i: Vec<AF>,
+        external_inputs: Vec<AF>,
+    ) -> Result<Vec<AF>, folding_schemes::Error> {
+        let witness = self
+            .execute_ark(i, &z_i, &external_inputs)
+            .map_err(|e| folding_schemes::Error::Other(e.to_string()))?;
+
+        self.program
+            .public_outputs
+            .iter()
+            .map(|id| ff_to_ark_prime_field(&witness.0[id]))
+            .collect::<Result<_, _>>()
+            .map_err(|e| folding_schemes::Error::Other(e.to_string()))
+    }
+
+    fn generate_step_constraints(
+        &self,
+        cs: ConstraintSystemRef<AF>,
+        i: usize,
+        z_i: Vec<FpVar<AF>>,
+        external_inputs: Vec<FpVar<AF>>,
+    ) -> Result<Vec<FpVar<AF>>, SynthesisError> {
+        let io = &self.program;
+
+        // Note: the values are missing while generating the parameters
+        let values = |vars: &[FpVar<AF>]| {
+            vars.iter()
+                .map(|var| var.value())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let witness = match (values(&z_i), values(&external_inputs)) {
+            (Ok(z_i), Ok(external_inputs)) => Some(
+                self.execute_ark(i, &z_i, &external_inputs)
+                    .map_err(|_| SynthesisError::Unsatisfiable)?,
+            ),
+            _ => None,
+        };
+
+        let mut variables = BTreeMap::new();
+        for (id, var) in io
+            .public_inputs
+            .iter()
+            .zip(&z_i)
+            .chain(io.private_inputs.iter().zip(&external_inputs))
+        {
+            variables.insert(*id, to_variable(&cs, var)?);
+        }
+
+        let variables = enforce_r1cs(self, witness.as_ref(), &cs, variables)?;
+
+        Ok(io
+            .public_outputs
+            .iter()
+            .map(|id| {
+                let value = value(witness.as_ref(), id).ok();
+                FpVar::Var(AllocatedFp::new(value, variables[id], cs.clone()))
+            })
+            .collect())
+    }
+}
+
+/// Fold one step per external input from `z_0`, with folding scheme `FS`
+pub fn fold_steps<C1, C2, FS, F>(
+    params: &(FS::ProverParam, FS::VerifierParam),
+    structure: CircuitStructure<F>,
+    z_0: Vec<C1::ScalarField>,
+    external_inputs: impl IntoIterator<Item = Vec<C1::ScalarField>>,
+    mut rng: impl RngCore,
+) -> Result<FS, Error>
+where
+    C1: CurveGroup<BaseField = C2::ScalarField>,
+    C2: CurveGroup,
+    C2::BaseField: ArkPrimeField,
+    F: PrimeField,
+    FS: FoldingScheme<C1, C2, CircuitStructure<F>>,
+    Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<C1::ScalarField>>,
+{
+    let mut folding = FS::init(params, structure, z_0)?;
+    for external_inputs in external_inputs {
+        folding.prove_step(&mut rng, external_inputs, None)?;
+    }
+    Ok(folding)
+}
+
+/// Folding schemes of sonobe `fold_with` can pick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FoldingBackend {
+    Nova,
+    HyperNova,
+}
+
+/// A value for either backend, e.g. its parameters or its folded chain
+#[derive(Clone, Debug)]
+pub enum PerBackend<N, H> {
+    Nova(N),
+    HyperNova(H),
+}
+
+impl<N, H> PerBackend<N, H> {
+    pub fn backend(&self) -> FoldingBackend {
+        match self {
+            Self::Nova(_) => FoldingBackend::Nova,
+            Self::HyperNova(_) => FoldingBackend::HyperNova,
+        }
+    }
+}
+
+/// Implemented by sonobe's Nova only
+pub trait IsNova {}
+
+impl<C1, GC1, C2, GC2, FC, CS1, CS2, const H: bool> IsNova
+    for Nova<C1, GC1, C2, GC2, FC, CS1, CS2, H>
+where
+    C1: CurveGroup,
+    GC1: CurveVar<C1, CF2<C1>> + ToConstraintFieldGadget<CF2<C1>>,
+    C2: CurveGroup,
+    GC2: CurveVar<C2, CF2<C2>>,
+    FC: FCircuit<C1::ScalarField>,
+    CS1: CommitmentScheme<C1, H>,
+    CS2: CommitmentScheme<C2, H>,
+{
+}
+
+/// Implemented by sonobe's HyperNova only
+pub trait IsHyperNova {}
+
+impl<C1, GC1, C2, GC2, FC, CS1, CS2, const MU: usize, const NU: usize, const H: bool> IsHyperNova
+    for HyperNova<C1, GC1, C2, GC2, FC, CS1, CS2, MU, NU, H>
+where
+    C1: CurveGroup,
+    GC1: CurveVar<C1, CF2<C1>> + ToConstraintFieldGadget<CF2<C1>>,
+    C2: CurveGroup,
+    GC2: CurveVar<C2, CF2<C2>>,
+    FC: FCircuit<C1::ScalarField>,
+    CS1: CommitmentScheme<C1, H>,
+    CS2: CommitmentScheme<C2, H>,
+{
+}
+
+/// Same as `fold_steps`, with the backend of `params`: `N` is sonobe's Nova
+/// and `H` its HyperNova
+#[allow(clippy::type_complexity)]
+pub fn fold_with<C1, C2, N, H, F>(
+    params: &PerBackend<(N::ProverParam, N::VerifierParam), (H::ProverParam, H::VerifierParam)>,
+    structure: CircuitStructure<F>,
+    z_0: Vec<C1::ScalarField>,
+    external_inputs: impl IntoIterator<Item = Vec<C1::ScalarField>>,
+    rng: impl RngCore,
+) -> Result<PerBackend<N, H>, Error>
+where
+    C1: CurveGroup<BaseField = C2::ScalarField>,
+    C2: CurveGroup,
+    C2::BaseField: ArkPrimeField,
+    F: PrimeField,
+    N: FoldingScheme<C1, C2, CircuitStructure<F>> + IsNova,
+    H: FoldingScheme<C1, C2, CircuitStructure<F>> + IsHyperNova,
+    Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<C1::ScalarField>>,
+{
+    Ok(match params {
+        PerBackend::Nova(params) => {
+            PerBackend::Nova(fold_steps(params, structure, z_0, external_inputs, rng)?)
+        }
+        PerBackend::HyperNova(params) => {
+            PerBackend::HyperNova(fold_steps(params, structure, z_0, external_inputs, rng)?)
+        }
+    })
+}
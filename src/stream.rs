@@ -0,0 +1,40 @@
+//! Execution as a `Stream`, for async proving services.
+
+use std::sync::Arc;
+
+use acvm::{acir::acir_field::GenericFieldElement, blackbox_solver::BlackBoxFunctionSolver};
+use ark_ff::PrimeField as ArkPrimeField;
+use bn254_blackbox_solver::Bn254BlackBoxSolver;
+use ff::PrimeField;
+use ivc_program::{input::IO, witness::Witness};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+
+use crate::{execute_steps, program::CircuitStructure, Error, ExecutionResult};
+
+/// Same as `execute_steps`, solving on a blocking thread of the tokio
+/// runtime so the steps do not block it. Must be called within a runtime
+pub fn execute_steps_async<F: PrimeField, AF: ArkPrimeField>(
+    circuit: impl Into<Arc<CircuitStructure<F>>> + Send + 'static,
+    first_public_input: Witness<F>,
+    start_step_num: u64,
+    private_inputs: impl Iterator<Item = Witness<F>> + Send + 'static,
+) -> impl Stream<Item = Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>>
+where
+    Bn254BlackBoxSolver: BlackBoxFunctionSolver<GenericFieldElement<AF>>,
+{
+    let (sender, receiver) = mpsc::channel(1);
+
+    // Note: stops at the next step once the stream is dropped
+    tokio::task::spawn_blocking(move || {
+        let steps =
+            execute_steps::<F, AF>(circuit, first_public_input, start_step_num, private_inputs);
+        for step in steps {
+            if sender.blocking_send(step).is_err() {
+                break;
+            }
+        }
+    });
+
+    ReceiverStream::new(receiver)
+}
@@ -0,0 +1,180 @@
+//! Non-uniform folding of a `ProgramSet` with SuperNova.
+//!
+//! Every circuit of the set is a primary circuit of SuperNova, its index
+//! being its program counter. The program counter of SuperNova is bound to
+//! the first element of the state, and the first public output of a step is
+//! the next one, so every step folds into the running instance of the
+//! circuit it selected. The secondary circuit is trivial, with a single zero
+//! as state.
+// Note: nova-snark folds a single circuit, SuperNova is taken from arecibo,
+// its fork with non-uniform IVC
+
+use arecibo::{
+    supernova::{
+        NonUniformCircuit, PublicParams, RecursiveSNARK, StepCircuit, TrivialSecondaryCircuit,
+    },
+    traits::{snark::default_ck_hint, CurveCycleEquipped, Dual, Engine},
+};
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem, SynthesisError};
+use ff::{Field, PrimeField};
+use ivc_program::{input::IO, witness::Witness};
+use nova_snark::traits::circuit::StepCircuit as NovaStepCircuit;
+
+use crate::{nova::NoirStepCircuit, program_set::ProgramSet, Error, ExecutionResult};
+
+pub type SetSecondaryCircuit<E1> = TrivialSecondaryCircuit<<Dual<E1> as Engine>::Scalar>;
+
+/// The circuit of index `index` of a `ProgramSet`
+#[derive(Clone)]
+pub struct SetStepCircuit<F: PrimeField> {
+    pub index: usize,
+    pub circuit: NoirStepCircuit<F>,
+}
+
+impl<F: PrimeField> StepCircuit<F> for SetStepCircuit<F> {
+    fn arity(&self) -> usize {
+        NovaStepCircuit::arity(&self.circuit)
+    }
+
+    fn circuit_index(&self) -> usize {
+        self.index
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        pc: Option<&AllocatedNum<F>>,
+        z: &[AllocatedNum<F>],
+    ) -> Result<(Option<AllocatedNum<F>>, Vec<AllocatedNum<F>>), SynthesisError> {
+        if let (Some(pc), Some(state_pc)) = (pc, z.first()) {
+            cs.enforce(
+                || "program counter of the state",
+                |lc| lc + pc.get_variable(),
+                |lc| lc + CS::one(),
+                |lc| lc + state_pc.get_variable(),
+            );
+        }
+
+        let z_out = NovaStepCircuit::synthesize(&self.circuit, cs, z)?;
+        Ok((z_out.first().cloned(), z_out))
+    }
+}
+
+/// `set` as the primary circuits of SuperNova, starting with the circuit of
+/// index `initial_index`
+pub struct NonUniformProgramSet<'a, F> {
+    pub set: &'a ProgramSet<F>,
+    pub initial_index: usize,
+}
+
+impl<'a, F: PrimeField> NonUniformProgramSet<'a, F> {
+    /// The circuit of index `index`, without witness.
+    /// Note: panics if there is no such circuit, as `primary_circuit`
+    pub fn circuit(&self, index: usize) -> SetStepCircuit<F> {
+        SetStepCircuit {
+            index,
+            circuit: NoirStepCircuit::new(self.set.circuits[index].clone()),
+        }
+    }
+}
+
+impl<'a, E1: CurveCycleEquipped> NonUniformCircuit<E1> for NonUniformProgramSet<'a, E1::Scalar> {
+    type C1 = SetStepCircuit<E1::Scalar>;
+    type C2 = SetSecondaryCircuit<E1>;
+
+    fn num_circuits(&self) -> usize {
+        self.set.len()
+    }
+
+    fn primary_circuit(&self, circuit_index: usize) -> Self::C1 {
+        self.circuit(circuit_index)
+    }
+
+    fn secondary_circuit(&self) -> Self::C2 {
+        Default::default()
+    }
+
+    fn initial_circuit_index(&self) -> usize {
+        self.initial_index
+    }
+}
+
+/// Public parameters for folding the steps of `set`, expected to be compiled
+pub fn create_set_public_params<E1: CurveCycleEquipped>(
+    set: &ProgramSet<E1::Scalar>,
+) -> PublicParams<E1> {
+    let circuits = NonUniformProgramSet {
+        set,
+        initial_index: 0,
+    };
+    PublicParams::setup(&circuits, &*default_ck_hint(), &*default_ck_hint())
+}
+
+/// Fold every step of `steps` in order, as yielded by
+/// `ProgramSet::execute_steps` from `start_state`, with the circuit of each
+pub fn prove_set_steps<E1, I>(
+    set: &ProgramSet<E1::Scalar>,
+    pp: &PublicParams<E1>,
+    start_state: &[E1::Scalar],
+    steps: I,
+) -> Result<RecursiveSNARK<E1>, Error>
+where
+    E1: CurveCycleEquipped,
+    I: IntoIterator<
+        Item = Result<
+            (
+                usize,
+                ExecutionResult<E1::Scalar>,
+                Witness<E1::Scalar>,
+                IO<E1::Scalar>,
+            ),
+            Error,
+        >,
+    >,
+{
+    let initial_index = set
+        .program_counter(&IO(start_state.to_vec()))
+        .ok_or(Error::InvalidProgramCounter(0))?;
+    let circuits = NonUniformProgramSet { set, initial_index };
+    let circuit_secondary = SetSecondaryCircuit::<E1>::default();
+    let z0_secondary = vec![<Dual<E1> as Engine>::Scalar::ZERO];
+
+    let mut recursive_snark: Option<RecursiveSNARK<E1>> = None;
+
+    for step in steps {
+        let (index, result, witness, _) = step?;
+        if index >= set.len() {
+            return Err(Error::InvalidProgramCounter(result.iteration_number));
+        }
+        let mut step_circuit = circuits.circuit(index);
+        step_circuit.circuit = step_circuit.circuit.with_witness(witness);
+
+        if recursive_snark.is_none() {
+            recursive_snark = Some(RecursiveSNARK::new(
+                pp,
+                &circuits,
+                &step_circuit,
+                &circuit_secondary,
+                start_state,
+                &z0_secondary,
+            )?);
+        }
+
+        let snark = recursive_snark.as_mut().unwrap();
+        snark.prove_step(pp, &step_circuit, &circuit_secondary)?;
+    }
+
+    recursive_snark.ok_or(Error::InvalidInput)
+}
+
+/// Verify `proof` of the steps of a set from `start_state`, returning the
+/// state after the last step
+pub fn verify_set_steps<E1: CurveCycleEquipped>(
+    proof: &RecursiveSNARK<E1>,
+    pp: &PublicParams<E1>,
+    start_state: &[E1::Scalar],
+) -> Result<Vec<E1::Scalar>, Error> {
+    let z0_secondary = vec![<Dual<E1> as Engine>::Scalar::ZERO];
+    let (state, _) = proof.verify(pp, start_state, &z0_secondary)?;
+    Ok(state)
+}
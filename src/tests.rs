@@ -1,10 +1,23 @@
 use bellpepper_core::{test_cs::TestConstraintSystem, ConstraintSystem};
 use ff::PrimeField;
-use ivc_program::{input::IO, program::IVCProgram, witness::Witness, Step};
+use ivc_program::{
+    input::IO,
+    program::{IOProfile, IVCProgram, WitnessID},
+    witness::Witness,
+    Step,
+};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{fs::File, path::Path};
 
-use crate::{compile, execute_steps, load_circuit_from_file, program::CircuitStructure};
+use crate::{
+    compile, compile_with_limits, execute_n_steps, execute_step, execute_steps, execute_steps_from,
+    execute_steps_to_dir, export, gate::AcirArithGate, import, inputs::read_io, inspect_file,
+    layout::IOLayout, load_abi_from_file, load_circuit_from_file, load_program_from_file,
+    program::CircuitStructure, program_digest, r1cs::R1CSEncoding, replay, run_to_end,
+    verify_chain, BatchExecutor, Bn254BlackBoxSolver, CompileLimits, Curve, Error,
+    ExecutionCheckpoint, ExecutionPlan, ExecutionResult, ExecutionSession, NoOracle, Phase,
+    ProgramSet, SourceLocation, Transcript, UnexecutedCircuit,
+};
 
 #[inline]
 fn read<T: DeserializeOwned>(path: &str) -> T {
@@ -61,6 +74,15 @@ fn test_compile_and_execute() {
 
         let (circuit_structure, ivc_program) = compile::<F, AF>(noir_circuit).unwrap();
 
+        assert_eq!(
+            circuit_structure.program.num_witness,
+            ivc_program.num_witness
+        );
+        assert_eq!(
+            circuit_structure.num_constraints().unwrap(),
+            ivc_program.r1cs_constraints.len()
+        );
+
         write(NOIR_IVC_PROGRAM_PATH, &circuit_structure);
 
         write(IVC_PROGRAM_PATH, &ivc_program);
@@ -100,6 +122,124 @@ fn test_compile_and_execute() {
     });
 }
 
+#[test]
+fn test_session_blinding() {
+    // Note: a copy of the project, so the files of the other tests stay
+    let project = std::env::temp_dir().join("noir_ivc_session_blinding");
+    std::fs::create_dir_all(project.join("target")).unwrap();
+    std::fs::copy("test_folder/invert/Nargo.toml", project.join("Nargo.toml")).unwrap();
+    std::fs::copy(NOIR_PROGRAM_PATH, project.join("target/invert.json")).unwrap();
+
+    let session = ExecutionSession::new(&project).with_blinding([7; 32]);
+    let (structure, _) = session.compile::<F, AF>().unwrap();
+    let io_profile = structure.program.io.clone();
+    let execute = || {
+        session
+            .execute::<F, AF>(
+                structure.clone(),
+                read_io::<F, _>(INPUT_PATHS[0])
+                    .unwrap()
+                    .make_witness(&io_profile),
+                0,
+                HINT_PATHS
+                    .into_iter()
+                    .map(|path| read_io::<F, _>(path).unwrap().make_witness(&io_profile)),
+            )
+            .unwrap()
+    };
+
+    let results = execute();
+    let blinded: Witness<F> = read(session.witness_path(0).to_str().unwrap());
+    let private_input = &results[0].private_input.0;
+    assert!(private_input
+        .iter()
+        .all(|(id, value)| blinded.0[id] != *value));
+
+    let witness = session.read_witness::<F>(0).unwrap();
+    assert!(private_input
+        .iter()
+        .all(|(id, value)| witness.0[id] == *value));
+    let result = session.read_result::<F>(1).unwrap();
+    assert_eq!(result.private_input.0, results[1].private_input.0);
+    assert_eq!(result.public_output.0, results[1].public_output.0);
+
+    // a rerun is blinded with another nonce
+    execute();
+    let reblinded: Witness<F> = read(session.witness_path(0).to_str().unwrap());
+    assert_ne!(reblinded.0, blinded.0);
+    assert_eq!(session.read_witness::<F>(0).unwrap().0, witness.0);
+
+    let other_key = ExecutionSession::new(&project).with_blinding([8; 32]);
+    assert_ne!(other_key.read_witness::<F>(0).unwrap().0, witness.0);
+}
+
+#[test]
+fn test_session_compile_and_execute() {
+    let layout = ExecutionSession::new("test_folder/invert");
+    assert_eq!(
+        layout.artifact_path().unwrap(),
+        Path::new(NOIR_PROGRAM_PATH)
+    );
+    assert_eq!(layout.structure_path(), Path::new(NOIR_IVC_PROGRAM_PATH));
+    assert_eq!(layout.witness_path(1), Path::new(WITNESS_PATHS[1]));
+    assert_eq!(layout.result_path(1), Path::new(EXECUTION_RES_PATHS[1]));
+    assert_eq!(layout.io_path(1), Path::new(INPUT_PATHS[1]));
+
+    // Note: a copy of the project, so the files of the other tests stay
+    let project = std::env::temp_dir().join("noir_ivc_session_compile_and_execute");
+    std::fs::create_dir_all(project.join("target")).unwrap();
+    std::fs::copy("test_folder/invert/Nargo.toml", project.join("Nargo.toml")).unwrap();
+    std::fs::copy(NOIR_PROGRAM_PATH, project.join("target/invert.json")).unwrap();
+    let session = ExecutionSession::new(&project);
+
+    // 1. compile
+    {
+        let (circuit_structure, ivc_program) = session.compile::<F, AF>().unwrap();
+
+        assert_eq!(
+            circuit_structure.program.num_witness,
+            ivc_program.num_witness
+        );
+        assert_eq!(
+            circuit_structure.num_constraints().unwrap(),
+            ivc_program.r1cs_constraints.len()
+        );
+    }
+
+    // 2. execute
+    let circuit: CircuitStructure<F> = session.read_structure().unwrap();
+    let io_profile = circuit.program.io.clone();
+    let results = session
+        .execute::<F, AF>(
+            circuit,
+            read_io::<F, _>(INPUT_PATHS[0])
+                .unwrap()
+                .make_witness(&io_profile),
+            0,
+            HINT_PATHS
+                .into_iter()
+                .map(|path| read_io::<F, _>(path).unwrap().make_witness(&io_profile)),
+        )
+        .unwrap();
+
+    assert_eq!(results.len(), HINT_PATHS.len());
+    let result: ExecutionResult<F> = session.read_result(1).unwrap();
+    assert_eq!(result.public_output.0, results[1].public_output.0);
+
+    // 3. resume the chain at step 1 and execute it again
+    let mut resumed = session.resume_from_artifacts::<F>(1).unwrap();
+    assert_eq!(resumed.iteration_number, 1);
+    let hint: IO<F> = read_io(HINT_PATHS[1]).unwrap();
+    let (result, _) = resumed
+        .execute_ref::<AF, _, _>(
+            hint.make_witness(&io_profile),
+            &mut NoOracle,
+            &Bn254BlackBoxSolver,
+        )
+        .unwrap();
+    assert_eq!(result.public_output.0, results[1].public_output.0);
+}
+
 #[test]
 fn test_compile_execute_cs() {
     test_compile_and_execute();
@@ -130,3 +270,1884 @@ fn test_compile_execute_cs() {
         assert!(cs.is_satisfied());
     }
 }
+
+/// The public input of the first step of the invert circuit and the private
+/// input of every step, as witnesses of `io_profile`
+fn invert_inputs(io_profile: &IOProfile) -> (Witness<F>, Vec<Witness<F>>) {
+    let public_input = read_io::<F, _>(INPUT_PATHS[0])
+        .unwrap()
+        .make_witness(io_profile);
+    let hints = HINT_PATHS
+        .into_iter()
+        .map(|path| read_io::<F, _>(path).unwrap().make_witness(io_profile))
+        .collect();
+
+    (public_input, hints)
+}
+
+/// The compiled invert circuit with its inputs (see `invert_inputs`)
+fn invert_circuit() -> (CircuitStructure<F>, Witness<F>, Vec<Witness<F>>) {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+    let (public_input, hints) = invert_inputs(&circuit.program.io);
+
+    (circuit, public_input, hints)
+}
+
+/// Execute the first step of `circuit`, returning its witness
+fn execute_first_step(circuit: CircuitStructure<F>) -> Witness<F> {
+    let (public_input, mut hints) = invert_inputs(&circuit.program.io);
+
+    let (_, witness, _) =
+        execute_step::<F, AF>(circuit, public_input, hints.swap_remove(0), 0).unwrap();
+
+    witness
+}
+
+/// Execute the first step of `circuit` and check it against `program`
+fn check_first_step(circuit: CircuitStructure<F>, program: IVCProgram<F>) {
+    let witness = execute_first_step(circuit);
+    let step = Step { witness, program };
+
+    let mut cs = TestConstraintSystem::<F>::new();
+    step.prove(cs.namespace(|| "prove")).unwrap();
+    assert!(cs.is_satisfied());
+}
+
+#[test]
+fn test_quadratic_encoding() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    let mut circuit = circuit.with_encoding(R1CSEncoding::Quadratic);
+    let quadratic_program = circuit.compile().unwrap();
+    assert!(quadratic_program.r1cs_constraints.len() < program.r1cs_constraints.len());
+
+    check_first_step(circuit, quadratic_program);
+}
+
+#[test]
+fn test_fold_constants() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    circuit.fold_constants();
+    let folded_program = circuit.compile().unwrap();
+    assert!(folded_program.r1cs_constraints.len() <= program.r1cs_constraints.len());
+
+    check_first_step(circuit, folded_program);
+}
+
+#[test]
+fn test_prune() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    let remap = circuit.prune();
+    let pruned_program = circuit.compile().unwrap();
+    assert!(pruned_program.num_witness <= program.num_witness);
+    assert!(circuit.padding.is_empty());
+
+    // the renumbering is monotone, so IO vectors keep their order
+    let input: IO<u128> = read(INPUT_PATHS[0]);
+    let input: IO<F> = IO(input.0.iter().map(|x| F::from_u128(*x)).collect());
+    assert_eq!(
+        remap.apply(&input.make_witness(&program.io)).0,
+        input.make_witness(&circuit.program.io).0
+    );
+
+    check_first_step(circuit, pruned_program);
+}
+
+#[test]
+fn test_renumber_io_first() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    circuit.renumber_io_first();
+    let renumbered_program = circuit.compile().unwrap();
+    assert_eq!(renumbered_program.num_witness, program.num_witness);
+
+    let public: Vec<u32> = circuit
+        .program
+        .public_inputs
+        .iter()
+        .chain(circuit.program.public_outputs.iter())
+        .map(|id| id.0)
+        .collect();
+    assert_eq!(public, (0..public.len() as u32).collect::<Vec<_>>());
+
+    check_first_step(circuit, renumbered_program);
+}
+
+#[test]
+fn test_abi_layout() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+
+    let abi = load_abi_from_file(NOIR_PROGRAM_PATH).unwrap();
+    circuit.set_abi(&abi).unwrap();
+    let layout = circuit.layout.clone().unwrap();
+
+    // fn main(x: [Field; 2], y: pub [Field; 2]) -> pub [Field; 2]
+    assert_eq!(layout.parameters.len(), 2);
+    assert_eq!(layout.parameters[0].0, "x");
+    assert_eq!(layout.private_inputs, layout.parameters[0].1);
+    assert_eq!(layout.public_inputs, layout.parameters[1].1);
+    assert_eq!(layout.public_outputs.len(), 2);
+
+    let input: IO<u128> = read(INPUT_PATHS[0]);
+    let input: IO<F> = IO(input.0.iter().map(|x| F::from_u128(*x)).collect());
+    assert_eq!(
+        IOLayout::make_witness(&layout.public_inputs, &input)
+            .unwrap()
+            .0,
+        input.make_witness(&circuit.program.io).0
+    );
+
+    // the layout follows renumbering
+    circuit.renumber_io_first();
+    assert_eq!(
+        circuit.layout.as_ref().unwrap().public_inputs,
+        circuit
+            .program
+            .public_inputs
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>()
+    );
+
+    let program = circuit.compile().unwrap();
+    check_first_step(circuit, program);
+}
+
+#[test]
+fn test_export_circom_r1cs() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    let mut bytes = vec![];
+    export::write_circom_r1cs(&circuit, &mut bytes).unwrap();
+
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+    assert_eq!(&bytes[..4], b"r1cs");
+    assert_eq!(u32_at(4), 1);
+    assert_eq!(u32_at(8), 3);
+
+    // header section: type, size, n8, prime, then the counts
+    assert_eq!(u32_at(12), 1);
+    assert_eq!(u32_at(24), 32);
+    let counts = 28 + 32;
+    assert_eq!(u32_at(counts), program.num_witness + 1);
+    assert_eq!(u32_at(counts + 4), program.public_outputs.len() as u32);
+    assert_eq!(u32_at(counts + 8), program.public_inputs.len() as u32);
+    assert_eq!(u32_at(counts + 12), program.private_inputs.len() as u32);
+    assert_eq!(u32_at(counts + 24), program.r1cs_constraints.len() as u32);
+}
+
+#[test]
+fn test_export_wtns() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    let witness = execute_first_step(circuit.clone());
+    let wires = export::to_wire_order(&circuit, &witness).unwrap();
+    assert_eq!(wires.len(), program.num_witness as usize);
+
+    // the first wires hold the public outputs
+    let first_output = program.public_outputs.iter().next().unwrap();
+    assert_eq!(wires.0[&WitnessID(0)], witness.0[first_output]);
+
+    let mut bytes = vec![];
+    export::write_wtns(&wires, &mut bytes).unwrap();
+
+    assert_eq!(&bytes[..4], b"wtns");
+    // magic, version, sections, two section headers, header content, one
+    assert_eq!(bytes.len(), 12 + 2 * 12 + 40 + 32 * (wires.len() + 1));
+}
+
+#[test]
+fn test_import_circom_r1cs() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    let mut r1cs = vec![];
+    export::write_circom_r1cs(&circuit, &mut r1cs).unwrap();
+
+    let witness = execute_first_step(circuit.clone());
+    let mut wtns = vec![];
+    export::write_wtns(
+        &export::to_wire_order(&circuit, &witness).unwrap(),
+        &mut wtns,
+    )
+    .unwrap();
+
+    let sym = "1,1,0,main.out[0]\n2,-1,0,main.removed\n";
+    let names = import::read_sym(sym.as_bytes()).unwrap();
+    assert_eq!(names[&1], "main.out[0]");
+
+    let mut imported = import::read_circom_r1cs::<F>(&mut r1cs.as_slice())
+        .unwrap()
+        .into_structure(&names);
+    assert_eq!(
+        imported.program.public_inputs.len(),
+        program.public_inputs.len()
+    );
+
+    let imported_program = imported.compile().unwrap();
+    assert_eq!(
+        imported_program.r1cs_constraints.len(),
+        program.r1cs_constraints.len()
+    );
+
+    let witness = import::read_wtns::<F>(&mut wtns.as_slice()).unwrap();
+    let step = imported.make_step(&witness).unwrap();
+
+    let mut cs = TestConstraintSystem::<F>::new();
+    step.prove(cs.namespace(|| "prove")).unwrap();
+    assert!(cs.is_satisfied());
+}
+
+#[cfg(feature = "ark")]
+#[test]
+fn test_ark_constraint_synthesizer() {
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem};
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+    circuit.compile().unwrap();
+
+    let cs = ConstraintSystem::<AF>::new_ref();
+    circuit.clone().generate_constraints(cs.clone()).unwrap();
+    assert_eq!(cs.num_constraints(), program.r1cs_constraints.len());
+    assert_eq!(
+        cs.num_instance_variables(),
+        1 + program.public_inputs.len() + program.public_outputs.len()
+    );
+
+    let witness = execute_first_step(circuit.clone());
+    let cs = ConstraintSystem::<AF>::new_ref();
+    crate::ArkCircuit {
+        structure: circuit,
+        witness,
+    }
+    .generate_constraints(cs.clone())
+    .unwrap();
+    assert!(cs.is_satisfied().unwrap());
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_nova_step_circuit() {
+    use bellpepper_core::num::AllocatedNum;
+    use nova_snark::traits::circuit::StepCircuit;
+    use std::sync::Arc;
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+    circuit.compile().unwrap();
+
+    let witness = execute_first_step(circuit.clone());
+    let step_circuit =
+        crate::NoirStepCircuit::new(Arc::new(circuit.clone())).with_witness(witness.clone());
+
+    let mut cs = TestConstraintSystem::<F>::new();
+    let z_in = circuit
+        .program
+        .public_inputs
+        .iter()
+        .map(|id| AllocatedNum::alloc(cs.namespace(|| format!("z{}", id.0)), || Ok(witness.0[id])))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let z_out = step_circuit.synthesize(&mut cs, &z_in).unwrap();
+    assert!(cs.is_satisfied());
+
+    let outputs: Vec<F> = circuit
+        .program
+        .public_outputs
+        .iter()
+        .map(|id| witness.0[id])
+        .collect();
+    assert_eq!(
+        z_out
+            .iter()
+            .map(|z| z.get_value().unwrap())
+            .collect::<Vec<_>>(),
+        outputs
+    );
+}
+
+#[cfg(feature = "sonobe")]
+#[test]
+fn test_sonobe_fcircuit() {
+    use ark_ff::PrimeField as _;
+    use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar, R1CSVar};
+    use ark_relations::r1cs::ConstraintSystem;
+    use folding_schemes::frontend::FCircuit;
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+    circuit.compile().unwrap();
+
+    let witness = execute_first_step(circuit.clone());
+    let to_ark = |ids: &std::collections::BTreeSet<WitnessID>| -> Vec<AF> {
+        ids.iter()
+            .map(|id| AF::from_le_bytes_mod_order(witness.0[id].to_repr().as_ref()))
+            .collect()
+    };
+    let z_0 = to_ark(&circuit.program.public_inputs);
+    let external_inputs = to_ark(&circuit.program.private_inputs);
+
+    let fcircuit = <CircuitStructure<F> as FCircuit<AF>>::new(circuit.clone()).unwrap();
+    let z_1 = fcircuit
+        .step_native(0, z_0.clone(), external_inputs.clone())
+        .unwrap();
+    assert_eq!(z_1, to_ark(&circuit.program.public_outputs));
+
+    let cs = ConstraintSystem::<AF>::new_ref();
+    let alloc = |values: &[AF]| Vec::<FpVar<AF>>::new_witness(cs.clone(), || Ok(values.to_vec()));
+    let z_1_var = fcircuit
+        .generate_step_constraints(
+            cs.clone(),
+            0,
+            alloc(&z_0).unwrap(),
+            alloc(&external_inputs).unwrap(),
+        )
+        .unwrap();
+    assert!(cs.is_satisfied().unwrap());
+    assert_eq!(
+        z_1_var
+            .iter()
+            .map(|var| var.value().unwrap())
+            .collect::<Vec<_>>(),
+        z_1
+    );
+}
+
+#[cfg(feature = "sonobe")]
+#[test]
+fn test_sonobe_fold_with() {
+    use ark_bn254::{constraints::GVar, Bn254, G1Projective};
+    use ark_ff::PrimeField as _;
+    use ark_grumpkin::{constraints::GVar as GVar2, Projective as G2Projective};
+    use folding_schemes::{
+        commitment::{kzg::KZG, pedersen::Pedersen},
+        folding::{hypernova, nova},
+        transcript::poseidon::poseidon_canonical_config,
+        FoldingScheme,
+    };
+
+    use crate::{fold_with, FoldingBackend, PerBackend};
+
+    type Nova = nova::Nova<
+        G1Projective,
+        GVar,
+        G2Projective,
+        GVar2,
+        CircuitStructure<F>,
+        KZG<'static, Bn254>,
+        Pedersen<G2Projective>,
+        false,
+    >;
+    type HyperNova = hypernova::HyperNova<
+        G1Projective,
+        GVar,
+        G2Projective,
+        GVar2,
+        CircuitStructure<F>,
+        KZG<'static, Bn254>,
+        Pedersen<G2Projective>,
+        1,
+        1,
+        false,
+    >;
+
+    fn verify<FS: FoldingScheme<G1Projective, G2Projective, CircuitStructure<F>>>(
+        params: &FS::VerifierParam,
+        folding: &FS,
+        z_0: Vec<AF>,
+    ) -> Vec<AF> {
+        let (running, incoming, cyclefold) = folding.instances();
+        FS::verify(
+            params.clone(),
+            z_0,
+            folding.state(),
+            AF::from(HINT_PATHS.len() as u64),
+            running,
+            incoming,
+            cyclefold,
+        )
+        .unwrap();
+        folding.state()
+    }
+
+    let (structure, start_state, _) = invert_chain();
+    let structure = (*structure).clone();
+    let to_ark = |io: IO<F>| -> Vec<AF> {
+        io.0.iter()
+            .map(|x| AF::from_le_bytes_mod_order(x.to_repr().as_ref()))
+            .collect()
+    };
+    let z_0 = to_ark(start_state.clone());
+    let external_inputs = || {
+        HINT_PATHS
+            .into_iter()
+            .map(|path| to_ark(read_io::<F, _>(path).unwrap()))
+    };
+    let expected = to_ark(IO(start_state.0.iter().map(|x| x + F::from(4)).collect()));
+
+    let mut rng = ark_std::test_rng();
+    let poseidon_config = poseidon_canonical_config::<AF>();
+    let nova_params = Nova::preprocess(
+        &mut rng,
+        &nova::PreprocessorParam::new(poseidon_config.clone(), structure.clone()),
+    )
+    .unwrap();
+    let hypernova_params = HyperNova::preprocess(
+        &mut rng,
+        &hypernova::PreprocessorParam::new(poseidon_config, structure.clone()),
+    )
+    .unwrap();
+
+    let params = PerBackend::Nova(nova_params.clone());
+    let folded = fold_with::<_, _, Nova, HyperNova, _>(
+        &params,
+        structure.clone(),
+        z_0.clone(),
+        external_inputs(),
+        &mut rng,
+    )
+    .unwrap();
+    assert_eq!(folded.backend(), FoldingBackend::Nova);
+    let PerBackend::Nova(folding) = folded else {
+        unreachable!()
+    };
+    assert_eq!(verify(&nova_params.1, &folding, z_0.clone()), expected);
+
+    let params = PerBackend::HyperNova(hypernova_params.clone());
+    let folded = fold_with::<_, _, Nova, HyperNova, _>(
+        &params,
+        structure,
+        z_0.clone(),
+        external_inputs(),
+        &mut rng,
+    )
+    .unwrap();
+    assert_eq!(folded.backend(), FoldingBackend::HyperNova);
+    let PerBackend::HyperNova(folding) = folded else {
+        unreachable!()
+    };
+    assert_eq!(verify(&hypernova_params.1, &folding, z_0), expected);
+}
+
+#[cfg(feature = "solidity")]
+#[test]
+fn test_solidity_verifier() {
+    use ark_ff::PrimeField as _;
+    use folding_schemes::{
+        folding::nova::PreprocessorParam, transcript::poseidon::poseidon_canonical_config,
+        FoldingScheme,
+    };
+
+    use crate::{
+        export::{
+            decide_chain, decider_params, decode_calldata, encode_calldata, encode_words,
+            solidity_verifier, verifier_key, verify_decided_chain, ChainNova,
+        },
+        fold_steps,
+    };
+
+    let (mut structure, _, _) = invert_circuit();
+    structure.compile().unwrap();
+    let to_ark = |io: IO<F>| -> Vec<AF> {
+        io.0.iter()
+            .map(|x| AF::from_le_bytes_mod_order(x.to_repr().as_ref()))
+            .collect()
+    };
+    let start_state = read_io::<F, _>(INPUT_PATHS[0]).unwrap();
+    let external_inputs = HINT_PATHS
+        .into_iter()
+        .map(|path| to_ark(read_io::<F, _>(path).unwrap()));
+
+    let mut rng = ark_std::test_rng();
+    let nova_params = ChainNova::<F>::preprocess(
+        &mut rng,
+        &PreprocessorParam::new(poseidon_canonical_config::<AF>(), structure.clone()),
+    )
+    .unwrap();
+    let folded = fold_steps::<_, _, ChainNova<F>, _>(
+        &nova_params,
+        structure.clone(),
+        to_ark(start_state.clone()),
+        external_inputs,
+        &mut rng,
+    )
+    .unwrap();
+
+    let (prover_params, verifier_params) = decider_params(&mut rng, nova_params, &folded).unwrap();
+    let (proof, public_io) = decide_chain(&mut rng, prover_params, folded).unwrap();
+    assert_eq!(public_io.num_steps, HINT_PATHS.len() as u64);
+    assert_eq!(public_io.z_0, start_state.0);
+    let expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(4)).collect();
+    assert_eq!(public_io.z_i, expected);
+    assert!(verify_decided_chain(verifier_params.clone(), &proof, &public_io).unwrap());
+
+    let mut wrong_io = public_io.clone();
+    wrong_io.z_i[0] += F::from(1);
+    assert!(!verify_decided_chain(verifier_params.clone(), &proof, &wrong_io).unwrap_or(false));
+
+    let state_len = structure.program.public_inputs.len();
+    let contract = solidity_verifier(&verifier_key(verifier_params, state_len));
+    assert!(contract.contains("contract NovaDecider"));
+    assert!(contract.contains("function verifyNovaProof"));
+
+    let calldata = encode_calldata(&proof, &public_io).unwrap();
+    let (words, decoded_io) = decode_calldata::<F>(&calldata, state_len).unwrap();
+    assert_eq!(decoded_io, public_io);
+    assert_eq!(encode_words(&words, &decoded_io), calldata);
+    assert!(decode_calldata::<F>(&calldata, state_len + 1).is_err());
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_nova_scotia() {
+    use crate::nova_scotia::{create_public_params, create_recursive_circuit, verify};
+    use nova_snark::provider::{Bn256EngineKZG, GrumpkinEngine};
+    use std::sync::Arc;
+
+    type E1 = Bn256EngineKZG;
+    type E2 = GrumpkinEngine;
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+    circuit.compile().unwrap();
+    let structure = Arc::new(circuit);
+
+    let start_state: IO<u128> = read(INPUT_PATHS[0]);
+    let start_state: Vec<F> = start_state.0.iter().map(|x| F::from_u128(*x)).collect();
+    let private_inputs = HINT_PATHS
+        .iter()
+        .map(|path| {
+            let input: IO<String> = read(path);
+            IO(input
+                .0
+                .iter()
+                .map(|x| F::from_str_vartime(x).unwrap())
+                .collect())
+        })
+        .collect();
+
+    let pp = create_public_params::<E1, E2>(structure.clone()).unwrap();
+    let recursive_snark =
+        create_recursive_circuit::<E1, E2, AF>(structure, start_state.clone(), private_inputs, &pp)
+            .unwrap();
+
+    let state = verify(&recursive_snark, &pp, HINT_PATHS.len(), &start_state).unwrap();
+    let expected: Vec<F> = start_state.iter().map(|x| x + F::from(4)).collect();
+    assert_eq!(state, expected);
+}
+
+#[cfg(feature = "nova")]
+type NovaE1 = nova_snark::provider::Bn256EngineKZG;
+#[cfg(feature = "nova")]
+type NovaE2 = nova_snark::provider::GrumpkinEngine;
+
+/// The compiled invert circuit, its start state and its executed steps
+#[cfg(feature = "nova")]
+#[allow(clippy::type_complexity)]
+fn invert_chain() -> (
+    std::sync::Arc<CircuitStructure<F>>,
+    IO<F>,
+    Vec<Result<(ExecutionResult<F>, Witness<F>, IO<F>), Error>>,
+) {
+    let (mut circuit, public_input, hints) = invert_circuit();
+    circuit.compile().unwrap();
+    let structure = std::sync::Arc::new(circuit);
+
+    let start_state: IO<F> = read_io(INPUT_PATHS[0]).unwrap();
+    let steps =
+        execute_steps::<F, AF>(structure.clone(), public_input, 0, hints.into_iter()).collect();
+
+    (structure, start_state, steps)
+}
+
+/// Execute and fold the steps of the invert circuit, returning the public
+/// parameters, the recursive proof and the start state
+#[cfg(feature = "nova")]
+fn prove_invert() -> (
+    crate::nova_scotia::NoirPublicParams<NovaE1, NovaE2>,
+    crate::nova_scotia::NoirRecursiveSNARK<NovaE1, NovaE2>,
+    IO<F>,
+) {
+    use crate::{nova_scotia::create_public_params, prove::prove_steps};
+
+    let (structure, start_state, steps) = invert_chain();
+    let pp = create_public_params::<NovaE1, NovaE2>(structure.clone()).unwrap();
+    let recursive_snark = prove_steps(structure, &pp, steps).unwrap();
+
+    (pp, recursive_snark, start_state)
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_proving_config() {
+    use crate::prove::{CommitmentScheme, ProvingConfig};
+
+    for commitment in [CommitmentScheme::Ipa, CommitmentScheme::Kzg] {
+        let (structure, start_state, steps) = invert_chain();
+        let chain = ProvingConfig::default()
+            .with_commitment(commitment)
+            .prove(structure, steps)
+            .unwrap();
+        assert_eq!(chain.commitment(), commitment);
+
+        let state = chain.verify(HINT_PATHS.len(), &start_state.0).unwrap();
+        let expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(4)).collect();
+        assert_eq!(state, expected);
+    }
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_prove_steps() {
+    let (pp, recursive_snark, start_state) = prove_invert();
+
+    let state = crate::nova_scotia::verify(&recursive_snark, &pp, HINT_PATHS.len(), &start_state.0)
+        .unwrap();
+    let expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(4)).collect();
+    assert_eq!(state, expected);
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_state_digest() {
+    use crate::{
+        nova_scotia::{create_public_params_with, verify},
+        prove::prove_steps_with,
+        NoirStepCircuit, StateDigest,
+    };
+    use nova_snark::traits::circuit::StepCircuit;
+
+    let (structure, start_state, steps) = invert_chain();
+    let circuit = NoirStepCircuit::new(structure).with_state_digest();
+    assert_eq!(circuit.arity(), 1);
+
+    let pp = create_public_params_with::<NovaE1, NovaE2>(&circuit).unwrap();
+    let recursive_snark = prove_steps_with(&circuit, &pp, steps).unwrap();
+
+    let digest = StateDigest::default();
+    let z0 = [digest.hash(&start_state.0)];
+    assert_eq!(circuit.z(&start_state.0), z0);
+
+    let state = verify(&recursive_snark, &pp, HINT_PATHS.len(), &z0).unwrap();
+    let expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(4)).collect();
+    assert_eq!(state, [digest.hash(&expected)]);
+
+    assert!(verify(&recursive_snark, &pp, HINT_PATHS.len(), &start_state.0).is_err());
+    assert_ne!(
+        digest.hash(&[F::from(0)]),
+        digest.hash(&[F::from(0), F::from(0)])
+    );
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_input_commitment() {
+    use crate::{
+        nova_scotia::{create_public_params_with, verify},
+        prove::prove_steps_with,
+        NoirStepCircuit, StateDigest,
+    };
+
+    let (structure, start_state, _) = invert_chain();
+    let io_profile = structure.program.io.clone();
+    let digest = StateDigest::default();
+
+    let mut execution =
+        UnexecutedCircuit::new(0, start_state.make_witness(&io_profile), structure.clone())
+            .with_input_commitment(digest.clone());
+
+    let mut steps = vec![];
+    let mut commitments = vec![];
+    for path in HINT_PATHS {
+        let hint = read_io::<F, _>(path).unwrap();
+        let (result, witness) = execution
+            .execute_ref::<AF, _, _>(
+                hint.make_witness(&io_profile),
+                &mut NoOracle,
+                &Bn254BlackBoxSolver,
+            )
+            .unwrap();
+
+        let randomness = result.input_randomness.unwrap();
+        assert_eq!(
+            result.input_commitment,
+            Some(digest.commit(&hint.0, randomness))
+        );
+        // the commitment hides the input
+        assert_ne!(result.input_commitment, Some(digest.hash(&hint.0)));
+        commitments.push(result.input_commitment.unwrap());
+        let next = IO(result.public_output.0.values().cloned().collect());
+        steps.push(Ok((result, witness, next)));
+    }
+
+    let circuit = NoirStepCircuit::new(structure).with_input_commitment();
+    let pp = create_public_params_with::<NovaE1, NovaE2>(&circuit).unwrap();
+    let recursive_snark = prove_steps_with(&circuit, &pp, steps).unwrap();
+
+    let z0 = circuit.z(&start_state.0);
+    assert_eq!(z0.len(), start_state.0.len() + 1);
+
+    let state = verify(&recursive_snark, &pp, HINT_PATHS.len(), &z0).unwrap();
+    let mut expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(4)).collect();
+    expected.push(digest.chain(&commitments));
+    assert_eq!(state, expected);
+
+    commitments.reverse();
+    assert_ne!(state.last(), Some(&digest.chain(&commitments)));
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_prove_chains() {
+    use crate::{
+        nova_scotia::create_public_params,
+        prove::{prove_chains, verify_chains},
+    };
+
+    let (structure, start_state, _) = invert_chain();
+    let pp = create_public_params::<NovaE1, NovaE2>(structure.clone()).unwrap();
+
+    let chains = (0..2).map(|_| invert_chain().2);
+    let mut proofs = prove_chains(structure, &pp, chains).unwrap();
+    assert_eq!(proofs[0].start_state, start_state.0);
+
+    let start_states = vec![start_state.0.clone(); 2];
+    let expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(4)).collect();
+    assert_eq!(
+        verify_chains(&pp, &proofs, &start_states).unwrap(),
+        [expected.clone(), expected]
+    );
+
+    let mut wrong_states = start_states.clone();
+    wrong_states[1][0] += F::from(1);
+    assert!(verify_chains(&pp, &proofs, &wrong_states).is_err());
+    assert!(verify_chains(&pp, &proofs, &start_states[..1]).is_err());
+
+    proofs[1].start_state = wrong_states[1].clone();
+    assert!(verify_chains(&pp, &proofs, &wrong_states).is_err());
+}
+
+#[cfg(feature = "nova")]
+#[test]
+fn test_compressed_snark() {
+    use crate::prove::{
+        finalize, read_artifact, setup_compression, verify_compressed, write_artifact,
+        ArtifactKind, NoirCompressedSNARK, NoirVerifierKey,
+    };
+    use nova_snark::{
+        provider::{hyperkzg, ipa_pc},
+        spartan::snark::RelaxedR1CSSNARK,
+    };
+
+    type S1 = RelaxedR1CSSNARK<NovaE1, hyperkzg::EvaluationEngine<NovaE1>>;
+    type S2 = RelaxedR1CSSNARK<NovaE2, ipa_pc::EvaluationEngine<NovaE2>>;
+
+    let (pp, recursive_snark, start_state) = prove_invert();
+
+    let (pk, vk) = setup_compression::<_, _, S1, S2>(&pp).unwrap();
+    let proof = finalize(&pp, &pk, &recursive_snark).unwrap();
+
+    let state = verify_compressed(&proof, &vk, HINT_PATHS.len(), &start_state.0).unwrap();
+    let expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(4)).collect();
+    assert_eq!(state, expected);
+
+    assert!(verify_compressed(&proof, &vk, HINT_PATHS.len() + 1, &start_state.0).is_err());
+
+    // stored and loaded by another verifier
+    let mut proof_bytes = vec![];
+    write_artifact(&mut proof_bytes, ArtifactKind::CompressedSNARK, &proof).unwrap();
+    let mut vk_bytes = vec![];
+    write_artifact(&mut vk_bytes, ArtifactKind::VerifierKey, &vk).unwrap();
+
+    let proof: NoirCompressedSNARK<NovaE1, NovaE2, S1, S2> =
+        read_artifact(proof_bytes.as_slice(), ArtifactKind::CompressedSNARK).unwrap();
+    let vk: NoirVerifierKey<NovaE1, NovaE2, S1, S2> =
+        read_artifact(vk_bytes.as_slice(), ArtifactKind::VerifierKey).unwrap();
+    assert_eq!(
+        verify_compressed(&proof, &vk, HINT_PATHS.len(), &start_state.0).unwrap(),
+        expected
+    );
+
+    assert!(matches!(
+        read_artifact::<NoirVerifierKey<NovaE1, NovaE2, S1, S2>>(
+            proof_bytes.as_slice(),
+            ArtifactKind::VerifierKey
+        ),
+        Err(Error::InvalidArtifact(_))
+    ));
+    proof_bytes[4] += 1;
+    assert!(matches!(
+        read_artifact::<NoirCompressedSNARK<NovaE1, NovaE2, S1, S2>>(
+            proof_bytes.as_slice(),
+            ArtifactKind::CompressedSNARK
+        ),
+        Err(Error::UnsupportedArtifactVersion(2))
+    ));
+}
+
+#[cfg(feature = "halo2")]
+#[test]
+fn test_halo2_circuit() {
+    use halo2_proofs::dev::MockProver;
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+
+    let witness = execute_first_step(circuit.clone());
+    let halo2_circuit = crate::NoirHalo2Circuit::new(&circuit)
+        .with_witness(&witness)
+        .unwrap();
+    let instance = halo2_circuit.instance(&witness).unwrap();
+
+    let k = (halo2_circuit.layout.rows.len() + 10)
+        .next_power_of_two()
+        .trailing_zeros();
+    let prover = MockProver::run(k, &halo2_circuit, vec![instance]).unwrap();
+    prover.assert_satisfied();
+}
+
+#[test]
+fn test_partition() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+    let witness = execute_first_step(circuit.clone());
+
+    let parts = circuit.partition(2).unwrap();
+    assert!(parts.len() > 1);
+
+    let mut next_input: Option<Vec<F>> = None;
+    for part in &parts {
+        let step = part.make_step(&witness).unwrap();
+
+        let mut cs = TestConstraintSystem::<F>::new();
+        step.prove(cs.namespace(|| "prove")).unwrap();
+        assert!(cs.is_satisfied());
+
+        let io = &part.structure.program;
+        let values = |ids: &std::collections::BTreeSet<WitnessID>| -> Vec<F> {
+            ids.iter().map(|id| step.witness.0[id]).collect()
+        };
+        if let Some(input) = next_input {
+            assert_eq!(values(&io.public_inputs), input);
+        }
+        next_input = Some(values(&io.public_outputs));
+    }
+
+    let outputs: Vec<F> = program
+        .public_outputs
+        .iter()
+        .map(|id| witness.0[id])
+        .collect();
+    assert_eq!(next_input.unwrap(), outputs);
+}
+
+#[test]
+fn test_specialize() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+
+    // fix y[0] = 1, so the first output is fixed to 3 as well
+    let y0 = *circuit.program.public_inputs.iter().next().unwrap();
+    circuit
+        .specialize(&[(y0, F::one())].into_iter().collect())
+        .unwrap();
+    assert_eq!(circuit.program.public_inputs.len(), 1);
+    assert_eq!(circuit.program.public_outputs.len(), 1);
+
+    let program = circuit.compile().unwrap();
+
+    let ids = |ids: &std::collections::BTreeSet<WitnessID>| ids.iter().cloned().collect::<Vec<_>>();
+    let public_input =
+        IOLayout::make_witness(&ids(&circuit.program.public_inputs), &IO(vec![F::from(2)]))
+            .unwrap();
+    let private_input: IO<String> = read(HINT_PATHS[0]);
+    let private_input = IOLayout::make_witness(
+        &ids(&circuit.program.private_inputs),
+        &IO(private_input
+            .0
+            .iter()
+            .map(|x| F::from_str_vartime(x).unwrap())
+            .collect()),
+    )
+    .unwrap();
+
+    let (_, witness, _) = execute_step::<F, AF>(circuit, public_input, private_input, 0).unwrap();
+
+    let step = Step { witness, program };
+    let mut cs = TestConstraintSystem::<F>::new();
+    step.prove(cs.namespace(|| "prove")).unwrap();
+    assert!(cs.is_satisfied());
+}
+
+#[test]
+fn test_compile_limits() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (_, program) = compile::<F, AF>(noir_circuit.clone()).unwrap();
+    let num_constraints = program.r1cs_constraints.len();
+
+    let limits = |max_constraints, max_witnesses| CompileLimits {
+        max_constraints,
+        max_witnesses,
+    };
+
+    assert!(compile_with_limits::<F, AF>(
+        noir_circuit.clone(),
+        limits(Some(num_constraints), None)
+    )
+    .is_ok());
+    assert!(matches!(
+        compile_with_limits::<F, AF>(
+            noir_circuit.clone(),
+            limits(Some(num_constraints - 1), None)
+        ),
+        Err(Error::LimitExceeded {
+            what: "constraints",
+            ..
+        })
+    ));
+    assert!(matches!(
+        compile_with_limits::<F, AF>(noir_circuit, limits(None, Some(1))),
+        Err(Error::LimitExceeded {
+            what: "witnesses",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_program_digest() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (structure, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    assert_eq!(structure.digest, Some(program_digest(&program)));
+    structure.check_program(&program).unwrap();
+
+    let mut other = program.clone();
+    other.io.public_inputs.clear();
+    assert!(matches!(
+        structure.check_program(&other),
+        Err(Error::DigestMismatch { .. })
+    ));
+
+    let mut uncompiled = structure.clone();
+    uncompiled.r1cs = None;
+    uncompiled.digest = None;
+    assert_eq!(uncompiled.digest().unwrap(), program_digest(&program));
+}
+
+#[test]
+fn test_constraint_check() {
+    // Note: the R1CS of a structure that is not compiled is generated once
+    let (mut structure, public_input, hints) = invert_circuit();
+    structure.r1cs = None;
+
+    let mut circuit = UnexecutedCircuit::new(0, public_input, structure).with_constraint_check();
+    for hint in hints {
+        let (result, _) = circuit
+            .execute_ref::<AF, _, _>(hint, &mut NoOracle, &Bn254BlackBoxSolver)
+            .unwrap();
+        assert_eq!(result.stats.constraints, None);
+    }
+    assert_eq!(circuit.iteration_number, 2);
+}
+
+#[test]
+fn test_unsatisfiable_gate() {
+    let gate = |add_terms: Vec<(F, WitnessID)>, constant: u64| AcirArithGate {
+        mul_terms: vec![],
+        add_terms,
+        constant_term: -F::from(constant),
+    };
+    let io = IOProfile {
+        public_inputs: [WitnessID(1)].into_iter().collect(),
+        private_inputs: Default::default(),
+        public_outputs: [WitnessID(1)].into_iter().collect(),
+        private_outputs: Default::default(),
+    };
+
+    // 0 = 1
+    let mut structure = CircuitStructure::<F>::from_gates(
+        vec![gate(vec![(F::from(1), WitnessID(1))], 0), gate(vec![], 1)],
+        io.clone(),
+    );
+    assert!(matches!(
+        structure.compile(),
+        Err(Error::UnsatisfiableGate {
+            gate_index: 1,
+            opcode_index: 1,
+            message: None,
+        })
+    ));
+
+    // w0 = 2 and w0 = 3
+    let mut structure = CircuitStructure::<F>::from_gates(
+        vec![
+            gate(vec![(F::from(1), WitnessID(0))], 2),
+            gate(vec![(F::from(1), WitnessID(0))], 3),
+        ],
+        io,
+    );
+    assert!(matches!(
+        structure.compile(),
+        Err(Error::UnsatisfiableGate { .. })
+    ));
+}
+
+#[test]
+fn test_memory_across_lowering() {
+    use crate::{
+        load::UnsupportedProgramError,
+        lowering::{check_memory_segments, LoweredOpcode},
+        memory::{MemoryAccess, MemoryBlock},
+    };
+    use acvm::acir::circuit::opcodes::BlockType;
+
+    let variable = |id| AcirArithGate {
+        mul_terms: vec![],
+        add_terms: vec![(F::from(1), WitnessID(id))],
+        constant_term: F::from(0),
+    };
+    let lowered = |position| LoweredOpcode::<F> {
+        position,
+        lowering: "test".to_string(),
+        opcode: serde_json::Value::Null,
+        gates: vec![],
+        data: serde_json::Value::Null,
+    };
+
+    // mem = [w0]; lowered opcodes at 2 and 5; w2 = mem[w1] at 3
+    let mut block = MemoryBlock::<F>::new(0, BlockType::Memory, 1, vec![WitnessID(0)]);
+    block.accesses.push(MemoryAccess {
+        position: 3,
+        is_write: false,
+        index: variable(1),
+        value: variable(2),
+    });
+    let memory = vec![block];
+
+    assert!(check_memory_segments(&memory, &[lowered(0), lowered(5)]).is_ok());
+    assert!(matches!(
+        check_memory_segments(&memory, &[lowered(0), lowered(2), lowered(5)]),
+        Err(UnsupportedProgramError::MemoryAcrossLowering(0, 2))
+    ));
+}
+
+#[test]
+fn test_brillig_call_from_other_opcode() {
+    use crate::brillig::BrilligCall;
+    use acvm::acir::{acir_field::GenericFieldElement, circuit::Opcode};
+
+    let opcode: Opcode<GenericFieldElement<AF>> = Opcode::AssertZero(Default::default());
+    assert!(matches!(
+        BrilligCall::from_opcode(0, &opcode),
+        Err(Error::BrilligError(_))
+    ));
+}
+
+#[test]
+fn test_program_without_functions() {
+    use crate::load::UnsupportedProgramError;
+    use acvm::acir::{acir_field::GenericFieldElement, circuit::Program};
+
+    let program: Program<GenericFieldElement<AF>> = Program {
+        functions: vec![],
+        unconstrained_functions: vec![],
+    };
+    assert!(matches!(
+        CircuitStructure::<F>::from_program(program, Default::default()),
+        Err(UnsupportedProgramError::NoFunctions)
+    ));
+}
+
+#[test]
+fn test_execute_ref_retry() {
+    let (circuit, public_input, hints) = invert_circuit();
+    let io_profile = circuit.program.io.clone();
+    let mut circuit = UnexecutedCircuit::new(0, public_input.clone(), circuit);
+
+    // x * y = 1 fails with the wrong inverses
+    let wrong_hint = IO(vec![F::from(2), F::from(2)]).make_witness(&io_profile);
+    assert!(circuit
+        .execute_ref::<AF, _, _>(wrong_hint, &mut NoOracle, &Bn254BlackBoxSolver)
+        .is_err());
+    assert_eq!(circuit.iteration_number, 0);
+    assert_eq!(circuit.public_input.0, public_input.0);
+
+    circuit
+        .execute_ref::<AF, _, _>(hints[0].clone(), &mut NoOracle, &Bn254BlackBoxSolver)
+        .unwrap();
+    assert_eq!(circuit.iteration_number, 1);
+}
+
+#[test]
+fn test_pipelined_steps() {
+    let (circuit, public_input, hints) = invert_circuit();
+    let io_profile = circuit.program.io.clone();
+
+    let mut sequential = UnexecutedCircuit::new(0, public_input.clone(), circuit.clone());
+    let pipelined = execute_steps::<F, AF>(circuit, public_input, 0, hints.clone().into_iter());
+
+    for (hint, step) in hints.into_iter().zip(pipelined) {
+        let (_, witness, next_input) = step.unwrap();
+        let (_, expected) = sequential
+            .execute_ref::<AF, _, _>(hint, &mut NoOracle, &Bn254BlackBoxSolver)
+            .unwrap();
+
+        assert_eq!(witness.0, expected.0);
+        assert_eq!(
+            next_input.make_witness(&io_profile).0,
+            sequential.public_input.0
+        );
+    }
+}
+
+#[test]
+fn test_execute_steps_to_dir() {
+    let (circuit, public_input, hints) = invert_circuit();
+
+    let dir = std::env::temp_dir().join("noir_ivc_execute_steps_to_dir");
+    let streamed: Vec<_> = execute_steps_to_dir::<F, AF, _>(
+        circuit.clone(),
+        public_input.clone(),
+        0,
+        hints.clone().into_iter(),
+        &dir,
+    )
+    .unwrap()
+    .collect::<Result<_, _>>()
+    .unwrap();
+    let expected: Vec<_> = execute_steps::<F, AF>(circuit, public_input, 0, hints.into_iter())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(streamed.len(), expected.len());
+    for ((result, next), (_, witness, expected_next)) in streamed.iter().zip(&expected) {
+        let path = dir.join(format!("witness_{}.json", result.iteration_number));
+        let written: Witness<F> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written.0, witness.0);
+        assert_eq!(next.0, expected_next.0);
+    }
+}
+
+#[test]
+fn test_run_to_end() {
+    let (circuit, public_input, hints) = invert_circuit();
+
+    let (_, _, expected) = execute_steps::<F, AF>(
+        circuit.clone(),
+        public_input.clone(),
+        0,
+        hints.clone().into_iter(),
+    )
+    .last()
+    .unwrap()
+    .unwrap();
+
+    for verify in [false, true] {
+        let (final_io, last) =
+            run_to_end::<F, AF>(circuit.clone(), public_input.clone(), hints.clone(), verify)
+                .unwrap();
+        assert_eq!(final_io.0, expected.0);
+        assert_eq!(last.unwrap().iteration_number, hints.len() as u64 - 1);
+    }
+}
+
+#[test]
+fn test_verify_chain() {
+    let (circuit, public_input, hints) = invert_circuit();
+    let io_profile = circuit.program.io.clone();
+
+    let mut results: Vec<ExecutionResult<F>> =
+        execute_steps::<F, AF>(circuit, public_input, 0, hints.into_iter())
+            .map(|step| step.unwrap().0)
+            .collect();
+    verify_chain(&results, &io_profile).unwrap();
+
+    let tampered = results[1].public_input.0.values_mut().next().unwrap();
+    *tampered += F::from(1);
+    assert!(matches!(
+        verify_chain(&results, &io_profile),
+        Err(Error::BrokenChain {
+            iteration_number: 1,
+            ..
+        })
+    ));
+
+    results.swap(0, 1);
+    assert!(verify_chain(&results, &io_profile).is_err());
+}
+
+#[test]
+fn test_replay() {
+    let (circuit, public_input, mut hints) = invert_circuit();
+
+    let (mut result, witness, _) =
+        execute_step::<F, AF>(circuit.clone(), public_input, hints.swap_remove(0), 0).unwrap();
+
+    assert_eq!(
+        replay::<F, AF>(circuit.clone(), &result).unwrap().0,
+        witness.0
+    );
+
+    let tampered = result.public_output.0.values_mut().next().unwrap();
+    *tampered += F::from(1);
+    assert!(matches!(
+        replay::<F, AF>(circuit, &result),
+        Err(Error::ReplayMismatch("public output"))
+    ));
+}
+
+#[test]
+fn test_transcript() {
+    let (circuit, public_input, hints) = invert_circuit();
+
+    let run = || {
+        let circuit =
+            UnexecutedCircuit::new(0, public_input.clone(), circuit.clone()).with_transcript();
+        let results: Vec<ExecutionResult<F>> = execute_steps_from::<F, AF, _, _>(
+            circuit,
+            hints.clone().into_iter(),
+            NoOracle,
+            Bn254BlackBoxSolver,
+        )
+        .map(|step| step.unwrap().0)
+        .collect();
+        Transcript::from_results(&results)
+    };
+
+    let transcript = run();
+    assert_eq!(transcript.0.len(), hints.len());
+    assert_eq!(transcript.first_divergence(&run()), None);
+
+    let mut other = run();
+    other.0[1].io = "0".repeat(64);
+    assert_eq!(transcript.first_divergence(&other), Some(1));
+}
+
+#[test]
+fn test_batch_executor() {
+    let (circuit, public_input, hints) = invert_circuit();
+
+    let steps: Vec<_> = execute_steps::<F, AF>(
+        circuit.clone(),
+        public_input.clone(),
+        0,
+        hints.clone().into_iter(),
+    )
+    .map(Result::unwrap)
+    .collect();
+
+    // the inputs of the chain, solved independently
+    let inputs: Vec<_> = steps
+        .iter()
+        .zip(hints)
+        .map(|((result, _, _), hint)| (result.public_input.clone(), hint))
+        .collect();
+    let results = BatchExecutor::new(circuit)
+        .with_threads(2)
+        .execute::<AF>(&inputs);
+
+    assert_eq!(results.len(), steps.len());
+    for (result, (_, witness, _)) in results.into_iter().zip(&steps) {
+        assert_eq!(result.unwrap().1 .0, witness.0);
+    }
+}
+
+#[test]
+fn test_execute_steps_send() {
+    fn assert_send<T: Send>(_: &T) {}
+
+    let (circuit, public_input, hints) = invert_circuit();
+
+    let steps = execute_steps::<F, AF>(circuit, public_input, 0, hints.into_iter());
+    assert_send(&steps);
+
+    let iterations = std::thread::spawn(move || {
+        steps
+            .map(|step| step.unwrap().0.iteration_number)
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(iterations, vec![0, 1]);
+}
+
+#[test]
+fn test_execute_n_steps() {
+    use ff::Field;
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+    let io_profile = circuit.program.io.clone();
+
+    let first_input: IO<F> = read_io(INPUT_PATHS[0]).unwrap();
+
+    // the hints are the inverses of the public input, i.e. of the previous output
+    let steps: Vec<_> = execute_n_steps::<F, AF>(
+        circuit,
+        first_input.make_witness(&io_profile),
+        3,
+        |_, previous| {
+            let input = match previous {
+                Some(previous) => previous.public_output.0.values().cloned().collect(),
+                None => first_input.0.clone(),
+            };
+            IO(input.iter().map(|x| x.invert().unwrap()).collect()).make_witness(&io_profile)
+        },
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+
+    assert_eq!(steps.len(), 3);
+    assert_eq!(steps[2].0.iteration_number, 2);
+
+    let (result, witness, _) = &steps[0];
+    assert!(result.stats.opcodes > 0);
+    assert!(result.stats.constraints.is_some());
+    assert_eq!(result.stats.witnesses, witness.0.len());
+}
+
+#[test]
+fn test_next_input_hook() {
+    let (circuit, public_input, mut hints) = invert_circuit();
+    let io_profile = circuit.program.io.clone();
+
+    // repeat the first step
+    let circuit = UnexecutedCircuit::new(0, public_input.clone(), circuit)
+        .with_next_input(|result| result.public_input.clone());
+
+    let steps: Vec<_> = execute_steps_from::<F, AF, _, _>(
+        circuit,
+        vec![hints.swap_remove(0); 3].into_iter(),
+        NoOracle,
+        Bn254BlackBoxSolver,
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+
+    for (result, _, next_input) in steps {
+        assert_eq!(result.public_input.0, public_input.0);
+        assert_eq!(next_input.make_witness(&io_profile).0, public_input.0);
+    }
+}
+
+#[test]
+fn test_checkpoint_resume() {
+    let (circuit, public_input, hints) = invert_circuit();
+
+    let mut first = UnexecutedCircuit::new(0, public_input, circuit.clone());
+    first
+        .execute_ref::<AF, _, _>(hints[0].clone(), &mut NoOracle, &Bn254BlackBoxSolver)
+        .unwrap();
+
+    let saved = serde_json::to_string(&first.checkpoint().unwrap()).unwrap();
+    let checkpoint: ExecutionCheckpoint<F> = serde_json::from_str(&saved).unwrap();
+
+    let mut other = circuit.clone().with_encoding(R1CSEncoding::Quadratic);
+    other.compile().unwrap();
+    assert!(matches!(
+        UnexecutedCircuit::resume(checkpoint.clone(), other),
+        Err(Error::DigestMismatch { .. })
+    ));
+
+    let mut resumed = UnexecutedCircuit::resume(checkpoint, circuit).unwrap();
+    let (result, _) = resumed
+        .execute_ref::<AF, _, _>(hints[1].clone(), &mut NoOracle, &Bn254BlackBoxSolver)
+        .unwrap();
+    assert_eq!(result.iteration_number, 1);
+}
+
+#[test]
+fn test_progress() {
+    use std::sync::{Arc, Mutex};
+
+    let (circuit, public_input, hints) = invert_circuit();
+    let constraints = circuit.num_constraints().unwrap();
+    let witnesses = circuit.program.num_witness as usize;
+
+    let reported = Arc::new(Mutex::new(vec![]));
+    let circuit = UnexecutedCircuit::new(0, public_input, circuit).with_progress({
+        let reported = reported.clone();
+        move |progress| reported.lock().unwrap().push(progress.clone())
+    });
+
+    execute_steps_from::<F, AF, _, _>(circuit, hints.into_iter(), NoOracle, Bn254BlackBoxSolver)
+        .for_each(|step| {
+            step.unwrap();
+        });
+
+    let reported = reported.lock().unwrap();
+    assert_eq!(
+        reported.iter().map(|p| p.index).collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+    assert_eq!(reported[0].constraints, Some(constraints));
+    assert_eq!(reported[0].witnesses, witnesses);
+}
+
+#[test]
+fn test_cancellation() {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    let (circuit, public_input, mut hints) = invert_circuit();
+
+    let cancel = Arc::new(AtomicBool::new(true));
+    let circuit = UnexecutedCircuit::new(0, public_input, circuit).with_cancellation(cancel);
+
+    let steps: Vec<_> = execute_steps_from::<F, AF, _, _>(
+        circuit,
+        vec![hints.swap_remove(0); 3].into_iter(),
+        NoOracle,
+        Bn254BlackBoxSolver,
+    )
+    .collect();
+
+    assert_eq!(steps.len(), 1);
+    assert!(matches!(steps[0], Err(Error::Cancelled)));
+}
+
+#[test]
+fn test_solve_timeout() {
+    use std::time::Duration;
+
+    let (circuit, public_input, mut hints) = invert_circuit();
+    let hint = hints.swap_remove(0);
+
+    let mut circuit =
+        UnexecutedCircuit::new(0, public_input, circuit).with_max_solve_duration(Duration::ZERO);
+    assert!(matches!(
+        circuit.execute_ref::<AF, _, _>(hint.clone(), &mut NoOracle, &Bn254BlackBoxSolver),
+        Err(Error::SolveTimeout(_))
+    ));
+
+    circuit.max_solve_duration = Some(Duration::from_secs(60));
+    circuit
+        .execute_ref::<AF, _, _>(hint, &mut NoOracle, &Bn254BlackBoxSolver)
+        .unwrap();
+    assert_eq!(circuit.iteration_number, 1);
+}
+
+#[test]
+fn test_solve_timeout_in_brillig() {
+    use acvm::acir::{
+        acir_field::GenericFieldElement,
+        brillig::Opcode as BrilligOpcode,
+        circuit::{
+            brillig::{BrilligBytecode, BrilligOutputs},
+            Circuit as ACVMCircuit, Opcode, Program,
+        },
+        native_types::Witness as ACVMWitness,
+    };
+    use std::time::Duration;
+
+    // w0 = f() where f never returns
+    let program: Program<GenericFieldElement<AF>> = Program {
+        functions: vec![ACVMCircuit {
+            current_witness_index: 1,
+            opcodes: vec![Opcode::BrilligCall {
+                id: 0,
+                inputs: vec![],
+                outputs: vec![BrilligOutputs::Simple(ACVMWitness(0))],
+                predicate: None,
+            }],
+            ..Default::default()
+        }],
+        unconstrained_functions: vec![BrilligBytecode {
+            bytecode: vec![BrilligOpcode::Jump { location: 0 }],
+        }],
+    };
+    let structure = CircuitStructure::<F>::from_program(program, Default::default()).unwrap();
+
+    let mut circuit = UnexecutedCircuit::new(0, Witness(Default::default()), structure)
+        .with_max_solve_duration(Duration::from_millis(100));
+    assert!(matches!(
+        circuit.execute_ref::<AF, _, _>(
+            Witness(Default::default()),
+            &mut NoOracle,
+            &Bn254BlackBoxSolver
+        ),
+        Err(Error::SolveTimeout(_))
+    ));
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_execute_steps_async() {
+    use tokio_stream::StreamExt;
+
+    let (circuit, public_input, hints) = invert_circuit();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    // the stream must be created within the runtime
+    let steps: Vec<_> = runtime.block_on(async {
+        crate::execute_steps_async::<F, AF>(circuit, public_input, 0, hints.into_iter())
+            .collect()
+            .await
+    });
+
+    assert_eq!(steps.len(), 2);
+    assert!(steps.iter().all(|step| step.is_ok()));
+}
+
+#[test]
+fn test_solve_stalled() {
+    // w0 * w1 = w2 cannot be solved from w2
+    let gate = AcirArithGate {
+        mul_terms: vec![(F::from(1), WitnessID(0), WitnessID(1))],
+        add_terms: vec![(-F::from(1), WitnessID(2))],
+        constant_term: F::from(0),
+    };
+    let io = IOProfile {
+        public_inputs: [WitnessID(2)].into_iter().collect(),
+        private_inputs: Default::default(),
+        public_outputs: [WitnessID(0)].into_iter().collect(),
+        private_outputs: Default::default(),
+    };
+    let mut structure = CircuitStructure::<F>::from_gates(vec![gate], io);
+    structure.compile().unwrap();
+    structure.source_map.insert(
+        0,
+        SourceLocation {
+            path: "src/main.nr".to_string(),
+            line: 3,
+            column: 5,
+        },
+    );
+
+    let public_input = Witness([(WitnessID(2), F::from(6))].into_iter().collect());
+    let private_input = Witness(Default::default());
+
+    let Err(error) = execute_step::<F, AF>(structure, public_input, private_input, 0) else {
+        panic!("expected a stalled solve");
+    };
+
+    assert!(error.to_string().contains("at src/main.nr:3"));
+    assert!(matches!(
+        error,
+        Error::SolveStalled {
+            opcode_index: 0,
+            ref missing_witnesses,
+            ..
+        } if *missing_witnesses == vec![0, 1]
+    ));
+}
+
+#[test]
+fn test_validate_input() {
+    let gate = AcirArithGate {
+        mul_terms: vec![(F::from(1), WitnessID(0), WitnessID(1))],
+        add_terms: vec![(-F::from(1), WitnessID(2))],
+        constant_term: F::from(0),
+    };
+    let io = IOProfile {
+        public_inputs: [WitnessID(0)].into_iter().collect(),
+        private_inputs: [WitnessID(1)].into_iter().collect(),
+        public_outputs: [WitnessID(2)].into_iter().collect(),
+        private_outputs: Default::default(),
+    };
+    let structure = CircuitStructure::<F>::from_gates(vec![gate], io);
+
+    let public_input = Witness([(WitnessID(0), F::from(2))].into_iter().collect());
+    let private_input = Witness([(WitnessID(1), F::from(3))].into_iter().collect());
+    structure
+        .validate_input(&public_input, &private_input)
+        .unwrap();
+    assert!(structure.is_valid_input(&public_input, &private_input));
+
+    let wrong_input = Witness([(WitnessID(2), F::from(3))].into_iter().collect());
+    let mismatch = structure
+        .validate_input(&public_input, &wrong_input)
+        .unwrap_err();
+    assert!(structure.is_valid_public_input(&public_input));
+    assert!(!structure.is_valid_private_input(&wrong_input));
+    assert!(!structure.is_valid_input(&public_input, &wrong_input));
+    assert!(mismatch.missing_public.is_empty());
+    assert_eq!(mismatch.missing_private, vec![WitnessID(1)]);
+    assert_eq!(mismatch.unexpected_private, vec![WitnessID(2)]);
+    assert_eq!(
+        mismatch.to_string(),
+        "missing private inputs [1], unexpected private inputs [2]"
+    );
+
+    assert!(matches!(
+        execute_step::<F, AF>(structure, public_input, wrong_input, 0),
+        Err(Error::InputMismatch(_))
+    ));
+}
+
+#[test]
+fn test_load_errors() {
+    assert!(matches!(
+        load_program_from_file::<AF, _>("test_folder/missing.json"),
+        Err(Error::IOError(_))
+    ));
+    assert!(matches!(
+        load_circuit_from_file::<AF, _>("test_folder/missing.json", false),
+        Err(Error::IOError(_))
+    ));
+    assert!(matches!(
+        load_abi_from_file("test_folder/missing.json"),
+        Err(Error::IOError(_))
+    ));
+}
+
+#[test]
+fn test_curve() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (mut structure, program) = compile::<F, AF>(noir_circuit).unwrap();
+
+    assert_eq!(structure.curve, Some(Curve::Bn254));
+    assert_eq!(program.curve, Curve::Bn254.to_string());
+    structure.check_program(&program).unwrap();
+
+    // compiled and executed over another field
+    let other_circuit = acvm::acir::circuit::Program {
+        functions: vec![Default::default()],
+        unconstrained_functions: vec![],
+    };
+    assert!(matches!(
+        compile::<F, ark_pallas::Fr>(other_circuit),
+        Err(Error::CurveMismatch {
+            expected: Some(Curve::Bn254),
+            found: Some(Curve::Pallas),
+        })
+    ));
+    let mut other_program = program.clone();
+    other_program.curve = Curve::Pallas.to_string();
+    assert!(structure.check_program(&other_program).is_err());
+
+    let mut execution = UnexecutedCircuit::new(0, Witness(Default::default()), structure.clone());
+    assert!(matches!(
+        execution.execute_ref::<ark_pallas::Fr, _, _>(
+            Witness(Default::default()),
+            &mut NoOracle,
+            &crate::StubbedBlackBoxSolver
+        ),
+        Err(Error::CurveMismatch { .. })
+    ));
+
+    structure.program.curve = Curve::Pallas.to_string();
+    assert!(structure.check_curve().is_err());
+
+    structure.curve = Some(Curve::Pallas);
+    assert!(matches!(
+        structure.check_program(&program),
+        Err(Error::CurveMismatch {
+            expected: Some(Curve::Pallas),
+            found: Some(Curve::Bn254),
+        })
+    ));
+}
+
+#[test]
+fn test_program_set() {
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let set = ProgramSet::<F>::compile(vec![noir_circuit.clone(), noir_circuit]).unwrap();
+    assert_eq!(set.len(), 2);
+
+    // Note: the state of the invert circuit starts at [1, 2] and grows by 2
+    // every step, so the first step runs circuit 1 and selects no circuit
+    let start_state: IO<F> = read_io(INPUT_PATHS[0]).unwrap();
+    let steps: Vec<_> = set
+        .execute_steps::<AF>(
+            start_state,
+            0,
+            HINT_PATHS
+                .into_iter()
+                .map(|path| read_io::<F, _>(path).unwrap()),
+        )
+        .collect();
+
+    assert_eq!(steps.len(), 2);
+    let (index, result, _, _) = steps[0].as_ref().unwrap();
+    assert_eq!(*index, 1);
+    assert_eq!(result.iteration_number, 0);
+    assert!(matches!(steps[1], Err(Error::InvalidProgramCounter(1))));
+
+    assert!(matches!(
+        ProgramSet::<F>::new(vec![]),
+        Err(Error::InvalidProgramSet(_))
+    ));
+}
+
+/// A program of state `[pc, x]`, computing `pc + pc_step` and
+/// `x_scale * x + x_offset`
+#[cfg(feature = "supernova")]
+fn affine_program(
+    pc_step: i128,
+    x_scale: i128,
+    x_offset: i128,
+) -> acvm::acir::circuit::Program<acvm::acir::acir_field::GenericFieldElement<AF>> {
+    use acvm::acir::{
+        acir_field::GenericFieldElement,
+        circuit::{Circuit as ACVMCircuit, Opcode, Program, PublicInputs},
+        native_types::{Expression, Witness as ACVMWitness},
+    };
+
+    let value = GenericFieldElement::<AF>::from;
+    let witnesses = |ids: &[u32]| ids.iter().map(|id| ACVMWitness(*id)).collect();
+
+    // w2 = w0 + pc_step; w3 = x_scale * w1 + x_offset
+    let opcodes = vec![
+        Opcode::AssertZero(Expression {
+            mul_terms: vec![],
+            linear_combinations: vec![(value(1), ACVMWitness(2)), (value(-1), ACVMWitness(0))],
+            q_c: value(-pc_step),
+        }),
+        Opcode::AssertZero(Expression {
+            mul_terms: vec![],
+            linear_combinations: vec![
+                (value(1), ACVMWitness(3)),
+                (value(-x_scale), ACVMWitness(1)),
+            ],
+            q_c: value(-x_offset),
+        }),
+    ];
+    Program {
+        functions: vec![ACVMCircuit {
+            current_witness_index: 3,
+            opcodes,
+            public_parameters: PublicInputs(witnesses(&[0, 1])),
+            return_values: PublicInputs(witnesses(&[2, 3])),
+            ..Default::default()
+        }],
+        unconstrained_functions: vec![],
+    }
+}
+
+#[cfg(feature = "supernova")]
+#[test]
+fn test_program_set_supernova() {
+    use crate::{create_set_public_params, prove_set_steps, verify_set_steps};
+    use arecibo::traits::Engine;
+
+    type E1 = arecibo::provider::Bn256EngineKZG;
+    type SF = <E1 as Engine>::Scalar;
+
+    // Note: circuit 0 doubles x and selects circuit 1, which adds 3 to x and
+    // selects circuit 0 again
+    let set =
+        ProgramSet::<SF>::compile(vec![affine_program(1, 2, 0), affine_program(-1, 1, 3)]).unwrap();
+    let start_state = vec![SF::from(0), SF::from(5)];
+    let steps: Vec<_> = set
+        .execute_steps::<AF>(
+            IO(start_state.clone()),
+            0,
+            std::iter::repeat_with(|| IO(vec![])).take(3),
+        )
+        .collect();
+    let indices: Vec<usize> = steps.iter().map(|step| step.as_ref().unwrap().0).collect();
+    assert_eq!(indices, [0, 1, 0]);
+
+    let pp = create_set_public_params::<E1>(&set);
+    let proof = prove_set_steps(&set, &pp, &start_state, steps).unwrap();
+    let state = verify_set_steps(&proof, &pp, &start_state).unwrap();
+
+    // [0, 5] -> [1, 10] -> [0, 13] -> [1, 26]
+    assert_eq!(state, [SF::from(1), SF::from(26)]);
+}
+
+#[test]
+fn test_execution_plan() {
+    use ff::Field;
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let plan = ExecutionPlan::<F>::compile(
+        noir_circuit.clone(),
+        noir_circuit.clone(),
+        noir_circuit.clone(),
+    )
+    .unwrap();
+
+    // Note: the state reaches [5, 6] after the two steps of the hints
+    let start_state: IO<F> = read_io(INPUT_PATHS[0]).unwrap();
+    let [init_input, step_input] = HINT_PATHS.map(|path| read_io::<F, _>(path).unwrap());
+    let final_input = IO(vec![
+        F::from(5).invert().unwrap(),
+        F::from(6).invert().unwrap(),
+    ]);
+    let steps: Vec<_> = plan
+        .execute::<AF>(
+            IO(start_state.0.clone()),
+            init_input,
+            std::iter::once(step_input),
+            final_input,
+        )
+        .map(|step| step.unwrap())
+        .collect();
+
+    let phases: Vec<Phase> = steps.iter().map(|(phase, ..)| *phase).collect();
+    assert_eq!(phases, [Phase::Init, Phase::Step, Phase::Final]);
+    let results: Vec<_> = steps.iter().map(|(_, res, ..)| res.clone()).collect();
+    assert_eq!(results[0].iteration_number, 0);
+    verify_chain(&results, &plan.step.program.io).unwrap();
+
+    let expected: Vec<F> = start_state.0.iter().map(|x| x + F::from(6)).collect();
+    assert_eq!(steps[2].3 .0, expected);
+
+    let (mut circuit, _) = compile::<F, AF>(noir_circuit).unwrap();
+    let mut broken = circuit.clone();
+    broken.program.public_outputs.pop_first();
+    assert!(matches!(
+        ExecutionPlan::new(broken, circuit.clone(), circuit.clone()),
+        Err(Error::InvalidExecutionPlan(_))
+    ));
+
+    // with the ABI, the state must also keep its shape: [Field; 2] is
+    // returned, so two Field parameters do not take it
+    let abi = load_abi_from_file(NOIR_PROGRAM_PATH).unwrap();
+    let mut split_abi = abi.clone();
+    let parameters = split_abi["parameters"].as_array_mut().unwrap();
+    let y = parameters.iter().position(|p| p["name"] == "y").unwrap();
+    let field = |name: &str| serde_json::json!({"name": name, "type": {"kind": "field"}, "visibility": "public"});
+    parameters.splice(y..=y, [field("y0"), field("y1")]);
+
+    circuit.set_abi(&abi).unwrap();
+    let mut split = circuit.clone();
+    split.set_abi(&split_abi).unwrap();
+
+    assert!(ExecutionPlan::new(circuit.clone(), circuit.clone(), circuit.clone()).is_ok());
+    assert!(matches!(
+        ExecutionPlan::new(circuit.clone(), split.clone(), circuit.clone()),
+        Err(Error::InvalidExecutionPlan(_))
+    ));
+    assert!(matches!(
+        ExecutionPlan::new(circuit.clone(), circuit, split),
+        Err(Error::InvalidExecutionPlan(_))
+    ));
+}
+
+#[test]
+fn test_inspect() {
+    let metadata = inspect_file::<F, AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    assert!(metadata.unsupported.is_none(), "{:?}", metadata.unsupported);
+    assert!(metadata.abi_mismatch.is_none());
+    assert_eq!(metadata.constrained_functions, 1);
+    assert!(metadata.opcodes["AssertZero"] > 0);
+
+    let noir_circuit = load_program_from_file::<AF, _>(NOIR_PROGRAM_PATH).unwrap();
+    let (circuit, program) = compile::<F, AF>(noir_circuit).unwrap();
+    let r1cs = metadata.r1cs.as_ref().unwrap();
+    assert_eq!(r1cs.constraints, circuit.num_constraints().unwrap());
+    assert_eq!(r1cs.witnesses, program.num_witness);
+    assert!(!metadata.parameters.is_empty());
+
+    assert!(metadata.to_string().ends_with("Supported: yes"));
+    let json = serde_json::to_value(&metadata).unwrap();
+    assert_eq!(json["r1cs"]["constraints"], r1cs.constraints);
+}
@@ -0,0 +1,96 @@
+//! Canonical hashes of executed steps.
+//!
+//! Two runs of the same chain must solve the same witnesses. The hashes only
+//! depend on the witness values, taken in witness order as their canonical
+//! representation, so transcripts of different machines can be compared to
+//! detect nondeterminism or a faulty field conversion.
+
+use ff::PrimeField;
+use ivc_program::witness::Witness;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::ExecutionResult;
+
+fn update_witness<F: PrimeField>(hasher: &mut Sha256, witness: &Witness<F>) {
+    hasher.update((witness.0.len() as u64).to_le_bytes());
+    for (id, value) in &witness.0 {
+        hasher.update(id.0.to_le_bytes());
+        hasher.update(value.to_repr().as_ref());
+    }
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Hashes of a step, recorded with `UnexecutedCircuit::with_transcript`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub iteration_number: u64,
+    /// Of the witness solved by ACVM, in the ACIR numbering
+    pub solved_witness: String,
+    /// Of the public and private inputs and outputs
+    pub io: String,
+}
+
+impl TranscriptEntry {
+    pub(crate) fn new<F: PrimeField>(
+        result: &ExecutionResult<F>,
+        solved_witness: &Witness<F>,
+    ) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"noir-ivc solved witness v1");
+        update_witness(&mut hasher, solved_witness);
+        let solved_witness = hex(hasher);
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"noir-ivc io v1");
+        for witness in [
+            &result.public_input,
+            &result.private_input,
+            &result.public_output,
+            &result.private_output,
+        ] {
+            update_witness(&mut hasher, witness);
+        }
+
+        Self {
+            iteration_number: result.iteration_number,
+            solved_witness,
+            io: hex(hasher),
+        }
+    }
+}
+
+/// The entries of a run, in step order
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transcript(pub Vec<TranscriptEntry>);
+
+impl Transcript {
+    /// Collect the entries recorded in `results`
+    pub fn from_results<'a, F: 'a>(
+        results: impl IntoIterator<Item = &'a ExecutionResult<F>>,
+    ) -> Self {
+        Self(
+            results
+                .into_iter()
+                .filter_map(|result| result.transcript.clone())
+                .collect(),
+        )
+    }
+
+    /// The first step the transcripts disagree on, `None` if they match on
+    /// their common steps
+    pub fn first_divergence(&self, other: &Transcript) -> Option<u64> {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .find(|(entry, other)| entry != other)
+            .map(|(entry, _)| entry.iteration_number)
+    }
+}